@@ -1,24 +1,60 @@
+use std::future::Future;
+
 use futures::StreamExt;
 use rig::{
     agent::Agent,
-    completion::{AssistantContent, CompletionModel},
+    completion::{AssistantContent, Chat, CompletionModel},
     message::Message,
     streaming::StreamingChat,
 };
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 
-pub async fn cli_chatbot<M: CompletionModel + Clone + Unpin>(
-    chatbot: Agent<M>,
+use crate::history::ChatHistory;
+use crate::mcp_adaptor::McpManager;
+
+// Follow-up turns the agent may take to act on tool results before the user speaks again
+const MAX_TOOL_ROUNDS: usize = 8;
+
+pub async fn cli_chatbot<M, F, Fut>(
+    mut chatbot: Agent<M>,
+    mut mcp_manager: McpManager,
+    mut history: ChatHistory,
+    reload: F,
 ) -> anyhow::Result<()>
 where
+    M: CompletionModel + Clone + Unpin,
     Agent<M>: StreamingChat<M::StreamingResponse>,
     M::StreamingResponse: Clone + Unpin,
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<(Agent<M>, McpManager)>>,
 {
-    let mut chat_log = vec![];
-
     let mut output = BufWriter::new(tokio::io::stdout());
     let mut input = BufReader::new(tokio::io::stdin());
-    output.write_all(b"Enter :q to quit\n").await?;
+    output
+        .write_all(
+            b"Enter :q to quit, :servers to list MCP servers, :reload to reload configuration, :clear to start a new conversation\n",
+        )
+        .await?;
+    if !history.messages.is_empty() {
+        output
+            .write_all(
+                format!(
+                    "Resumed a conversation of {} messages\n",
+                    history.messages.len()
+                )
+                .as_bytes(),
+            )
+            .await?;
+        output_missing_tools(&chatbot, &history, &mut output).await?;
+    }
+    // Let the user know up front when the tool set is degraded
+    for (name, error) in &mcp_manager.failed {
+        output_error(
+            format!("MCP server '{name}' failed to start, its tools are unavailable: {error}"),
+            &mut output,
+        )
+        .await?;
+    }
     loop {
         output.write_all(b"\x1b[32muser>\x1b[0m ").await?;
         // Flush stdout to ensure the prompt appears before input
@@ -31,56 +67,233 @@ where
         if input == ":q" {
             break;
         }
-        match chatbot.stream_chat(input, chat_log.clone()).await {
-            Ok(mut response) => {
-                tracing::info!(%input);
-                chat_log.push(Message::user(input));
-                stream_output_agent_start(&mut output).await?;
-                let mut message_buf = String::new();
-                while let Some(message) = response.next().await {
-                    match message {
-                        Ok(AssistantContent::Text(text)) => {
-                            message_buf.push_str(&text.text);
-                            output_agent(&text.text, &mut output).await?;
-                        }
-                        Ok(AssistantContent::ToolCall(tool_call)) => {
-                            chat_log.push(Message::assistant(format!(
-                                "Calling tool: {} with args: {}",
-                                tool_call.function.name, tool_call.function.arguments
-                            )));
-                            let result = chatbot
-                                .tools
-                                .call(
-                                    &tool_call.function.name,
-                                    tool_call.function.arguments.to_string(),
-                                )
-                                .await;
-                            match result {
-                                Ok(tool_call_result) => {
-                                    stream_output_agent_finished(&mut output).await?;
-                                    stream_output_toolcall(&tool_call_result, &mut output).await?;
-                                    stream_output_agent_start(&mut output).await?;
-                                    chat_log.push(Message::user(tool_call_result));
-                                }
-                                Err(e) => {
-                                    output_error(e, &mut output).await?;
-                                }
-                            }
+        // Show which MCP integrations are live
+        if input == ":servers" {
+            output_servers(&mcp_manager, &mut output).await?;
+            continue;
+        }
+        // Re-read the config and rebuild servers, tools and index without exiting
+        if input == ":reload" {
+            match reload().await {
+                Ok((new_chatbot, new_manager)) => {
+                    let summary = new_manager.describe_changes(&mcp_manager);
+                    let previous_manager = std::mem::replace(&mut mcp_manager, new_manager);
+                    previous_manager.shutdown().await;
+                    chatbot = new_chatbot;
+                    tracing::info!(%summary, "Reloaded configuration");
+                    output
+                        .write_all(format!("Reloaded configuration: {summary}\n").as_bytes())
+                        .await?;
+                    output.flush().await?;
+                    output_missing_tools(&chatbot, &history, &mut output).await?;
+                }
+                Err(error) => {
+                    output_error(
+                        format!("Reload failed, keeping the current configuration: {error}"),
+                        &mut output,
+                    )
+                    .await?;
+                }
+            }
+            continue;
+        }
+        if input == ":clear" {
+            history.clear();
+            save_history(&history, &mut output).await?;
+            output.write_all(b"Started a new conversation\n").await?;
+            output.flush().await?;
+            continue;
+        }
+        // Keep streaming while the agent calls tools, so it can answer with their results
+        let mut prompt = input.to_string();
+        let mut rounds = 0;
+        loop {
+            match stream_turn(&chatbot, &prompt, &mut history, &mut output).await? {
+                TurnOutcome::CalledTools(tool_results) if rounds < MAX_TOOL_ROUNDS => {
+                    rounds += 1;
+                    prompt = tool_results;
+                }
+                TurnOutcome::CalledTools(_) => {
+                    output_error(
+                        format!("Stopped after {MAX_TOOL_ROUNDS} rounds of tool calls"),
+                        &mut output,
+                    )
+                    .await?;
+                    break;
+                }
+                TurnOutcome::Finished => break,
+                TurnOutcome::Unavailable(error) if rounds > 0 => {
+                    output_error(error, &mut output).await?;
+                    break;
+                }
+                TurnOutcome::Unavailable(error) => {
+                    // Fall back to a single complete response for providers that can't stream
+                    tracing::warn!(%error, "Streaming failed, falling back to non-streaming chat");
+                    match chatbot.chat(input, history.messages.clone()).await {
+                        Ok(response) => {
+                            tracing::info!(%input);
+                            history.messages.push(Message::user(input));
+                            stream_output_agent_start(&mut output).await?;
+                            output_agent(&response, &mut output).await?;
+                            stream_output_agent_finished(&mut output).await?;
+                            history.messages.push(Message::assistant(response));
                         }
                         Err(error) => {
                             output_error(error, &mut output).await?;
                         }
                     }
+                    break;
+                }
+            }
+        }
+        save_history(&history, &mut output).await?;
+    }
+
+    Ok(())
+}
+
+/// How a streamed turn ended
+enum TurnOutcome {
+    /// The agent answered without calling tools
+    Finished,
+    /// The agent called tools, their results are the prompt for the next turn
+    CalledTools(String),
+    /// The request couldn't be streamed
+    Unavailable(String),
+}
+
+// Stream one agent reply, printing text as it arrives and running the tools it calls
+async fn stream_turn<M>(
+    chatbot: &Agent<M>,
+    prompt: &str,
+    history: &mut ChatHistory,
+    output: &mut BufWriter<tokio::io::Stdout>,
+) -> std::io::Result<TurnOutcome>
+where
+    M: CompletionModel + Clone + Unpin,
+    Agent<M>: StreamingChat<M::StreamingResponse>,
+    M::StreamingResponse: Clone + Unpin,
+{
+    let mut response = match chatbot.stream_chat(prompt, history.messages.clone()).await {
+        Ok(response) => response,
+        Err(error) => return Ok(TurnOutcome::Unavailable(error.to_string())),
+    };
+    tracing::info!(%prompt);
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        log_selected_tools(chatbot, prompt).await;
+    }
+    history.messages.push(Message::user(prompt));
+    stream_output_agent_start(output).await?;
+    let mut message_buf = String::new();
+    let mut tool_results = Vec::new();
+    while let Some(message) = response.next().await {
+        match message {
+            Ok(AssistantContent::Text(text)) => {
+                message_buf.push_str(&text.text);
+                output_agent(&text.text, output).await?;
+            }
+            Ok(AssistantContent::ToolCall(tool_call)) => {
+                history.messages.push(Message::assistant(format!(
+                    "Calling tool: {} with args: {}",
+                    tool_call.function.name, tool_call.function.arguments
+                )));
+                history.record_tool(&tool_call.function.name);
+                // Mark the invocation before the (possibly slow) tool runs
+                stream_output_agent_finished(output).await?;
+                stream_output_toolcall_invoked(
+                    &tool_call.function.name,
+                    &tool_call.function.arguments,
+                    output,
+                )
+                .await?;
+                let result = chatbot
+                    .tools
+                    .call(
+                        &tool_call.function.name,
+                        tool_call.function.arguments.to_string(),
+                    )
+                    .await;
+                match result {
+                    Ok(tool_call_result) => {
+                        stream_output_toolcall(&tool_call_result, output).await?;
+                        tool_results.push(format!(
+                            "Result of {}: {tool_call_result}",
+                            tool_call.function.name
+                        ));
+                    }
+                    Err(e) => {
+                        output_error(&e, output).await?;
+                        tool_results.push(format!("{} failed: {e}", tool_call.function.name));
+                    }
                 }
-                chat_log.push(Message::assistant(message_buf));
-                stream_output_agent_finished(&mut output).await?;
+                // Resume streaming the agent's reply
+                stream_output_agent_start(output).await?;
             }
             Err(error) => {
-                output_error(error, &mut output).await?;
+                output_error(error, output).await?;
+            }
+        }
+    }
+    history.messages.push(Message::assistant(message_buf));
+    stream_output_agent_finished(output).await?;
+
+    if tool_results.is_empty() {
+        Ok(TurnOutcome::Finished)
+    } else {
+        Ok(TurnOutcome::CalledTools(tool_results.join("\n\n")))
+    }
+}
+
+// Repeats the agent's vector search for the turn, so it only runs when debug logging is on
+async fn log_selected_tools<M: CompletionModel>(chatbot: &Agent<M>, prompt: &str) {
+    for (top_k, index) in &chatbot.dynamic_tools {
+        match index.top_n_ids(prompt, *top_k).await {
+            Ok(selected) => {
+                let tools: Vec<String> = selected
+                    .into_iter()
+                    .map(|(score, name)| format!("{name} ({score:.3})"))
+                    .collect();
+                tracing::debug!(top_k, ?tools, "Selected tools for turn");
             }
+            Err(error) => tracing::debug!(%error, "Failed to look up the selected tools"),
         }
     }
+}
 
+// A failed save is reported but doesn't end the session
+async fn save_history(
+    history: &ChatHistory,
+    output: &mut BufWriter<tokio::io::Stdout>,
+) -> std::io::Result<()> {
+    if let Err(error) = history.save().await {
+        tracing::warn!(%error, "Failed to save chat history");
+        output_error(format!("Failed to save chat history: {error}"), output).await?;
+    }
+    Ok(())
+}
+
+// Tools from a resumed conversation that the current servers no longer provide
+async fn output_missing_tools<M: CompletionModel>(
+    chatbot: &Agent<M>,
+    history: &ChatHistory,
+    output: &mut BufWriter<tokio::io::Stdout>,
+) -> std::io::Result<()> {
+    let missing: Vec<&str> = history
+        .tools
+        .iter()
+        .filter(|tool| !chatbot.tools.contains(tool))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        output_error(
+            format!(
+                "Tools used earlier in this conversation are unavailable: {}",
+                missing.join(", ")
+            ),
+            output,
+        )
+        .await?;
+    }
     Ok(())
 }
 
@@ -97,6 +310,21 @@ pub async fn output_error(
     Ok(())
 }
 
+pub async fn output_servers(
+    mcp_manager: &McpManager,
+    output: &mut BufWriter<tokio::io::Stdout>,
+) -> std::io::Result<()> {
+    let statuses = mcp_manager.server_statuses().await;
+    if statuses.is_empty() {
+        output.write_all(b"No MCP servers connected\n").await?;
+    }
+    for status in statuses {
+        output.write_all(format!("{status}\n").as_bytes()).await?;
+    }
+    output.flush().await?;
+    Ok(())
+}
+
 pub async fn output_agent(
     content: impl std::fmt::Display,
     output: &mut BufWriter<tokio::io::Stdout>,
@@ -106,6 +334,21 @@ pub async fn output_agent(
     Ok(())
 }
 
+pub async fn stream_output_toolcall_invoked(
+    name: &str,
+    arguments: impl std::fmt::Display,
+    output: &mut BufWriter<tokio::io::Stdout>,
+) -> std::io::Result<()> {
+    output
+        .write_all(b"\x1b[1;33m\xF0\x9F\x9B\xA0 Calling tool: \x1b[0m")
+        .await?;
+    output
+        .write_all(format!("{name} {arguments}\n").as_bytes())
+        .await?;
+    output.flush().await?;
+    Ok(())
+}
+
 pub async fn stream_output_toolcall(
     content: impl std::fmt::Display,
     output: &mut BufWriter<tokio::io::Stdout>,
@@ -2,13 +2,29 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+pub mod agent;
+pub mod chat;
+pub mod embedding;
 pub mod mcp;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
+    pub agent: agent::AgentConfig,
+    #[serde(default)]
+    pub embedding: embedding::EmbeddingConfig,
+    #[serde(default)]
+    pub chat: chat::ChatConfig,
+    /// How many of the most relevant tools are offered to the agent each turn
+    #[serde(default = "default_tool_top_k")]
+    pub tool_top_k: usize,
     pub mcp: mcp::McpConfig,
 }
 
+fn default_tool_top_k() -> usize {
+    4
+}
+
 impl Config {
     pub async fn retrieve(path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let content = tokio::fs::read_to_string(path).await?;
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use rig::providers::openai;
+use serde::{Deserialize, Serialize};
+
+/// LLM backends the chatbot can build a client for, keyed from the environment
+/// (`OPENAI_API_KEY`, `ANTHROPIC_API_KEY`, `GEMINI_API_KEY`)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    OpenAI,
+    Anthropic,
+    Gemini,
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Provider::OpenAI => "openai",
+            Provider::Anthropic => "anthropic",
+            Provider::Gemini => "gemini",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Provider of the completion model, fixed for the session
+    #[serde(default)]
+    pub provider: Provider,
+    /// Completion model, required unless the provider is OpenAI
+    model: Option<String>,
+    /// Most tokens per response, which Anthropic requires for models it has no default for
+    pub max_tokens: Option<u64>,
+    /// System prompt given inline
+    preamble: Option<String>,
+    /// Path to a file holding the system prompt
+    preamble_file: Option<PathBuf>,
+}
+
+impl AgentConfig {
+    /// The configured completion model, or the provider's default
+    pub fn model(&self) -> anyhow::Result<&str> {
+        match (&self.model, self.provider) {
+            (Some(model), _) => Ok(model),
+            (None, Provider::OpenAI) => Ok(openai::GPT_4_1),
+            (None, provider) => anyhow::bail!("agent.model is required for provider '{provider}'"),
+        }
+    }
+
+    /// The configured system prompt, if any, read from disk when given as a file
+    pub async fn preamble(&self) -> anyhow::Result<Option<String>> {
+        match (&self.preamble, &self.preamble_file) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("agent.preamble and agent.preamble_file are mutually exclusive")
+            }
+            (Some(preamble), None) => Ok(Some(preamble.clone())),
+            (None, Some(path)) => {
+                if !path.is_file() {
+                    anyhow::bail!("agent.preamble_file '{}' does not exist", path.display());
+                }
+                let preamble = tokio::fs::read_to_string(path).await.with_context(|| {
+                    format!("Failed to read agent.preamble_file '{}'", path.display())
+                })?;
+                Ok(Some(preamble))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// JSON file the conversation is saved to after each turn and resumed from on startup
+    pub history_file: Option<PathBuf>,
+}
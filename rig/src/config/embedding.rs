@@ -0,0 +1,27 @@
+use rig::providers::openai;
+use serde::{Deserialize, Serialize};
+
+use super::agent::Provider;
+
+/// The model tool schemas are embedded with for dynamic tool selection
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmbeddingConfig {
+    /// Provider of the embedding model, fixed for the session
+    #[serde(default)]
+    pub provider: Provider,
+    /// Embedding model, required unless the provider is OpenAI
+    model: Option<String>,
+}
+
+impl EmbeddingConfig {
+    /// The configured embedding model, or the provider's default
+    pub fn model(&self) -> anyhow::Result<&str> {
+        match (&self.model, self.provider) {
+            (Some(model), _) => Ok(model),
+            (None, Provider::OpenAI) => Ok(openai::TEXT_EMBEDDING_3_LARGE),
+            (None, provider) => {
+                anyhow::bail!("embedding.model is required for provider '{provider}'")
+            }
+        }
+    }
+}
@@ -34,26 +34,29 @@ pub struct McpConfig {
 impl McpConfig {
     pub async fn create_manager(&self) -> anyhow::Result<McpManager> {
         let mut clients = HashMap::new();
-        let mut task_set = tokio::task::JoinSet::<anyhow::Result<_>>::new();
+        let mut failed = HashMap::new();
+        let mut task_set = tokio::task::JoinSet::new();
         for server in &self.server {
             let server = server.clone();
             task_set.spawn(async move {
-                let client = server.transport.start().await?;
-                anyhow::Result::Ok((server.name.clone(), client))
+                // Keep the name with the outcome so failures can be attributed
+                let result = server.transport.start().await;
+                (server.name, result)
             });
         }
         let start_up_result = task_set.join_all().await;
-        for result in start_up_result {
+        for (name, result) in start_up_result {
             match result {
-                Ok((name, client)) => {
+                Ok(client) => {
                     clients.insert(name, client);
                 }
                 Err(e) => {
-                    eprintln!("Failed to start server: {:?}", e);
+                    tracing::error!(server = %name, error = %e, "Failed to start MCP server");
+                    failed.insert(name, format!("{e:#}"));
                 }
             }
         }
-        Ok(McpManager { clients })
+        Ok(McpManager { clients, failed })
     }
 }
 
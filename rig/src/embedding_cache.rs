@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use rig::{
+    OneOrMany,
+    embeddings::{Embedding, EmbeddingModel, EmbeddingsBuilder},
+    tool::ToolSchema,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedEmbedding {
+    schema_hash: u64,
+    embeddings: Vec<Embedding>,
+}
+
+/// Tool embeddings persisted between runs, so unchanged tools are not re-embedded
+pub struct EmbeddingCache {
+    path: PathBuf,
+    entries: HashMap<String, CachedEmbedding>,
+}
+
+impl EmbeddingCache {
+    /// Load the cache, starting empty if the file is missing or unreadable
+    pub async fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!(path = %path.display(), error = %e, "Ignoring corrupt embedding cache");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self { path, entries }
+    }
+
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let content = serde_json::to_string(&self.entries)?;
+        tokio::fs::write(&self.path, content).await?;
+        Ok(())
+    }
+
+    /// Embed tool schemas, reusing cached embeddings for tools whose schema is unchanged
+    pub async fn embed_tools<M: EmbeddingModel>(
+        &mut self,
+        model: M,
+        model_id: &str,
+        schemas: Vec<ToolSchema>,
+    ) -> anyhow::Result<Vec<(ToolSchema, OneOrMany<Embedding>)>> {
+        let mut embedded = Vec::new();
+        let mut stale = Vec::new();
+        for schema in schemas {
+            let schema_hash = Self::schema_hash(model_id, &schema);
+            let cached = self
+                .entries
+                .get(&schema.name)
+                .filter(|entry| entry.schema_hash == schema_hash)
+                .and_then(|entry| OneOrMany::many(entry.embeddings.clone()).ok());
+            match cached {
+                Some(embeddings) => embedded.push((schema, embeddings)),
+                None => stale.push(schema),
+            }
+        }
+
+        tracing::info!(
+            cached = embedded.len(),
+            embedding = stale.len(),
+            "Embedding tool schemas"
+        );
+
+        if !stale.is_empty() {
+            let fresh = EmbeddingsBuilder::new(model)
+                .documents(stale)?
+                .build()
+                .await?;
+            for (schema, embeddings) in fresh {
+                self.entries.insert(
+                    schema.name.clone(),
+                    CachedEmbedding {
+                        schema_hash: Self::schema_hash(model_id, &schema),
+                        embeddings: embeddings.clone().into_iter().collect(),
+                    },
+                );
+                embedded.push((schema, embeddings));
+            }
+        }
+
+        Ok(embedded)
+    }
+
+    // A changed hash (e.g. after a toolchain upgrade) only costs a re-embed
+    fn schema_hash(model_id: &str, schema: &ToolSchema) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        model_id.hash(&mut hasher);
+        schema.name.hash(&mut hasher);
+        schema.context.to_string().hash(&mut hasher);
+        schema.embedding_docs.hash(&mut hasher);
+        hasher.finish()
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// The conversation so far, optionally persisted so a session can be resumed
+#[derive(Debug, Default)]
+pub struct ChatHistory {
+    path: Option<PathBuf>,
+    pub messages: Vec<Message>,
+    /// Tools the agent selected and called, in order of first use
+    pub tools: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedHistory {
+    messages: Vec<Message>,
+    #[serde(default)]
+    tools: Vec<String>,
+}
+
+impl ChatHistory {
+    /// Resume the conversation saved at `path`, starting empty if there is none yet.
+    /// Without a path the history lives in memory only
+    pub async fn load(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let saved = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).with_context(|| {
+                format!(
+                    "Chat history {} is corrupt, move it aside to start a new session",
+                    path.display()
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SavedHistory {
+                messages: vec![],
+                tools: vec![],
+            },
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read chat history {}", path.display()));
+            }
+        };
+        Ok(Self {
+            path: Some(path),
+            messages: saved.messages,
+            tools: saved.tools,
+        })
+    }
+
+    /// Write the history to its file, replacing the previous copy only once fully written
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let saved = SavedHistory {
+            messages: self.messages.clone(),
+            tools: self.tools.clone(),
+        };
+        let content = serde_json::to_string_pretty(&saved)?;
+        let partial = path.with_extension("json.partial");
+        tokio::fs::write(&partial, content).await?;
+        tokio::fs::rename(&partial, path).await?;
+        Ok(())
+    }
+
+    pub fn record_tool(&mut self, name: &str) {
+        if !self.tools.iter().any(|tool| tool == name) {
+            self.tools.push(name.to_string());
+        }
+    }
+
+    /// Forget the conversation, e.g. to start over without deleting the file by hand
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.tools.clear();
+    }
+}
@@ -1,14 +1,23 @@
 use rig::{
+    agent::Agent,
     client::{CompletionClient, EmbeddingsClient, ProviderClient},
-    embeddings::EmbeddingsBuilder,
-    providers::openai,
+    completion::CompletionModel,
+    providers::{anthropic, gemini, openai},
+    streaming::StreamingChat,
     vector_store::in_memory_store::InMemoryVectorStore,
 };
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+use crate::config::agent::Provider;
 pub mod chat;
 pub mod config;
+pub mod embedding_cache;
+pub mod history;
 pub mod mcp_adaptor;
 
+const CONFIG_PATH: &str = "rig/config.toml";
+const EMBEDDING_CACHE_PATH: &str = "rig/embedding_cache.json";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let file_appender = RollingFileAppender::new(
@@ -26,31 +35,137 @@ async fn main() -> anyhow::Result<()> {
         .with_ansi(false)
         .init();
 
-    let config = config::Config::retrieve("rig/config.toml").await?;
-    let openai_client = openai::Client::from_env();
-
-    let mcp_manager = config.mcp.create_manager().await?;
+    // Providers pick the client types, so they are read once; models can change on reload
+    let config = config::Config::retrieve(CONFIG_PATH).await?;
     tracing::info!(
-        "MCP Manager created, {} servers started",
-        mcp_manager.clients.len()
+        agent = %config.agent.provider,
+        embedding = %config.embedding.provider,
+        "Using providers"
     );
-    let tool_set = mcp_manager.get_tool_set().await?;
-    let embedding_model = openai_client.embedding_model(openai::TEXT_EMBEDDING_3_LARGE);
-    let embeddings = EmbeddingsBuilder::new(embedding_model.clone())
-        .documents(tool_set.schemas()?)?
-        .build()
-        .await?;
-    let store = InMemoryVectorStore::from_documents_with_id_f(embeddings, |f| {
-        tracing::info!("store tool {}", f.name);
-        f.name.clone()
-    });
-    let index = store.index(embedding_model);
-    let agent = openai_client
-        .agent(openai::GPT_4_1)
-        .dynamic_tools(4, index, tool_set)
-        .build();
-
-    chat::cli_chatbot(agent).await?;
+    // The history file is also read once, changing it takes effect on the next start
+    let history = history::ChatHistory::load(config.chat.history_file.clone()).await?;
+    match config.agent.provider {
+        Provider::OpenAI => {
+            with_embedding_client(openai::Client::from_env(), &config, history).await
+        }
+        Provider::Anthropic => {
+            with_embedding_client(anthropic::Client::from_env(), &config, history).await
+        }
+        Provider::Gemini => {
+            with_embedding_client(gemini::Client::from_env(), &config, history).await
+        }
+    }
+}
+
+async fn with_embedding_client<C>(
+    completion_client: C,
+    config: &config::Config,
+    history: history::ChatHistory,
+) -> anyhow::Result<()>
+where
+    C: CompletionClient,
+    C::CompletionModel: Clone + Unpin,
+    Agent<C::CompletionModel>:
+        StreamingChat<<C::CompletionModel as CompletionModel>::StreamingResponse>,
+    <C::CompletionModel as CompletionModel>::StreamingResponse: Clone + Unpin,
+{
+    let providers = (config.agent.provider, config.embedding.provider);
+    match config.embedding.provider {
+        Provider::OpenAI => {
+            run(
+                completion_client,
+                openai::Client::from_env(),
+                providers,
+                history,
+            )
+            .await
+        }
+        Provider::Gemini => {
+            run(
+                completion_client,
+                gemini::Client::from_env(),
+                providers,
+                history,
+            )
+            .await
+        }
+        Provider::Anthropic => anyhow::bail!(
+            "Anthropic doesn't offer embedding models, set embedding.provider to openai or gemini"
+        ),
+    }
+}
+
+async fn run<C, E>(
+    completion_client: C,
+    embedding_client: E,
+    providers: (Provider, Provider),
+    history: history::ChatHistory,
+) -> anyhow::Result<()>
+where
+    C: CompletionClient,
+    C::CompletionModel: Clone + Unpin,
+    Agent<C::CompletionModel>:
+        StreamingChat<<C::CompletionModel as CompletionModel>::StreamingResponse>,
+    <C::CompletionModel as CompletionModel>::StreamingResponse: Clone + Unpin,
+    E: EmbeddingsClient,
+    E::EmbeddingModel: Clone + Send + Sync + 'static,
+{
+    // Everything derived from the config is built here so the chat can rebuild it on reload
+    let client = &completion_client;
+    let embedder = &embedding_client;
+    let build_agent = move || async move {
+        let config = config::Config::retrieve(CONFIG_PATH).await?;
+        if (config.agent.provider, config.embedding.provider) != providers {
+            anyhow::bail!("Changing agent.provider or embedding.provider requires a restart");
+        }
+        // Resolve the preamble and models before starting servers so a bad config fails fast
+        let preamble = config.agent.preamble().await?;
+        let model = config.agent.model()?;
+        if config.tool_top_k == 0 {
+            anyhow::bail!("tool_top_k must be at least 1");
+        }
+        let embedding_model_id = config.embedding.model()?;
+
+        let mcp_manager = config.mcp.create_manager().await?;
+        tracing::info!(
+            "MCP Manager created, {} servers started, {} failed",
+            mcp_manager.clients.len(),
+            mcp_manager.failed.len()
+        );
+        let tool_set = mcp_manager.get_tool_set().await?;
+        let embedding_model = embedder.embedding_model(embedding_model_id);
+        // Only tools whose schema changed since the last run are sent to the embedding API
+        let mut embedding_cache = embedding_cache::EmbeddingCache::load(EMBEDDING_CACHE_PATH).await;
+        let embeddings = embedding_cache
+            .embed_tools(
+                embedding_model.clone(),
+                embedding_model_id,
+                tool_set.schemas()?,
+            )
+            .await?;
+        if let Err(e) = embedding_cache.save().await {
+            tracing::warn!(error = %e, "Failed to save embedding cache");
+        }
+        let store = InMemoryVectorStore::from_documents_with_id_f(embeddings, |f| {
+            tracing::info!("store tool {}", f.name);
+            f.name.clone()
+        });
+        let index = store.index(embedding_model);
+        let mut agent_builder = client.agent(model);
+        if let Some(preamble) = &preamble {
+            agent_builder = agent_builder.preamble(preamble);
+        }
+        if let Some(max_tokens) = config.agent.max_tokens {
+            agent_builder = agent_builder.max_tokens(max_tokens);
+        }
+        let agent = agent_builder
+            .dynamic_tools(config.tool_top_k, index, tool_set)
+            .build();
+        anyhow::Ok((agent, mcp_manager))
+    };
+
+    let (agent, mcp_manager) = build_agent().await?;
+    chat::cli_chatbot(agent, mcp_manager, history, build_agent).await?;
 
     Ok(())
 }
@@ -102,9 +102,115 @@ impl ToolEmbeddingDyn for McpToolAdaptor {
 
 pub struct McpManager {
     pub clients: HashMap<String, RunningService<RoleClient, ()>>,
+    /// Servers that failed to start, with the reason
+    pub failed: HashMap<String, String>,
+}
+
+/// Runtime state of a configured MCP server
+#[derive(Debug)]
+pub struct McpServerStatus {
+    pub name: String,
+    pub connected: bool,
+    pub server_info: Option<String>,
+    pub tool_count: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for McpServerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.connected { "connected" } else { "down" };
+        write!(f, "{:<20} {:<10}", self.name, status)?;
+        if let Some(tool_count) = self.tool_count {
+            write!(f, " {tool_count} tools")?;
+        }
+        if let Some(server_info) = &self.server_info {
+            write!(f, " ({server_info})")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " error: {error}")?;
+        }
+        Ok(())
+    }
 }
 
 impl McpManager {
+    /// Stop every running server
+    pub async fn shutdown(self) {
+        for (name, client) in self.clients {
+            if let Err(e) = client.cancel().await {
+                tracing::warn!(server = %name, error = %e, "Failed to stop MCP server");
+            }
+        }
+    }
+
+    /// Describe how the set of servers differs from a previous manager
+    pub fn describe_changes(&self, previous: &McpManager) -> String {
+        let added: Vec<&str> = self
+            .clients
+            .keys()
+            .filter(|name| !previous.clients.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        let removed: Vec<&str> = previous
+            .clients
+            .keys()
+            .filter(|name| !self.clients.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        let failed: Vec<&str> = self.failed.keys().map(String::as_str).collect();
+
+        let mut summary = format!("{} servers running", self.clients.len());
+        for (label, names) in [("added", added), ("removed", removed), ("failed", failed)] {
+            if !names.is_empty() {
+                summary.push_str(&format!(", {label}: {}", names.join(", ")));
+            }
+        }
+        summary
+    }
+
+    /// Query each connected server for its health and the tools it provides
+    pub async fn server_statuses(&self) -> Vec<McpServerStatus> {
+        let mut statuses = Vec::new();
+        for (name, client) in &self.clients {
+            let peer = client.peer();
+            let server_info = peer
+                .peer_info()
+                .map(|info| format!("{} {}", info.server_info.name, info.server_info.version));
+            if peer.is_transport_closed() {
+                statuses.push(McpServerStatus {
+                    name: name.clone(),
+                    connected: false,
+                    server_info,
+                    tool_count: None,
+                    error: Some("transport closed".to_string()),
+                });
+                continue;
+            }
+            let (tool_count, error) = match peer.list_all_tools().await {
+                Ok(tools) => (Some(tools.len()), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            statuses.push(McpServerStatus {
+                name: name.clone(),
+                connected: error.is_none(),
+                server_info,
+                tool_count,
+                error,
+            });
+        }
+        for (name, error) in &self.failed {
+            statuses.push(McpServerStatus {
+                name: name.clone(),
+                connected: false,
+                server_info: None,
+                tool_count: None,
+                error: Some(format!("failed to start: {error}")),
+            });
+        }
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
     pub async fn get_tool_set(&self) -> anyhow::Result<ToolSet> {
         let mut tool_set = ToolSet::default();
         let mut task = tokio::task::JoinSet::<anyhow::Result<_>>::new();
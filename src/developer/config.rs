@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Server settings loaded from a TOML file with `--config`.
+///
+/// Every field is optional, and the environment variables documented for each
+/// setting take precedence over the file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// A gitignore-style file restricting access, on top of the working directory's `.gitignore`
+    pub ignore_file: Option<PathBuf>,
+    /// Register only these tools, all of them when unset (`DEVELOPER_ENABLED_TOOLS`)
+    pub enabled_tools: Option<Vec<String>>,
+    /// Never register these tools (`DEVELOPER_DISABLED_TOOLS`)
+    pub disabled_tools: Option<Vec<String>>,
+    pub text_editor: TextEditorConfig,
+    pub shell: ShellSettings,
+    pub image: ImageSettings,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextEditorConfig {
+    /// `TEXT_EDITOR_MAX_HISTORY`
+    pub max_history: Option<usize>,
+    /// `TEXT_EDITOR_MAX_FILE_BYTES`
+    pub max_file_bytes: Option<u64>,
+    /// `TEXT_EDITOR_MAX_CHARS`
+    pub max_chars: Option<usize>,
+    /// `TEXT_EDITOR_REQUIRE_VIEW`
+    pub require_view: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShellSettings {
+    /// `SHELL_EXECUTABLE`
+    pub executable: Option<String>,
+    /// `SHELL_ARG`
+    pub arg: Option<String>,
+    /// `SHELL_TIMEOUT_SECS`
+    pub timeout_secs: Option<u64>,
+    /// `SHELL_TRIM_BLANK_LINES`
+    pub trim_blank_lines: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageSettings {
+    /// `IMAGE_MAX_WIDTH`, 0 returns images at full size
+    pub max_width: Option<u32>,
+}
+
+impl Config {
+    pub async fn retrieve(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let config: Self = toml::from_str(&content)?;
+        Ok(config)
+    }
+}
+
+/// Parse `name` from the environment, falling back to the configured value
+pub(crate) fn env_or<T: std::str::FromStr>(name: &str, configured: Option<T>) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<T>().ok())
+        .or(configured)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retrieve() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("developer.toml");
+        std::fs::write(
+            &path,
+            r#"
+ignore_file = ".developerignore"
+enabled_tools = ["text_editor", "shell"]
+
+[text_editor]
+max_history = 3
+
+[shell]
+executable = "/bin/sh"
+timeout_secs = 60
+
+[image]
+max_width = 0
+"#,
+        )
+        .unwrap();
+
+        let config = Config::retrieve(&path).await.unwrap();
+        assert_eq!(config.ignore_file, Some(PathBuf::from(".developerignore")));
+        assert_eq!(
+            config.enabled_tools,
+            Some(vec!["text_editor".to_string(), "shell".to_string()])
+        );
+        assert_eq!(config.text_editor.max_history, Some(3));
+        assert_eq!(config.text_editor.max_chars, None);
+        assert_eq!(config.shell.executable.as_deref(), Some("/bin/sh"));
+        assert_eq!(config.shell.timeout_secs, Some(60));
+        assert_eq!(config.image.max_width, Some(0));
+
+        // Typos are reported rather than silently ignored
+        std::fs::write(&path, "[shell]\ntimeout = 60\n").unwrap();
+        assert!(Config::retrieve(&path).await.is_err());
+
+        temp_dir.close().unwrap();
+    }
+}
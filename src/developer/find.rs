@@ -0,0 +1,169 @@
+use ignore::{WalkBuilder, gitignore::GitignoreBuilder};
+use rmcp::{
+    Error as McpError,
+    model::CallToolResult,
+    model::{Content, Role},
+};
+use std::path::{Path, PathBuf};
+
+use crate::developer::IgnorePatterns;
+
+// Only this many matches are returned, the count still covers every match
+const MAX_FIND_RESULTS: usize = 500;
+
+#[derive(Clone)]
+pub struct FileFinder {
+    // Gitignore patterns for file access control, shared with the other tools
+    ignore_patterns: IgnorePatterns,
+}
+
+impl Default for FileFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileFinder {
+    pub fn new() -> Self {
+        Self {
+            ignore_patterns: IgnorePatterns::new(),
+        }
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    /// List the files under `root` matching a gitignore-style glob, sorted by path.
+    /// A bare name such as `Cargo.toml` matches at any depth
+    pub async fn find(&self, root: PathBuf, pattern: String) -> Result<CallToolResult, McpError> {
+        let (matches, total) = self.find_matching(&root, &pattern)?;
+
+        let output = if matches.is_empty() {
+            format!(
+                "No files matching '{pattern}' found in {display}",
+                display = root.display()
+            )
+        } else {
+            let mut output = matches
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if total > matches.len() {
+                output.push_str(&format!(
+                    "\n... {} more matches not listed, narrow the pattern or root",
+                    total - matches.len()
+                ));
+            }
+            output
+        };
+
+        Ok(CallToolResult::success(vec![
+            Content::text(output).with_audience(vec![Role::Assistant]),
+            Content::text(format!("Found {total} files matching '{pattern}'"))
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    // The first matches in path order and the total number of matches
+    fn find_matching(&self, root: &Path, pattern: &str) -> Result<(Vec<PathBuf>, usize), McpError> {
+        if !root.is_dir() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a directory",
+                    display = root.display()
+                ),
+                None,
+            ));
+        }
+
+        // The glob is matched with gitignore rules so a bare name matches at any depth. It only
+        // selects files, unlike a walker override it doesn't bring back gitignored ones
+        let mut glob = GitignoreBuilder::new(root);
+        glob.add_line(None, pattern).map_err(|e| {
+            McpError::invalid_params(format!("Invalid glob '{pattern}': {e}"), None)
+        })?;
+        let glob = glob.build().map_err(|e| {
+            McpError::invalid_params(format!("Invalid glob '{pattern}': {e}"), None)
+        })?;
+
+        // Walk the tree without descending into ignored directories
+        let ignore_patterns = self.ignore_patterns.clone();
+        let walker = WalkBuilder::new(root)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !ignore_patterns.is_ignored(entry.path(), is_dir)
+            })
+            .build();
+
+        let mut matches: Vec<PathBuf> = walker
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter(|entry| glob.matched(entry.path(), false).is_ignore())
+            .map(|entry| entry.into_path())
+            .collect();
+        matches.sort();
+        let total = matches.len();
+        matches.truncate(MAX_FIND_RESULTS);
+        Ok((matches, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_by_glob_and_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+        std::fs::write(root.join("src/nested/Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("secret.rs"), "").unwrap();
+        // Files the repository ignores stay hidden even when the glob names them
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("target/build.rs"), "").unwrap();
+
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(&root);
+        builder.add_line(None, "secret.rs").unwrap();
+        let finder = FileFinder::new().with_ignore_patterns(builder.build().unwrap().into());
+
+        let (matches, total) = finder.find_matching(&root, "*.rs").unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(
+            matches,
+            vec![root.join("src/main.rs"), root.join("src/nested/lib.rs")]
+        );
+
+        // A bare name matches at any depth
+        let (matches, _) = finder.find_matching(&root, "Cargo.toml").unwrap();
+        assert_eq!(
+            matches,
+            vec![root.join("Cargo.toml"), root.join("src/nested/Cargo.toml")]
+        );
+
+        // A pattern with a slash is anchored to the root
+        let (matches, _) = finder.find_matching(&root, "src/*.rs").unwrap();
+        assert_eq!(matches, vec![root.join("src/main.rs")]);
+
+        let result = finder.find(root.clone(), "*.py".to_string()).await.unwrap();
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .starts_with("No files matching")
+        );
+        assert!(finder.find_matching(&root.join("Cargo.toml"), "*").is_err());
+
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+use rmcp::{
+    Error as McpError,
+    model::CallToolResult,
+    model::{Content, Role},
+};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use super::shell::{ExecuteOptions, Shell};
+
+// NUL-separated so paths with spaces or quotes come through verbatim
+const STATUS_COMMAND: &str = "git status --porcelain=v2 --branch -z";
+
+/// The state of a working tree, parsed from `git status --porcelain=v2 --branch`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GitStatus {
+    /// None on a detached HEAD
+    pub branch: Option<String>,
+    /// None before the first commit
+    pub commit: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    /// Changes recorded in the index
+    pub staged: Vec<FileChange>,
+    /// Changes in the working tree that are not staged
+    pub unstaged: Vec<FileChange>,
+    pub untracked: Vec<String>,
+    /// Paths with unresolved merge conflicts
+    pub conflicted: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub status: &'static str,
+    /// The source of a rename or copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_path: Option<String>,
+}
+
+impl GitStatus {
+    /// Parse the NUL-separated output of `git status --porcelain=v2 --branch -z`
+    pub fn parse(output: &str) -> Self {
+        let mut status = Self::default();
+        let mut records = output.split('\0').filter(|record| !record.is_empty());
+        while let Some(record) = records.next() {
+            let (kind, rest) = record.split_once(' ').unwrap_or((record, ""));
+            match kind {
+                "#" => status.parse_header(rest),
+                "1" => {
+                    // XY sub mH mI mW hH hI path
+                    let fields: Vec<&str> = rest.splitn(8, ' ').collect();
+                    if let [xy, .., path] = fields.as_slice() {
+                        status.push_change(xy, path, None);
+                    }
+                }
+                "2" => {
+                    // XY sub mH mI mW hH hI score path, followed by the original path
+                    let fields: Vec<&str> = rest.splitn(9, ' ').collect();
+                    if let [xy, .., path] = fields.as_slice() {
+                        let original_path = records.next().map(str::to_string);
+                        status.push_change(xy, path, original_path);
+                    }
+                }
+                // XY sub m1 m2 m3 mW h1 h2 h3 path
+                "u" => status
+                    .conflicted
+                    .extend(rest.splitn(10, ' ').nth(9).map(str::to_string)),
+                "?" => status.untracked.push(rest.to_string()),
+                _ => {}
+            }
+        }
+        status
+    }
+
+    fn parse_header(&mut self, header: &str) {
+        let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+        match key {
+            "branch.oid" if value != "(initial)" => self.commit = Some(value.to_string()),
+            "branch.head" if value != "(detached)" => self.branch = Some(value.to_string()),
+            "branch.upstream" => self.upstream = Some(value.to_string()),
+            "branch.ab" => {
+                let mut counts = value
+                    .split(' ')
+                    .map(|count| count.trim_start_matches(['+', '-']).parse().unwrap_or(0));
+                self.ahead = counts.next().unwrap_or(0);
+                self.behind = counts.next().unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+
+    fn push_change(&mut self, xy: &str, path: &str, original_path: Option<String>) {
+        let mut codes = xy.chars();
+        let change = |code| {
+            change_name(code).map(|status| FileChange {
+                path: path.to_string(),
+                status,
+                original_path: original_path.clone(),
+            })
+        };
+        self.staged.extend(codes.next().and_then(change));
+        self.unstaged.extend(codes.next().and_then(change));
+    }
+
+    /// A one-line overview, e.g. "On branch main, ahead 1 of origin/main: 2 staged, 1 unstaged, 0 untracked"
+    pub fn summary(&self) -> String {
+        let mut summary = match &self.branch {
+            Some(branch) => format!("On branch {branch}"),
+            None => "HEAD detached".to_string(),
+        };
+        if let Some(upstream) = &self.upstream {
+            let position = match (self.ahead, self.behind) {
+                (0, 0) => "up to date with".to_string(),
+                (ahead, 0) => format!("ahead {ahead} of"),
+                (0, behind) => format!("behind {behind} of"),
+                (ahead, behind) => format!("ahead {ahead}, behind {behind} of"),
+            };
+            summary.push_str(&format!(", {position} {upstream}"));
+        }
+        summary.push_str(&format!(
+            ": {} staged, {} unstaged, {} untracked",
+            self.staged.len(),
+            self.unstaged.len(),
+            self.untracked.len()
+        ));
+        if !self.conflicted.is_empty() {
+            summary.push_str(&format!(", {} conflicted", self.conflicted.len()));
+        }
+        summary
+    }
+}
+
+// Porcelain status letters, '.' means unchanged
+fn change_name(code: char) -> Option<&'static str> {
+    match code {
+        'M' => Some("modified"),
+        'T' => Some("type_changed"),
+        'A' => Some("added"),
+        'D' => Some("deleted"),
+        'R' => Some("renamed"),
+        'C' => Some("copied"),
+        _ => None,
+    }
+}
+
+/// Report the git status of the repository containing `dir`
+pub async fn status(shell: &Shell, dir: PathBuf) -> Result<CallToolResult, McpError> {
+    let options = ExecuteOptions {
+        cwd: Some(dir.clone()),
+        ..Default::default()
+    };
+    let output = shell.output(STATUS_COMMAND, options).await?;
+
+    if output.status.exit_code != Some(0) {
+        let stderr = output.stderr.trim();
+        if stderr.contains("not a git repository") {
+            return Err(McpError::invalid_request(
+                format!("'{}' is not inside a git repository", dir.display()),
+                None,
+            ));
+        }
+        let reason = if stderr.is_empty() {
+            format!("exit code {:?}", output.status.exit_code)
+        } else {
+            stderr.to_string()
+        };
+        return Err(McpError::internal_error(
+            format!("git status failed: {reason}"),
+            None,
+        ));
+    }
+
+    let status = GitStatus::parse(&output.stdout);
+    Ok(CallToolResult::success(vec![
+        Content::json(&status)?.with_audience(vec![Role::Assistant]),
+        Content::text(status.summary())
+            .with_audience(vec![Role::User])
+            .with_priority(0.0),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain_v2() {
+        let output = [
+            "# branch.oid 1f2e3d4c",
+            "# branch.head main",
+            "# branch.upstream origin/main",
+            "# branch.ab +2 -1",
+            "1 M. N... 100644 100644 100644 aaaa bbbb src/lib.rs",
+            "1 .M N... 100644 100644 100644 aaaa aaaa README with spaces.md",
+            "1 AD N... 000000 100644 000000 0000 cccc gone.txt",
+            "2 R. N... 100644 100644 100644 dddd dddd R100 new.rs",
+            "old.rs",
+            "u UU N... 100644 100644 100644 100644 eeee ffff 0000 conflict.rs",
+            "? notes.txt",
+            "",
+        ]
+        .join("\0");
+
+        let status = GitStatus::parse(&output);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.commit.as_deref(), Some("1f2e3d4c"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+
+        let staged: Vec<(&str, &str)> = status
+            .staged
+            .iter()
+            .map(|change| (change.path.as_str(), change.status))
+            .collect();
+        assert_eq!(
+            staged,
+            vec![
+                ("src/lib.rs", "modified"),
+                ("gone.txt", "added"),
+                ("new.rs", "renamed")
+            ]
+        );
+        assert_eq!(status.staged[2].original_path.as_deref(), Some("old.rs"));
+
+        let unstaged: Vec<(&str, &str)> = status
+            .unstaged
+            .iter()
+            .map(|change| (change.path.as_str(), change.status))
+            .collect();
+        assert_eq!(
+            unstaged,
+            vec![
+                ("README with spaces.md", "modified"),
+                ("gone.txt", "deleted")
+            ]
+        );
+        assert_eq!(status.untracked, vec!["notes.txt"]);
+        assert_eq!(status.conflicted, vec!["conflict.rs"]);
+        assert_eq!(
+            status.summary(),
+            "On branch main, ahead 2, behind 1 of origin/main: 3 staged, 2 unstaged, 1 untracked, 1 conflicted"
+        );
+
+        // A fresh repository with a detached or unborn head
+        let status = GitStatus::parse("# branch.oid (initial)\0# branch.head (detached)\0");
+        assert_eq!(status.branch, None);
+        assert_eq!(status.commit, None);
+        assert_eq!(
+            status.summary(),
+            "HEAD detached: 0 staged, 0 unstaged, 0 untracked"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_outside_repository() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let shell = Shell::new();
+        let result = status(&shell, temp_dir.path().to_path_buf()).await;
+        if let Err(err) = result {
+            // Without git installed the command fails differently
+            if err.message.contains("git status failed") {
+                return;
+            }
+            assert!(err.message.contains("is not inside a git repository"));
+        } else if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_ok()
+        {
+            // The temp dir may itself sit inside a repository
+            assert!(
+                temp_dir
+                    .path()
+                    .ancestors()
+                    .any(|dir| dir.join(".git").exists())
+            );
+        }
+        temp_dir.close().unwrap();
+    }
+}
@@ -4,10 +4,164 @@ use rmcp::{
     model::CallToolResult,
     model::{Content, Role},
 };
-use std::{io::Cursor, path::Path};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Semaphore;
+
+use crate::developer::{
+    DEFAULT_IMAGE_MAX_WIDTH, DEFAULT_JPEG_QUALITY, IgnorePatterns, acquire_permit, ocr,
+    validate_jpeg_quality,
+};
+
+/// An operation applied while processing, in the order it was applied
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ImageOperation {
+    /// Turned or flipped upright as the EXIF orientation asks
+    AutoOrient { orientation: u32 },
+    /// Cut out a rectangle of the source
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Turned clockwise by a multiple of 90 degrees
+    Rotate { degrees: u32 },
+    /// Downscaled to fit the maximum width, keeping the aspect ratio
+    FitWidth { max_width: u32 },
+    /// Scaled by a requested factor
+    Resize { factor: String },
+    /// Scaled to requested dimensions
+    ResizeTo { width: u32, height: u32 },
+    /// Converted to a single luminance channel
+    Grayscale,
+    /// Contrast raised (positive) or lowered (negative) by a percentage
+    Contrast { amount: f32 },
+}
+
+// Largest width or height an image may be resized to
+const MAX_DIMENSION: u32 = 8192;
+
+// Most images a single batch may process, each result is held in memory
+const MAX_BATCH_IMAGES: usize = 16;
+
+// Contrast adjustments beyond this turn every pixel gray or fully saturate it
+const MAX_CONTRAST: f32 = 100.0;
+
+/// Optional behaviors for `process`
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    /// Scale down by "1/2" or "1/4" after fitting to the maximum width
+    pub resize: Option<String>,
+    /// Target width, the height follows the aspect ratio unless also given
+    pub width: Option<u32>,
+    /// Target height, the width follows the aspect ratio unless also given
+    pub height: Option<u32>,
+    /// Write the result here instead of returning it, in the format of the extension
+    pub output_path: Option<PathBuf>,
+    /// Replace an existing file at `output_path`, which is refused otherwise
+    pub overwrite: bool,
+    /// Rectangle to cut out as `[x, y, width, height]`, before rotating and resizing
+    pub crop: Option<[u32; 4]>,
+    /// Clockwise rotation in degrees, a multiple of 90, applied before resizing
+    pub rotate: Option<i32>,
+    /// "png", "jpeg" or "webp", overriding the format inferred from the input
+    pub output_format: Option<String>,
+    /// Quality from 1 to 100 for JPEG output
+    pub quality: Option<u8>,
+    /// Report the EXIF metadata of the source
+    pub read_metadata: bool,
+    /// Turn the pixels upright according to the EXIF orientation before other operations
+    pub auto_orient: bool,
+    /// Convert to grayscale after resizing, e.g. to shrink screenshots for OCR
+    pub grayscale: bool,
+    /// Contrast change from -100 to 100, applied after grayscale
+    pub contrast: Option<f32>,
+    /// Recognize the text in the processed image, needs the `ocr` feature
+    pub ocr: bool,
+}
+
+/// EXIF details of a source image, absent fields weren't recorded
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ImageMetadata {
+    /// Decoded pixel dimensions, before any orientation is applied
+    pub width: u32,
+    pub height: u32,
+    /// EXIF orientation from 1 (upright) to 8
+    pub orientation: Option<u32>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    /// As recorded by the camera, "YYYY:MM:DD HH:MM:SS"
+    pub date_time_original: Option<String>,
+}
+
+impl ImageMetadata {
+    fn read(path: &Path, width: u32, height: u32) -> Self {
+        let mut metadata = Self {
+            width,
+            height,
+            ..Default::default()
+        };
+        // Formats without EXIF, or a missing block, leave only the dimensions
+        let Some(exif) = std::fs::File::open(path).ok().and_then(|file| {
+            exif::Reader::new()
+                .read_from_container(&mut std::io::BufReader::new(file))
+                .ok()
+        }) else {
+            return metadata;
+        };
+        let text = |tag| {
+            exif.get_field(tag, exif::In::PRIMARY)
+                .and_then(|field| match &field.value {
+                    exif::Value::Ascii(values) => values.first(),
+                    _ => None,
+                })
+                .map(|value| String::from_utf8_lossy(value).trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        metadata.orientation = exif
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0));
+        metadata.camera_make = text(exif::Tag::Make);
+        metadata.camera_model = text(exif::Tag::Model);
+        metadata.date_time_original = text(exif::Tag::DateTimeOriginal);
+        metadata
+    }
+}
+
+/// Machine-readable summary of a processed image, for chaining processing steps
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImageProcessingReport {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub format: String,
+    pub mime_type: String,
+    /// Size of the source file in bytes
+    pub original_size: u64,
+    /// Size of the encoded output in bytes
+    pub output_size: usize,
+    pub operations: Vec<ImageOperation>,
+    /// Where the result was written, when it wasn't returned inline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<String>,
+}
 
 #[derive(Clone)]
-pub struct ImageProcessor;
+pub struct ImageProcessor {
+    // Optional limit on concurrent image operations, shared with screen capture
+    concurrency_limit: Option<Arc<Semaphore>>,
+    // Gitignore patterns restricting where results are written, shared with the other tools
+    ignore_patterns: IgnorePatterns,
+    // Wider images are downscaled unless explicit dimensions are given, None keeps them as is
+    max_width: Option<u32>,
+}
 
 impl Default for ImageProcessor {
     fn default() -> Self {
@@ -17,7 +171,27 @@ impl Default for ImageProcessor {
 
 impl ImageProcessor {
     pub fn new() -> Self {
-        Self
+        Self {
+            concurrency_limit: None,
+            ignore_patterns: IgnorePatterns::new(),
+            max_width: Some(DEFAULT_IMAGE_MAX_WIDTH),
+        }
+    }
+
+    /// Cap output widths at `max_width` pixels, or never downscale with None
+    pub fn with_max_width(mut self, max_width: Option<u32>) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn with_concurrency_limit(mut self, concurrency_limit: Arc<Semaphore>) -> Self {
+        self.concurrency_limit = Some(concurrency_limit);
+        self
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
     }
 
     /// Helper function to handle Mac screenshot filenames that contain U+202F (narrow no-break space)
@@ -55,11 +229,207 @@ impl ImageProcessor {
         path.to_path_buf()
     }
 
+    // Whether explicit dimensions were requested instead of the width cap and resize factor
+    fn validate_dimensions(options: &ProcessOptions) -> Result<bool, McpError> {
+        if options.width.is_none() && options.height.is_none() {
+            return Ok(false);
+        }
+        if options.resize.is_some() {
+            return Err(McpError::invalid_params(
+                "Use either resize or width/height, not both",
+                None,
+            ));
+        }
+        for value in [options.width, options.height].into_iter().flatten() {
+            if value == 0 || value > MAX_DIMENSION {
+                return Err(McpError::invalid_params(
+                    format!("Invalid dimension {value}, it must be between 1 and {MAX_DIMENSION}"),
+                    None,
+                ));
+            }
+        }
+        Ok(true)
+    }
+
+    // The scale of the requested resize factor, if any
+    fn validate_resize(resize: Option<&str>) -> Result<Option<f32>, McpError> {
+        match resize {
+            None => Ok(None),
+            Some("1/2") => Ok(Some(0.5)),
+            Some("1/4") => Ok(Some(0.25)),
+            Some(resize_factor) => Err(McpError::invalid_params(
+                format!("Invalid resize factor '{resize_factor}'. Allowed values: '1/2', '1/4'"),
+                None,
+            )),
+        }
+    }
+
+    // The clockwise quarter turn to apply, in degrees, if any
+    fn validate_rotation(rotate: Option<i32>) -> Result<Option<u32>, McpError> {
+        let Some(degrees) = rotate else {
+            return Ok(None);
+        };
+        if degrees % 90 != 0 {
+            return Err(McpError::invalid_params(
+                format!("Invalid rotation {degrees}, it must be a multiple of 90 degrees"),
+                None,
+            ));
+        }
+        Ok(Some(degrees.rem_euclid(360) as u32).filter(|&degrees| degrees != 0))
+    }
+
+    fn validate_contrast(contrast: Option<f32>) -> Result<(), McpError> {
+        match contrast {
+            Some(contrast) if !(-MAX_CONTRAST..=MAX_CONTRAST).contains(&contrast) => {
+                Err(McpError::invalid_params(
+                    format!(
+                        "Invalid contrast {contrast}, it must be between -{MAX_CONTRAST} and {MAX_CONTRAST}"
+                    ),
+                    None,
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The requested output format, checked before any decoding work
+    fn validate_output_format(
+        options: &ProcessOptions,
+    ) -> Result<Option<xcap::image::ImageFormat>, McpError> {
+        validate_jpeg_quality(options.quality)?;
+        let requested = match options
+            .output_format
+            .as_ref()
+            .map(|format| format.to_ascii_lowercase())
+            .as_deref()
+        {
+            None => None,
+            Some("png") => Some(xcap::image::ImageFormat::Png),
+            Some("jpeg") | Some("jpg") => Some(xcap::image::ImageFormat::Jpeg),
+            Some("webp") => Some(xcap::image::ImageFormat::WebP),
+            Some(other) => {
+                return Err(McpError::invalid_params(
+                    format!("Unsupported output format '{other}', use 'png', 'jpeg' or 'webp'"),
+                    None,
+                ));
+            }
+        };
+
+        // A file named for one format must not silently hold another
+        match (requested, &options.output_path) {
+            (Some(requested), Some(output_path))
+                if xcap::image::ImageFormat::from_path(output_path)
+                    .is_ok_and(|named| named != requested) =>
+            {
+                Err(McpError::invalid_params(
+                    format!(
+                        "output_path '{display}' names a different format than output_format '{format}', make them match",
+                        display = output_path.display(),
+                        format = options.output_format.as_deref().unwrap_or_default()
+                    ),
+                    None,
+                ))
+            }
+            _ => Ok(requested),
+        }
+    }
+
+    // The output must neither replace the source nor, unless asked, any other existing file
+    fn check_output_path(source: &Path, options: &ProcessOptions) -> Result<(), McpError> {
+        let Some(output_path) = &options.output_path else {
+            return Ok(());
+        };
+        // Canonical paths also catch the source reached through a symlink or `..`
+        let canonical_output = std::fs::canonicalize(output_path).ok();
+        if canonical_output.is_some() && canonical_output == std::fs::canonicalize(source).ok() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "output_path '{}' is the source image, write the result to a different file",
+                    output_path.display()
+                ),
+                None,
+            ));
+        }
+        if output_path.exists() && !options.overwrite {
+            return Err(McpError::invalid_params(
+                format!(
+                    "output_path '{}' already exists, set overwrite to replace it",
+                    output_path.display()
+                ),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    // Write through a sibling temp file, so a failed write never leaves a truncated image,
+    // and only replace a file that appeared meanwhile when overwriting was asked for
+    fn write_output(output_path: &Path, bytes: &[u8], overwrite: bool) -> Result<(), McpError> {
+        let write_error = |e: std::io::Error| {
+            McpError::internal_error(format!("Failed to write image file: {e}"), None)
+        };
+        let directory = output_path.parent().unwrap_or_else(|| Path::new("."));
+        std::fs::create_dir_all(directory).map_err(|e| {
+            McpError::internal_error(format!("Failed to create directories: {e}"), None)
+        })?;
+        let mut temp_file = tempfile::NamedTempFile::new_in(directory).map_err(write_error)?;
+        std::io::Write::write_all(&mut temp_file, bytes).map_err(write_error)?;
+        let persisted = if overwrite {
+            temp_file.persist(output_path)
+        } else {
+            temp_file.persist_noclobber(output_path)
+        };
+        match persisted {
+            Ok(_) => Ok(()),
+            Err(e) if e.error.kind() == std::io::ErrorKind::AlreadyExists => {
+                Err(McpError::invalid_params(
+                    format!(
+                        "output_path '{}' already exists, set overwrite to replace it",
+                        output_path.display()
+                    ),
+                    None,
+                ))
+            }
+            Err(e) => Err(write_error(e.error)),
+        }
+    }
+
     pub async fn process(
         &self,
         path: String,
         resize: Option<String>,
     ) -> Result<CallToolResult, McpError> {
+        self.process_with_options(
+            path,
+            ProcessOptions {
+                resize,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    pub async fn process_with_options(
+        &self,
+        path: String,
+        options: ProcessOptions,
+    ) -> Result<CallToolResult, McpError> {
+        let resize = options.resize.clone();
+        let explicit_dimensions = Self::validate_dimensions(&options)?;
+        let resize_scale = Self::validate_resize(resize.as_deref())?;
+        let rotation = Self::validate_rotation(options.rotate)?;
+        Self::validate_contrast(options.contrast)?;
+        let requested_format = Self::validate_output_format(&options)?;
+        if let Some(output_path) = &options.output_path {
+            self.ignore_patterns.check_writable(output_path, false)?;
+        }
+        if options.ocr {
+            ocr::check_available()?;
+        }
+
+        // Queue behind other image/screen operations when the limit is reached
+        let _permit = acquire_permit(&self.concurrency_limit).await?;
+
         let path = Path::new(&path);
 
         let path = {
@@ -77,6 +447,7 @@ impl ImageProcessor {
                 None,
             ));
         }
+        Self::check_output_path(&path, &options)?;
 
         // Check file size (10MB limit for image files)
         const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024; // 10MB in bytes
@@ -103,10 +474,91 @@ impl ImageProcessor {
             McpError::internal_error(format!("Failed to open image file: {e}"), None)
         })?;
 
-        // Resize if necessary (same logic as screen_capture)
+        let (original_width, original_height) = (image.width(), image.height());
+        let mut operations = Vec::new();
+        let mut applied = Vec::new();
         let mut processed_image = image;
-        let max_width = 768;
-        if processed_image.width() > max_width {
+
+        let metadata = (options.read_metadata || options.auto_orient)
+            .then(|| ImageMetadata::read(&path, original_width, original_height));
+        if options.auto_orient {
+            let orientation = metadata.as_ref().and_then(|metadata| metadata.orientation);
+            if let Some((orientation, transform)) = orientation
+                .filter(|&orientation| orientation != 1)
+                .and_then(|orientation| {
+                    let transform = xcap::image::metadata::Orientation::from_exif(
+                        u8::try_from(orientation).ok()?,
+                    )?;
+                    Some((orientation, transform))
+                })
+            {
+                operations.push(ImageOperation::AutoOrient { orientation });
+                applied.push(format!("auto-oriented from EXIF orientation {orientation}"));
+                processed_image.apply_orientation(transform);
+            }
+        }
+
+        // Geometry comes first so the resize targets apply to the final shape
+        if let Some([x, y, width, height]) = options.crop {
+            let (image_width, image_height) = (processed_image.width(), processed_image.height());
+            let fits = width > 0
+                && height > 0
+                && u64::from(x) + u64::from(width) <= u64::from(image_width)
+                && u64::from(y) + u64::from(height) <= u64::from(image_height);
+            if !fits {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "Crop rectangle {width}x{height} at {x},{y} is outside the {image_width}x{image_height} image"
+                    ),
+                    None,
+                ));
+            }
+            operations.push(ImageOperation::Crop {
+                x,
+                y,
+                width,
+                height,
+            });
+            applied.push(format!("cropped to {width}x{height} at {x},{y}"));
+            processed_image = processed_image.crop_imm(x, y, width, height);
+        }
+        if let Some(degrees) = rotation {
+            operations.push(ImageOperation::Rotate { degrees });
+            applied.push(format!("rotated {degrees} degrees"));
+            processed_image = match degrees {
+                90 => processed_image.rotate90(),
+                180 => processed_image.rotate180(),
+                _ => processed_image.rotate270(),
+            };
+        }
+
+        // Resize if necessary (same logic as screen_capture)
+        if explicit_dimensions {
+            // Explicit dimensions replace the width cap
+            let (width, height) = match (options.width, options.height) {
+                (Some(width), Some(height)) => (width, height),
+                (Some(width), None) => (
+                    width,
+                    scaled(processed_image.height(), width, processed_image.width()),
+                ),
+                (None, Some(height)) => (
+                    scaled(processed_image.width(), height, processed_image.height()),
+                    height,
+                ),
+                (None, None) => unreachable!("explicit dimensions have a width or height"),
+            };
+            operations.push(ImageOperation::ResizeTo { width, height });
+            processed_image = xcap::image::DynamicImage::ImageRgba8(xcap::image::imageops::resize(
+                &processed_image,
+                width,
+                height,
+                xcap::image::imageops::FilterType::Lanczos3,
+            ));
+        } else if let Some(max_width) = self
+            .max_width
+            .filter(|max_width| processed_image.width() > *max_width)
+        {
+            operations.push(ImageOperation::FitWidth { max_width });
             let scale = max_width as f32 / processed_image.width() as f32;
             let new_height = (processed_image.height() as f32 * scale) as u32;
             processed_image = xcap::image::DynamicImage::ImageRgba8(xcap::image::imageops::resize(
@@ -118,19 +570,10 @@ impl ImageProcessor {
         }
 
         // Apply additional resize if requested
-        if let Some(ref resize_factor) = resize {
-            let resize_scale = match resize_factor.as_str() {
-                "1/2" => 0.5,
-                "1/4" => 0.25,
-                _ => {
-                    return Err(McpError::invalid_params(
-                        format!(
-                            "Invalid resize factor '{resize_factor}'. Allowed values: '1/2', '1/4'"
-                        ),
-                        None,
-                    ));
-                }
-            };
+        if let (Some(resize_factor), Some(resize_scale)) = (&resize, resize_scale) {
+            operations.push(ImageOperation::Resize {
+                factor: resize_factor.clone(),
+            });
 
             let new_width = (processed_image.width() as f32 * resize_scale) as u32;
             let new_height = (processed_image.height() as f32 * resize_scale) as u32;
@@ -147,6 +590,43 @@ impl ImageProcessor {
             ));
         }
 
+        // Color adjustments run on the final size, touching as few pixels as possible
+        if options.grayscale {
+            operations.push(ImageOperation::Grayscale);
+            applied.push("converted to grayscale".to_string());
+            // Keeps any alpha channel and 16-bit depth the source had
+            processed_image = processed_image.grayscale();
+        }
+        if let Some(amount) = options.contrast {
+            operations.push(ImageOperation::Contrast { amount });
+            applied.push(format!("contrast adjusted by {amount}"));
+            processed_image = processed_image.adjust_contrast(amount);
+        }
+
+        // Text is read from the image as returned, so the adjustments above can help it.
+        // Tesseract blocks for seconds on large images, so it runs off the async workers
+        let (processed_image, recognized_text) = if options.ocr {
+            let (image, text) = tokio::task::spawn_blocking(move || {
+                let text = ocr::recognize(&processed_image);
+                (processed_image, text)
+            })
+            .await
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to recognize text: {e}"), None)
+            })?;
+            (image, Some(text?))
+        } else {
+            (processed_image, None)
+        };
+        let recognized_text = recognized_text.map(|text| {
+            let text = if text.trim().is_empty() {
+                "No text was recognized in the image".to_string()
+            } else {
+                format!("Recognized text:\n{}", text.trim_end())
+            };
+            Content::text(text).with_audience(vec![Role::Assistant])
+        });
+
         // Determine output format based on input format
         let input_format =
             xcap::image::ImageFormat::from_path(&path).unwrap_or(xcap::image::ImageFormat::Png);
@@ -155,6 +635,16 @@ impl ImageProcessor {
             xcap::image::ImageFormat::WebP => (xcap::image::ImageFormat::Jpeg, "image/jpeg"), // Convert WebP to JPEG
             _ => (xcap::image::ImageFormat::Png, "image/png"), // Keep PNG, BMP, etc. as PNG
         };
+        // An explicit format wins, then a written file takes the format its extension names
+        let (output_format, mime_type) = match requested_format.or_else(|| {
+            options
+                .output_path
+                .as_ref()
+                .and_then(|output_path| xcap::image::ImageFormat::from_path(output_path).ok())
+        }) {
+            Some(format) => (format, format.to_mime_type()),
+            None => (output_format, mime_type),
+        };
 
         // Convert to appropriate format and encode as base64
         let mut bytes: Vec<u8> = Vec::new();
@@ -163,40 +653,111 @@ impl ImageProcessor {
         match output_format {
             xcap::image::ImageFormat::Jpeg => {
                 // Use JPEG with quality control for better compression
-                let quality = 85; // High quality but still compressed
+                let quality = options.quality.unwrap_or(DEFAULT_JPEG_QUALITY);
                 let mut encoder =
                     xcap::image::codecs::jpeg::JpegEncoder::new_with_quality(&mut cursor, quality);
-                let rgb_image = processed_image.to_rgb8();
+                // Grayscale keeps its single channel instead of being widened to RGB
+                let (pixels, color_type) = if !processed_image.color().has_color() {
+                    (
+                        processed_image.to_luma8().into_raw(),
+                        xcap::image::ColorType::L8,
+                    )
+                } else {
+                    (
+                        processed_image.to_rgb8().into_raw(),
+                        xcap::image::ColorType::Rgb8,
+                    )
+                };
                 encoder
                     .encode(
-                        &rgb_image,
-                        rgb_image.width(),
-                        rgb_image.height(),
-                        xcap::image::ColorType::Rgb8.into(),
+                        &pixels,
+                        processed_image.width(),
+                        processed_image.height(),
+                        color_type.into(),
                     )
                     .map_err(|e| {
                         McpError::internal_error(format!("Failed to encode JPEG: {e}"), None)
                     })?;
             }
-            _ => {
-                // Use PNG for other formats
+            xcap::image::ImageFormat::Png => {
                 processed_image
                     .write_to(&mut cursor, xcap::image::ImageFormat::Png)
                     .map_err(|e| {
                         McpError::internal_error(format!("Failed to write PNG: {e}"), None)
                     })?;
             }
+            other => {
+                processed_image.write_to(&mut cursor, other).map_err(|e| {
+                    McpError::invalid_params(
+                        format!("Failed to encode the image as {other:?}: {e}"),
+                        None,
+                    )
+                })?;
+            }
         }
 
-        let data = base64::prelude::BASE64_STANDARD.encode(bytes);
+        let report = ImageProcessingReport {
+            path: path.display().to_string(),
+            width: processed_image.width(),
+            height: processed_image.height(),
+            original_width,
+            original_height,
+            format: output_format
+                .extensions_str()
+                .first()
+                .copied()
+                .unwrap_or("png")
+                .to_string(),
+            mime_type: mime_type.to_string(),
+            original_size: file_size,
+            output_size: bytes.len(),
+            operations,
+            output_path: options
+                .output_path
+                .as_ref()
+                .map(|output_path| output_path.display().to_string()),
+        };
 
-        let resize_info = if let Some(ref resize_factor) = resize {
-            format!(" (resized by {resize_factor})")
-        } else {
+        if explicit_dimensions {
+            applied.push(format!(
+                "resized to {}x{}",
+                processed_image.width(),
+                processed_image.height()
+            ));
+        } else if let Some(ref resize_factor) = resize {
+            applied.push(format!("resized by {resize_factor}"));
+        }
+        let resize_info = if applied.is_empty() {
             String::new()
+        } else {
+            format!(" ({})", applied.join(", "))
         };
 
-        Ok(CallToolResult::success(vec![
+        if let Some(output_path) = &options.output_path {
+            Self::write_output(output_path, &bytes, options.overwrite)?;
+            let mut content = vec![
+                Content::text(format!(
+                    "Wrote processed image from {}{} to {}. Final dimensions: {}x{}, format: {}",
+                    path.display(),
+                    resize_info,
+                    output_path.display(),
+                    processed_image.width(),
+                    processed_image.height(),
+                    mime_type
+                ))
+                .with_audience(vec![Role::Assistant]),
+                Content::json(report)?.with_audience(vec![Role::Assistant]),
+            ];
+            if let Some(metadata) = metadata.filter(|_| options.read_metadata) {
+                content.push(Content::json(metadata)?.with_audience(vec![Role::Assistant]));
+            }
+            content.extend(recognized_text);
+            return Ok(CallToolResult::success(content));
+        }
+
+        let data = base64::prelude::BASE64_STANDARD.encode(bytes);
+
+        let mut content = vec![
             Content::text(format!(
                 "Successfully processed image from {}{}. Final dimensions: {}x{}, format: {}",
                 path.display(),
@@ -206,11 +767,84 @@ impl ImageProcessor {
                 mime_type
             ))
             .with_audience(vec![Role::Assistant]),
+            Content::json(report)?.with_audience(vec![Role::Assistant]),
             Content::image(data, mime_type.to_string()).with_priority(0.0),
-        ]))
+        ];
+        if let Some(metadata) = metadata.filter(|_| options.read_metadata) {
+            content.push(Content::json(metadata)?.with_audience(vec![Role::Assistant]));
+        }
+        content.extend(recognized_text);
+        Ok(CallToolResult::success(content))
+    }
+
+    /// Process several images with shared options, skipping files that fail
+    pub async fn process_batch(
+        &self,
+        paths: Vec<String>,
+        options: ProcessOptions,
+    ) -> Result<CallToolResult, McpError> {
+        if paths.is_empty() {
+            return Err(McpError::invalid_params("No paths were given", None));
+        }
+        if paths.len() > MAX_BATCH_IMAGES {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Too many images ({}), a batch may process at most {MAX_BATCH_IMAGES}",
+                    paths.len()
+                ),
+                None,
+            ));
+        }
+        if options.output_path.is_some() {
+            return Err(McpError::invalid_params(
+                "output_path names a single file and can't be used with a batch",
+                None,
+            ));
+        }
+        // Bad options would fail every file, so report them instead of skipping
+        Self::validate_dimensions(&options)?;
+        Self::validate_resize(options.resize.as_deref())?;
+        Self::validate_rotation(options.rotate)?;
+        Self::validate_contrast(options.contrast)?;
+        Self::validate_output_format(&options)?;
+        if options.ocr {
+            ocr::check_available()?;
+        }
+
+        let mut content = Vec::new();
+        let mut warnings = Vec::new();
+        for path in &paths {
+            match self
+                .process_with_options(path.clone(), options.clone())
+                .await
+            {
+                Ok(result) => content.extend(result.content),
+                Err(e) => warnings.push(format!("Skipped {path}: {}", e.message)),
+            }
+        }
+
+        let mut summary = format!(
+            "Processed {} of {} images",
+            paths.len() - warnings.len(),
+            paths.len()
+        );
+        for warning in &warnings {
+            summary.push_str(&format!("\n{warning}"));
+        }
+        content.insert(
+            0,
+            Content::text(summary).with_audience(vec![Role::Assistant]),
+        );
+        Ok(CallToolResult::success(content))
     }
 }
 
+/// `length * target / reference`, rounded and at least 1
+fn scaled(length: u32, target: u32, reference: u32) -> u32 {
+    ((u64::from(length) * u64::from(target) + u64::from(reference) / 2) / u64::from(reference))
+        .clamp(1, u64::from(MAX_DIMENSION)) as u32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -304,4 +938,610 @@ mod tests {
 
         temp_dir.close().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_serializes_operations() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file_path = temp_dir.path().join("test.png");
+        let img = xcap::image::RgbImage::new(1, 1);
+        img.save(&test_file_path).unwrap();
+
+        let limit = Arc::new(Semaphore::new(1));
+        let image_processor = ImageProcessor::new().with_concurrency_limit(limit.clone());
+
+        // Occupy the only slot, the next operation must wait for it
+        let permit = limit.clone().acquire_owned().await.unwrap();
+        let queued = tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            image_processor.process(test_file_path.to_string_lossy().to_string(), None),
+        )
+        .await;
+        assert!(
+            queued.is_err(),
+            "Operation should wait while the limit is reached"
+        );
+
+        // Once the slot is released the operation proceeds
+        drop(permit);
+        let result = image_processor
+            .process(test_file_path.to_string_lossy().to_string(), None)
+            .await;
+        assert!(result.is_ok());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_process_structured_report() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file_path = temp_dir.path().join("wide.png");
+        let img = xcap::image::RgbImage::new(1536, 100);
+        img.save(&test_file_path).unwrap();
+
+        let image_processor = ImageProcessor::new();
+        let result = image_processor
+            .process(
+                test_file_path.to_string_lossy().to_string(),
+                Some("1/2".to_string()),
+            )
+            .await
+            .unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(report["width"], 384);
+        assert_eq!(report["height"], 25);
+        assert_eq!(report["original_width"], 1536);
+        assert_eq!(report["format"], "png");
+        assert_eq!(report["mime_type"], "image/png");
+        assert_eq!(
+            report["operations"],
+            serde_json::json!([
+                {"op": "fit_width", "max_width": 768},
+                {"op": "resize", "factor": "1/2"}
+            ])
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_width() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file_path = temp_dir.path().join("wide.png");
+        xcap::image::RgbImage::new(2000, 100)
+            .save(&test_file_path)
+            .unwrap();
+        let path = test_file_path.to_string_lossy().to_string();
+        let width = |result: &CallToolResult| {
+            let report: serde_json::Value =
+                serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+            report["width"].as_u64().unwrap()
+        };
+
+        let result = ImageProcessor::new()
+            .process(path.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(width(&result), 768);
+
+        // A wider cap keeps more detail
+        let result = ImageProcessor::new()
+            .with_max_width(Some(1600))
+            .process(path.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(width(&result), 1600);
+
+        // Without a cap the image keeps its size
+        let result = ImageProcessor::new()
+            .with_max_width(None)
+            .process(path, None)
+            .await
+            .unwrap();
+        assert_eq!(width(&result), 2000);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_process_to_dimensions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file_path = temp_dir.path().join("photo.png");
+        xcap::image::RgbImage::new(800, 20)
+            .save(&test_file_path)
+            .unwrap();
+        let path = test_file_path.to_string_lossy().to_string();
+        let image_processor = ImageProcessor::new();
+        let dimensions = |result: &CallToolResult| {
+            let report: serde_json::Value =
+                serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+            (report["width"].clone(), report["height"].clone())
+        };
+
+        // One dimension keeps the aspect ratio, and the width cap doesn't apply
+        let result = image_processor
+            .process_with_options(
+                path.clone(),
+                ProcessOptions {
+                    width: Some(780),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(dimensions(&result), (780.into(), 20.into()));
+
+        let result = image_processor
+            .process_with_options(
+                path.clone(),
+                ProcessOptions {
+                    width: Some(40),
+                    height: Some(40),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(dimensions(&result), (40.into(), 40.into()));
+
+        let result = image_processor
+            .process_with_options(
+                path,
+                ProcessOptions {
+                    height: Some(0),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid dimension 0")
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_process_to_output_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source.png");
+        xcap::image::RgbImage::new(40, 20).save(&source).unwrap();
+        let thumbnail = temp_dir.path().join("thumbs/small.png");
+
+        let image_processor = ImageProcessor::new();
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    resize: Some("1/2".to_string()),
+                    output_path: Some(thumbnail.clone()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // Only the confirmation and report come back, no image data
+        assert_eq!(result.content.len(), 2);
+        assert!(result.content[0].as_text().unwrap().text.contains("20x10"));
+        let written = xcap::image::open(&thumbnail).unwrap();
+        assert_eq!((written.width(), written.height()), (20, 10));
+
+        // The extension picks the format
+        let jpeg = temp_dir.path().join("small.jpg");
+        image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(jpeg.clone()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let bytes = std::fs::read(&jpeg).unwrap();
+        assert_eq!(
+            xcap::image::guess_format(&bytes).unwrap(),
+            xcap::image::ImageFormat::Jpeg
+        );
+
+        // Existing files, and the source above all, are only replaced when asked
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(jpeg.clone()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("set overwrite"));
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(temp_dir.path().join("thumbs/../source.png")),
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("is the source image")
+        );
+
+        // An explicit format has to agree with the extension, jpg and jpeg are the same
+        let with_format = |output_format: &str| ProcessOptions {
+            output_path: Some(jpeg.clone()),
+            overwrite: true,
+            output_format: Some(output_format.to_string()),
+            ..Default::default()
+        };
+        let result = image_processor
+            .process_with_options(source.to_string_lossy().to_string(), with_format("png"))
+            .await;
+        assert!(result.unwrap_err().to_string().contains("different format"));
+        image_processor
+            .process_with_options(source.to_string_lossy().to_string(), with_format("jpeg"))
+            .await
+            .unwrap();
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_crop_and_rotate() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source.png");
+        let mut image = xcap::image::RgbImage::new(40, 20);
+        image.put_pixel(10, 5, xcap::image::Rgb([255, 0, 0]));
+        image.save(&source).unwrap();
+        let output = temp_dir.path().join("out.png");
+
+        let image_processor = ImageProcessor::new();
+        let process = |crop, rotate| {
+            image_processor.process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(output.clone()),
+                    overwrite: true,
+                    crop,
+                    rotate,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let result = process(Some([10, 5, 30, 10]), Some(-270)).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("cropped to 30x10 at 10,5, rotated 90 degrees"));
+        let written = xcap::image::open(&output).unwrap().to_rgb8();
+        assert_eq!(written.dimensions(), (10, 30));
+        // The crop's top-left corner ends up top-right after a clockwise turn
+        assert_eq!(written.get_pixel(9, 0), &xcap::image::Rgb([255, 0, 0]));
+
+        let err = process(Some([30, 0, 20, 20]), None).await.unwrap_err();
+        assert!(err.message.contains("outside the 40x20 image"));
+        let err = process(None, Some(45)).await.unwrap_err();
+        assert!(err.message.contains("multiple of 90"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_output_format_conversion() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source.png");
+        xcap::image::RgbImage::new(40, 20).save(&source).unwrap();
+
+        let image_processor = ImageProcessor::new();
+        let convert = |output_format: &str, quality| {
+            image_processor.process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_format: Some(output_format.to_string()),
+                    quality,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let result = convert("jpeg", Some(50)).await.unwrap();
+        let image = result.content[2].as_image().unwrap();
+        assert_eq!(image.mime_type, "image/jpeg");
+        let bytes = base64::prelude::BASE64_STANDARD
+            .decode(&image.data)
+            .unwrap();
+        assert_eq!(
+            xcap::image::guess_format(&bytes).unwrap(),
+            xcap::image::ImageFormat::Jpeg
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(report["mime_type"], "image/jpeg");
+
+        let result = convert("webp", None).await.unwrap();
+        assert_eq!(
+            result.content[2].as_image().unwrap().mime_type,
+            "image/webp"
+        );
+
+        assert!(convert("gif", None).await.is_err());
+        assert!(convert("jpeg", Some(0)).await.is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    // A JPEG whose EXIF block records a camera make and the given orientation
+    fn jpeg_with_exif(path: &Path, orientation: u16) {
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x02".to_vec();
+        // Make, ASCII, 4 bytes stored inline
+        tiff.extend_from_slice(b"\x01\x0f\0\x02\0\0\0\x04ACM\0");
+        // Orientation, SHORT, 1 value
+        tiff.extend_from_slice(b"\x01\x12\0\x03\0\0\0\x01");
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend_from_slice(&tiff);
+
+        let mut jpeg = Vec::new();
+        xcap::image::DynamicImage::ImageRgb8(xcap::image::RgbImage::new(40, 20))
+            .write_to(&mut Cursor::new(&mut jpeg), xcap::image::ImageFormat::Jpeg)
+            .unwrap();
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend_from_slice(&[0xff, 0xe1]);
+        bytes.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+        bytes.extend_from_slice(&segment);
+        bytes.extend_from_slice(&jpeg[2..]);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exif_metadata_and_auto_orient() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("photo.jpg");
+        jpeg_with_exif(&source, 6);
+
+        let image_processor = ImageProcessor::new();
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    read_metadata: true,
+                    auto_orient: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&result.content[3].as_text().unwrap().text).unwrap();
+        assert_eq!(metadata["orientation"], 6);
+        assert_eq!(metadata["camera_make"], "ACM");
+        assert_eq!(
+            (metadata["width"].as_u64(), metadata["height"].as_u64()),
+            (Some(40), Some(20))
+        );
+        assert!(metadata["date_time_original"].is_null());
+
+        // Orientation 6 is a quarter turn clockwise, so the result stands upright
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            (report["width"].as_u64(), report["height"].as_u64()),
+            (Some(20), Some(40))
+        );
+        assert_eq!(report["operations"][0]["op"], "auto_orient");
+
+        // Without the flags nothing is read or turned
+        let result = image_processor
+            .process(source.to_string_lossy().to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(result.content.len(), 3);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_grayscale_and_contrast() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("color.png");
+        let output = temp_dir.path().join("gray.png");
+        xcap::image::RgbImage::from_pixel(8, 8, xcap::image::Rgb([200, 40, 40]))
+            .save(&source)
+            .unwrap();
+
+        let image_processor = ImageProcessor::new();
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(output.clone()),
+                    grayscale: true,
+                    contrast: Some(20.0),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("converted to grayscale, contrast adjusted by 20"));
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            report["operations"],
+            serde_json::json!([{"op": "grayscale"}, {"op": "contrast", "amount": 20.0}])
+        );
+        // The written file keeps a single channel
+        let written = xcap::image::open(&output).unwrap();
+        assert_eq!(written.color(), xcap::image::ColorType::L8);
+
+        // Transparency survives, and a gray source stays single channel as JPEG
+        let translucent = temp_dir.path().join("translucent.png");
+        xcap::image::RgbaImage::from_pixel(8, 8, xcap::image::Rgba([200, 40, 40, 128]))
+            .save(&translucent)
+            .unwrap();
+        image_processor
+            .process_with_options(
+                translucent.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(output.clone()),
+                    overwrite: true,
+                    grayscale: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let written = xcap::image::open(&output).unwrap();
+        assert_eq!(written.color(), xcap::image::ColorType::La8);
+        let jpeg = temp_dir.path().join("gray.jpg");
+        image_processor
+            .process_with_options(
+                output.to_string_lossy().to_string(),
+                ProcessOptions {
+                    output_path: Some(jpeg.clone()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let written = xcap::image::open(&jpeg).unwrap();
+        assert_eq!(written.color(), xcap::image::ColorType::L8);
+
+        let result = image_processor
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    contrast: Some(150.0),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("Invalid contrast"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[cfg(not(feature = "ocr"))]
+    #[tokio::test]
+    async fn test_ocr_without_feature() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("text.png");
+        xcap::image::RgbImage::new(8, 8).save(&source).unwrap();
+
+        let result = ImageProcessor::new()
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    ocr: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("`ocr` feature"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[cfg(feature = "ocr")]
+    #[ignore = "needs Tesseract with its English language data"]
+    #[tokio::test]
+    async fn test_ocr_blank_image() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("blank.png");
+        xcap::image::RgbImage::from_pixel(64, 32, xcap::image::Rgb([255, 255, 255]))
+            .save(&source)
+            .unwrap();
+
+        let result = ImageProcessor::new()
+            .process_with_options(
+                source.to_string_lossy().to_string(),
+                ProcessOptions {
+                    ocr: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        // The image still comes back alongside the recognized text
+        assert!(
+            result
+                .content
+                .iter()
+                .any(|content| content.as_image().is_some())
+        );
+        assert!(result.content.iter().any(|content| {
+            content
+                .as_text()
+                .is_some_and(|text| text.text == "No text was recognized in the image")
+        }));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_process_batch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("first.png");
+        let second = temp_dir.path().join("second.png");
+        let broken = temp_dir.path().join("broken.png");
+        xcap::image::RgbImage::new(40, 20).save(&first).unwrap();
+        xcap::image::RgbImage::new(20, 40).save(&second).unwrap();
+        std::fs::write(&broken, "not an image").unwrap();
+        let paths: Vec<String> = [&first, &broken, &second]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let image_processor = ImageProcessor::new();
+        let options = ProcessOptions {
+            resize: Some("1/2".to_string()),
+            ..Default::default()
+        };
+        let result = image_processor
+            .process_batch(paths.clone(), options.clone())
+            .await
+            .unwrap();
+
+        let summary = &result.content[0].as_text().unwrap().text;
+        assert!(summary.starts_with("Processed 2 of 3 images"));
+        assert!(summary.contains("Skipped") && summary.contains("broken.png"));
+        let images: Vec<_> = result
+            .content
+            .iter()
+            .filter_map(|content| content.as_image())
+            .collect();
+        assert_eq!(images.len(), 2);
+
+        // Limits and bad options fail the whole batch
+        let too_many = vec![paths[0].clone(); MAX_BATCH_IMAGES + 1];
+        assert!(
+            image_processor
+                .process_batch(too_many, options)
+                .await
+                .is_err()
+        );
+        let bad_resize = ProcessOptions {
+            resize: Some("1/3".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            image_processor
+                .process_batch(paths, bad_resize)
+                .await
+                .is_err()
+        );
+
+        temp_dir.close().unwrap();
+    }
 }
@@ -0,0 +1,312 @@
+use regex::Regex;
+use rmcp::{
+    Error as McpError,
+    model::CallToolResult,
+    model::{Content, Role},
+};
+use std::path::{Path, PathBuf};
+
+use crate::developer::IgnorePatterns;
+
+// Candidate extensions tried for extensionless JavaScript/TypeScript specifiers
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs", "json"];
+
+#[derive(Clone)]
+pub struct ImportResolver {
+    // Gitignore patterns for file access control, shared with the other tools
+    ignore_patterns: IgnorePatterns,
+}
+
+impl Default for ImportResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImportResolver {
+    pub fn new() -> Self {
+        Self {
+            ignore_patterns: IgnorePatterns::new(),
+        }
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore_patterns.is_ignored(path, false)
+    }
+
+    pub async fn resolve(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        self.ignore_patterns.check(&path, false)?;
+
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a file.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+
+        let mut resolved: Vec<PathBuf> = Vec::new();
+        for candidate in Self::local_candidates(&path, &content) {
+            // Only report files that exist, are visible and were not already listed
+            let Ok(canonical) = std::fs::canonicalize(&candidate) else {
+                continue;
+            };
+            if !canonical.is_file() || self.is_ignored(&canonical) || resolved.contains(&canonical)
+            {
+                continue;
+            }
+            resolved.push(canonical);
+        }
+
+        let content = if resolved.is_empty() {
+            format!(
+                "No local imports found in {display}",
+                display = path.display()
+            )
+        } else {
+            format!(
+                "Local imports of {display}:\n{imports}",
+                display = path.display(),
+                imports = resolved
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        };
+
+        Ok(CallToolResult::success(vec![
+            Content::text(content.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(content)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    /// Collect the paths an import statement could refer to, based on the file's language
+    fn local_candidates(path: &Path, content: &str) -> Vec<PathBuf> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => Self::rust_candidates(path, dir, content),
+            Some("py") => Self::python_candidates(dir, content),
+            Some("js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs") => Self::js_candidates(dir, content),
+            Some("c" | "h" | "cc" | "cpp" | "cxx" | "hpp") => Self::c_candidates(dir, content),
+            _ => Vec::new(),
+        }
+    }
+
+    // `mod foo;` declarations, resolved the way rustc looks up module files
+    fn rust_candidates(path: &Path, dir: &Path, content: &str) -> Vec<PathBuf> {
+        let re = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+            .expect("valid rust mod regex");
+
+        // Non-root files own a directory named after themselves for child modules
+        let module_dir = match path.file_stem().and_then(|s| s.to_str()) {
+            Some("mod" | "main" | "lib") | None => dir.to_path_buf(),
+            Some(stem) => dir.join(stem),
+        };
+
+        re.captures_iter(content)
+            .flat_map(|caps| {
+                let name = &caps[1];
+                [
+                    module_dir.join(format!("{name}.rs")),
+                    module_dir.join(name).join("mod.rs"),
+                ]
+            })
+            .collect()
+    }
+
+    // Relative (`from .x import y`) and package-local (`import pkg.mod`) imports
+    fn python_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+        let from_re = Regex::new(r"(?m)^\s*from\s+(\.*)([A-Za-z0-9_.]*)\s+import\s+([^#\n]+)")
+            .expect("valid python from regex");
+        let import_re =
+            Regex::new(r"(?m)^\s*import\s+([^#\n]+)").expect("valid python import regex");
+
+        let module_paths = |base: &Path, module: &str| -> Vec<PathBuf> {
+            let relative: PathBuf = module.split('.').filter(|s| !s.is_empty()).collect();
+            vec![
+                base.join(&relative).with_extension("py"),
+                base.join(&relative).join("__init__.py"),
+            ]
+        };
+
+        let mut candidates = Vec::new();
+        for caps in from_re.captures_iter(content) {
+            let dots = caps[1].len();
+            let module = &caps[2];
+            // Each leading dot beyond the first walks up one package
+            let mut base = dir.to_path_buf();
+            for _ in 1..dots {
+                base.pop();
+            }
+            if module.is_empty() {
+                // `from . import a, b` imports sibling modules
+                for name in caps[3].split(',') {
+                    let name = name.split_whitespace().next().unwrap_or("");
+                    let name = name.trim_matches(|c| c == '(' || c == ')');
+                    if !name.is_empty() {
+                        candidates.extend(module_paths(&base, name));
+                    }
+                }
+            } else {
+                candidates.extend(module_paths(&base, module));
+            }
+        }
+        for caps in import_re.captures_iter(content) {
+            for module in caps[1].split(',') {
+                if let Some(module) = module.split_whitespace().next() {
+                    candidates.extend(module_paths(dir, module));
+                }
+            }
+        }
+        candidates
+    }
+
+    // `import ... from './x'`, `import './x'` and `require('./x')` with relative specifiers
+    fn js_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+        let re = Regex::new(
+            r#"(?:\bfrom\s+|\bimport\s+|\brequire\s*\(\s*|\bimport\s*\(\s*)['"](\.{1,2}/[^'"]+)['"]"#,
+        )
+        .expect("valid javascript import regex");
+
+        re.captures_iter(content)
+            .flat_map(|caps| {
+                let base = dir.join(&caps[1]);
+                let mut paths = vec![base.clone()];
+                for ext in JS_EXTENSIONS {
+                    let mut with_ext = base.clone().into_os_string();
+                    with_ext.push(format!(".{ext}"));
+                    paths.push(PathBuf::from(with_ext));
+                    paths.push(base.join(format!("index.{ext}")));
+                }
+                paths
+            })
+            .collect()
+    }
+
+    // Quoted `#include "x.h"`, angle-bracket includes are system headers
+    fn c_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+        let re = Regex::new(r#"(?m)^\s*#\s*include\s+"([^"]+)""#).expect("valid include regex");
+        re.captures_iter(content)
+            .map(|caps| dir.join(&caps[1]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::gitignore::GitignoreBuilder;
+
+    fn result_text(result: &CallToolResult) -> String {
+        result.content[0].as_text().unwrap().text.clone()
+    }
+
+    #[tokio::test]
+    async fn test_resolve_rust_modules() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("main.rs"),
+            "mod foo;\npub mod bar;\nmod missing;\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("foo.rs"), "").unwrap();
+        std::fs::create_dir(root.join("bar")).unwrap();
+        std::fs::write(root.join("bar/mod.rs"), "").unwrap();
+
+        let resolver = ImportResolver::new();
+        let result = resolver
+            .resolve(root.join("main.rs").to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let text = result_text(&result);
+        assert!(text.contains("foo.rs"));
+        assert!(text.contains("mod.rs"));
+        assert!(!text.contains("missing"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_python_and_js_imports() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("app.py"),
+            "import os\nfrom .utils import helper\nimport models\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("utils.py"), "").unwrap();
+        std::fs::write(root.join("models.py"), "").unwrap();
+        std::fs::write(
+            root.join("index.ts"),
+            "import React from 'react';\nimport { a } from './lib';\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("lib.ts"), "").unwrap();
+
+        let resolver = ImportResolver::new();
+        let result = resolver
+            .resolve(root.join("app.py").to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let text = result_text(&result);
+        assert!(text.contains("utils.py"));
+        assert!(text.contains("models.py"));
+        assert!(!text.contains("os.py"));
+
+        let result = resolver
+            .resolve(root.join("index.ts").to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let text = result_text(&result);
+        assert!(text.contains("lib.ts"));
+        assert!(!text.contains("react"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_respects_ignore_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::write(
+            root.join("main.c"),
+            "#include <stdio.h>\n#include \"util.h\"\n#include \"secret.h\"\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("util.h"), "").unwrap();
+        std::fs::write(root.join("secret.h"), "").unwrap();
+
+        let mut builder = GitignoreBuilder::new(&root);
+        builder.add_line(None, "secret.h").unwrap();
+        let ignore_patterns = IgnorePatterns::from(builder.build().unwrap());
+
+        let resolver = ImportResolver::new().with_ignore_patterns(ignore_patterns);
+        let result = resolver
+            .resolve(root.join("main.c").to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let text = result_text(&result);
+        assert!(text.contains("util.h"));
+        assert!(!text.contains("secret.h"));
+        assert!(!text.contains("stdio.h"));
+
+        temp_dir.close().unwrap();
+    }
+}
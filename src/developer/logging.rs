@@ -0,0 +1,68 @@
+use rmcp::model::{CallToolResult, LoggingLevel};
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+/// Swaps the server's log filter when a client sends `logging/setLevel`
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// `RUST_LOG` directives with the level as the default, e.g. for the reloadable filter
+pub fn filter_for(level: LoggingLevel) -> EnvFilter {
+    EnvFilter::from_default_env().add_directive(tracing_level(level).into())
+}
+
+// tracing has fewer levels, the syslog-style ones above error all map to error
+fn tracing_level(level: LoggingLevel) -> tracing::Level {
+    match level {
+        LoggingLevel::Debug => tracing::Level::DEBUG,
+        LoggingLevel::Info | LoggingLevel::Notice => tracing::Level::INFO,
+        LoggingLevel::Warning => tracing::Level::WARN,
+        _ => tracing::Level::ERROR,
+    }
+}
+
+/// Rank levels from least to most severe, `LoggingLevel` isn't ordered
+pub(crate) fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// The exit code of a command that failed, read from the status block of a shell result
+pub(crate) fn failed_exit_code(result: &CallToolResult) -> Option<i64> {
+    result.content.iter().find_map(|content| {
+        let status: serde_json::Value = serde_json::from_str(&content.as_text()?.text).ok()?;
+        status
+            .get("exit_code")?
+            .as_i64()
+            .filter(|exit_code| *exit_code != 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::Content;
+
+    #[test]
+    fn test_levels_and_failed_commands() {
+        assert!(severity(LoggingLevel::Warning) > severity(LoggingLevel::Notice));
+        assert!(severity(LoggingLevel::Emergency) > severity(LoggingLevel::Error));
+        assert_eq!(tracing_level(LoggingLevel::Critical), tracing::Level::ERROR);
+
+        let failed = CallToolResult::success(vec![
+            Content::text("oops"),
+            Content::json(serde_json::json!({"exit_code": 2, "signal": null})).unwrap(),
+        ]);
+        assert_eq!(failed_exit_code(&failed), Some(2));
+        let succeeded = CallToolResult::success(vec![
+            Content::json(serde_json::json!({"exit_code": 0})).unwrap(),
+        ]);
+        assert_eq!(failed_exit_code(&succeeded), None);
+    }
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Call counts and durations per tool, shared by every clone of the server
+#[derive(Clone, Default)]
+pub struct Metrics {
+    tools: Arc<Mutex<HashMap<String, ToolTiming>>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ToolTiming {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// One tool's timings as reported by `summary`, durations in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolSummary {
+    pub calls: u64,
+    /// Calls that returned an error or an error result
+    pub errors: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a finished call of `tool` that took `elapsed`
+    pub fn record(&self, tool: &str, elapsed: Duration, failed: bool) {
+        let mut tools = self.tools.lock().unwrap();
+        let call = ToolTiming {
+            calls: 1,
+            errors: u64::from(failed),
+            total: elapsed,
+            max: elapsed,
+        };
+        // Only a tool's first call allocates its key
+        if let Some(timing) = tools.get_mut(tool) {
+            timing.calls += call.calls;
+            timing.errors += call.errors;
+            timing.total += call.total;
+            timing.max = timing.max.max(call.max);
+        } else {
+            tools.insert(tool.to_string(), call);
+        }
+    }
+
+    /// Timings of every tool called so far, sorted by name
+    pub fn summary(&self) -> BTreeMap<String, ToolSummary> {
+        let tools = self.tools.lock().unwrap();
+        tools
+            .iter()
+            .map(|(name, timing)| {
+                let total_ms = millis(timing.total);
+                let summary = ToolSummary {
+                    calls: timing.calls,
+                    errors: timing.errors,
+                    total_ms,
+                    avg_ms: total_ms / timing.calls as f64,
+                    max_ms: millis(timing.max),
+                };
+                (name.clone(), summary)
+            })
+            .collect()
+    }
+}
+
+// Fractional milliseconds, rounded to microseconds
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_summarize() {
+        let metrics = Metrics::new();
+        metrics.record("shell", Duration::from_millis(30), false);
+        metrics.record("shell", Duration::from_millis(10), true);
+        metrics
+            .clone()
+            .record("image_processor", Duration::from_micros(1500), false);
+
+        let summary = metrics.summary();
+        assert_eq!(
+            summary.keys().collect::<Vec<_>>(),
+            vec!["image_processor", "shell"]
+        );
+        assert_eq!(
+            summary["shell"],
+            ToolSummary {
+                calls: 2,
+                errors: 1,
+                total_ms: 40.0,
+                avg_ms: 20.0,
+                max_ms: 30.0,
+            }
+        );
+        assert_eq!(summary["image_processor"].max_ms, 1.5);
+    }
+}
@@ -1,40 +1,157 @@
-use ignore::gitignore::GitignoreBuilder;
+use ignore::{
+    WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
 use rmcp::{
-    RoleServer, ServerHandler,
-    handler::server::{router::tool::ToolRouter, tool::Parameters},
+    Peer, RoleServer, ServerHandler,
+    handler::server::{
+        router::tool::ToolRouter,
+        tool::{Parameters, ToolCallContext},
+    },
     model::ErrorData as McpError,
     model::*,
     schemars,
     service::RequestContext,
-    tool, tool_handler, tool_router,
+    tool, tool_router,
 };
 use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
 
 // Tool descriptions (condensed from original markdown)
 
 // Parameter structs for tools
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct TextEditorParams {
-    #[schemars(description = "Allowed options are: `view`, `write`, `str_replace`, `undo_edit`.")]
+    #[schemars(
+        description = "Allowed options are: `view`, `write`, `str_replace`, `insert`, `search`, `undo_edit`, `undo_all`, `redo`, `restore_version`, `delete`, `move`, `copy`, `append_jsonl`, `stat`, `view_many`, `regex_replace`, `mkdir`, `list_dir`."
+    )]
     pub command: String,
     #[schemars(
-        description = "Absolute path to the file to operate on, e.g. `/repo/file.py`. For the `write` command, parent directories will be created if they do not exist."
+        description = "Absolute path to the file to operate on, e.g. `/repo/file.py`. For the `write` command, parent directories will be created if they do not exist. For the `search` command, a file or a directory to search recursively. For the `view_many` command, the first file to view. For the `mkdir` and `list_dir` commands, the directory."
     )]
     pub path: String,
-    #[schemars(description = "Content to write to the file (required for write command)")]
+    #[schemars(
+        description = "Content to write to the file (required for write command), the text to insert (required for insert command), or a single-line JSON record (required for append_jsonl command)"
+    )]
     pub file_text: Option<String>,
     #[schemars(description = "String to replace (required for str_replace command)")]
     pub old_str: Option<String>,
-    #[schemars(description = "New string to replace with (required for str_replace command)")]
+    #[schemars(
+        description = "New string to replace with (required for str_replace and regex_replace commands, where `$1` or `${name}` insert capture groups), or the absolute destination path (required for move and copy commands)"
+    )]
     pub new_str: Option<String>,
+    #[schemars(
+        description = "Optional for str_replace: match old_str treating any run of whitespace as equivalent, so indentation differences don't prevent a match. The match must still be unique."
+    )]
+    pub whitespace_insensitive: Option<bool>,
+    #[schemars(
+        description = "Optional for str_replace: replace only the Nth (1-based) match of old_str instead of requiring it to be unique"
+    )]
+    pub occurrence: Option<usize>,
+    #[schemars(
+        description = "Optional for str_replace: only match old_str where it isn't part of a longer word, e.g. `count` but not the start of `counter`. Errors if every match is inside a longer word."
+    )]
+    pub whole_word: Option<bool>,
+    #[schemars(
+        description = "Line after which file_text is inserted (required for insert command). 0 inserts at the start of the file."
+    )]
+    pub insert_line: Option<usize>,
+    #[schemars(
+        description = "Regular expression to look for (required for search and regex_replace commands)"
+    )]
+    pub pattern: Option<String>,
+    #[schemars(
+        description = "Optional for restore_version: the version to restore, 1 being the most recent. Omit to list the saved versions."
+    )]
+    pub version: Option<usize>,
+    #[schemars(
+        description = "Optional for write: add file_text to the end of the existing file instead of replacing it. Creates the file if it doesn't exist."
+    )]
+    pub append: Option<bool>,
+    #[schemars(
+        description = "Optional for write: replace an existing file even though it wasn't viewed in this session, when the server requires a view first"
+    )]
+    pub overwrite: Option<bool>,
+    #[schemars(
+        description = "Optional for delete: required to delete a directory and everything in it"
+    )]
+    pub recursive: Option<bool>,
+    #[schemars(
+        description = "Optional for str_replace: show the user a unified diff of the change instead of the edited section"
+    )]
+    pub show_diff: Option<bool>,
+    #[schemars(
+        description = "Optional for str_replace: lines of context around the change in the returned snippet, 0 for only the changed lines. Defaults to 4."
+    )]
+    pub context_lines: Option<usize>,
+    #[schemars(
+        description = "Optional: the file's encoding, e.g. `utf-8`, `utf-16le`, `utf-16be` or `latin1`, which is read as windows-1252. Files with a byte order mark are detected automatically; the choice is remembered for later commands on the file until it is deleted."
+    )]
+    pub encoding: Option<String>,
+    #[schemars(
+        description = "Optional for view_many: absolute paths of further files to view after `path`, up to 50 in all"
+    )]
+    pub paths: Option<Vec<String>>,
+    #[schemars(
+        description = "Optional for regex_replace: replace every match of pattern instead of requiring exactly one"
+    )]
+    pub all: Option<bool>,
+    #[schemars(
+        description = "Optional for view: return only the first N lines. Useful for large files when the total line count isn't known."
+    )]
+    pub head: Option<usize>,
+    #[schemars(
+        description = "Optional for view: return only the last N lines, e.g. the end of a log file. Can't be combined with head."
+    )]
+    pub tail: Option<usize>,
+    #[schemars(
+        description = "Optional for view: byte offset to continue viewing a file over the size limit from, as given by the previous chunk. Such files are returned a chunk at a time, starting at offset 0."
+    )]
+    pub offset: Option<u64>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct ShellParams {
     #[schemars(description = "Command to execute")]
     pub command: String,
+    #[schemars(
+        description = "Optional: strip leading/trailing blank lines and collapse runs of blank lines in the output. Set to false when exact output matters. Defaults to the server setting (on unless SHELL_TRIM_BLANK_LINES=false)."
+    )]
+    pub trim_blank_lines: Option<bool>,
+    #[schemars(
+        description = "Optional: absolute path of the directory to run the command in. Defaults to the server's working directory."
+    )]
+    pub cwd: Option<String>,
+    #[schemars(
+        description = "Optional: capture stdout and stderr separately and return them as JSON with the exit code, instead of one merged output"
+    )]
+    pub separate_streams: Option<bool>,
+    #[schemars(
+        description = "Optional: run the command in a persistent shell with this id, so `cd` and exported variables carry over to later commands with the same id. Sessions close after a period of inactivity or when the shell exits. Not supported on Windows."
+    )]
+    pub session_id: Option<String>,
+    #[schemars(
+        description = "Optional: environment variables for this command only, e.g. {\"RUST_LOG\": \"debug\"}. They are added to the server's environment and don't persist to later calls."
+    )]
+    pub env: Option<HashMap<String, String>>,
+    #[schemars(
+        description = "Optional: check the command against the server's restrictions and return the exact command line and shell that would run, without running it. Useful for getting a command approved before executing it."
+    )]
+    pub dry_run: Option<bool>,
+    #[schemars(
+        description = "Optional: text written to the command's standard input, e.g. a patch for `git apply` or content for `tee`. Without it the command's stdin is empty. Can't be combined with session_id."
+    )]
+    pub stdin: Option<String>,
+    #[schemars(
+        description = "Optional: \"text\" (default) for the combined output as a terminal shows it, or \"json\" for a single JSON object with command, stdout, stderr, exit_code and duration_ms. Can't be combined with session_id."
+    )]
+    pub output_format: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -45,16 +162,174 @@ pub struct ScreenCaptureParams {
         description = "Optional: the exact title of the window to capture. use the list_windows tool to find the available windows."
     )]
     pub window_title: Option<String>,
+    #[schemars(
+        description = "Optional: the id of the window to capture, from the list_windows tool. Unlike a title it doesn't change while the window is open, and it takes precedence over window_title."
+    )]
+    pub window_id: Option<u32>,
+    #[schemars(
+        description = "Optional: left edge of a region to capture, in pixels from the display or window's left edge. x, y, width and height must be given together."
+    )]
+    pub x: Option<u32>,
+    #[schemars(description = "Optional: top edge of the region to capture, in pixels")]
+    pub y: Option<u32>,
+    #[schemars(description = "Optional: width of the region to capture, in pixels")]
+    pub width: Option<u32>,
+    #[schemars(description = "Optional: height of the region to capture, in pixels")]
+    pub height: Option<u32>,
+    #[schemars(
+        description = "Optional: image format, \"png\" (default) or \"jpeg\". JPEG is much smaller for photo-heavy screens."
+    )]
+    pub format: Option<String>,
+    #[schemars(description = "Optional: JPEG quality from 1 to 100, defaults to 85")]
+    pub quality: Option<u8>,
+    #[schemars(
+        description = "Optional: capture every display, returning one labeled image per display. Can't be combined with display, window_title or a region."
+    )]
+    pub all_displays: Option<bool>,
+    #[schemars(
+        description = "Optional: how window_title is matched, \"exact\" (default), \"contains\" (case-insensitive substring) or \"regex\". If several windows match, the error lists them."
+    )]
+    pub match_mode: Option<String>,
+    #[schemars(
+        description = "Optional: draw the mouse pointer on the screenshot. Its position may be approximate, and it's unavailable on some platforms."
+    )]
+    pub include_cursor: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SaveReferenceScreenshotParams {
+    #[schemars(
+        description = "Name of the reference, using only letters, digits, '-' and '_'. Saving under an existing name replaces it."
+    )]
+    pub name: String,
+    #[schemars(description = "The display number to capture (0 is main display)")]
+    pub display: Option<i32>,
+    #[schemars(
+        description = "Optional: the region of the display to capture, in physical pixels from its top-left corner, clamped to the display. Captures the whole display when omitted."
+    )]
+    pub region: Option<CaptureRegion>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CompareReferenceScreenshotParams {
+    #[schemars(description = "Name of a reference saved with save_reference_screenshot")]
+    pub name: String,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct ImageProcessorParams {
-    #[schemars(description = "Absolute path to the image file to process")]
-    pub path: String,
+    #[schemars(description = "Absolute path to the image file to process. Give this or paths.")]
+    pub path: Option<String>,
+    #[schemars(
+        description = "Optional: absolute paths of up to 16 images to process with the same settings. Files that fail are skipped with a warning."
+    )]
+    pub paths: Option<Vec<String>>,
     #[schemars(
         description = "Optional resize factor to reduce image size. Allowed values: \"1/2\", \"1/4\""
     )]
     pub resize: Option<String>,
+    #[schemars(
+        description = "Optional: resize to this width in pixels. Keeps the aspect ratio unless height is also given. Replaces resize and the width cap (768px unless the server sets IMAGE_MAX_WIDTH)."
+    )]
+    pub width: Option<u32>,
+    #[schemars(
+        description = "Optional: resize to this height in pixels. Keeps the aspect ratio unless width is also given."
+    )]
+    pub height: Option<u32>,
+    #[schemars(
+        description = "Optional: absolute path to write the processed image to instead of returning it. The format follows the file extension."
+    )]
+    pub output_path: Option<String>,
+    #[schemars(
+        description = "Optional: replace output_path when it already exists, which is refused otherwise. The source image can't be replaced."
+    )]
+    pub overwrite: Option<bool>,
+    #[schemars(
+        description = "Optional: rectangle to crop to as [x, y, width, height], applied before rotating and resizing"
+    )]
+    pub crop: Option<[u32; 4]>,
+    #[schemars(
+        description = "Optional: clockwise rotation in degrees, a multiple of 90, applied before resizing"
+    )]
+    pub rotate: Option<i32>,
+    #[schemars(
+        description = "Optional: output format, \"png\", \"jpeg\" or \"webp\". Defaults to the input format, with WebP input becoming JPEG."
+    )]
+    pub output_format: Option<String>,
+    #[schemars(description = "Optional: JPEG quality from 1 to 100, default 85")]
+    pub quality: Option<u8>,
+    #[schemars(
+        description = "Optional: also return the EXIF metadata (dimensions, orientation, camera, DateTimeOriginal) as JSON"
+    )]
+    pub read_metadata: Option<bool>,
+    #[schemars(
+        description = "Optional: turn the image upright according to its EXIF orientation before other operations"
+    )]
+    pub auto_orient: Option<bool>,
+    #[schemars(
+        description = "Optional: convert to grayscale after resizing. Shrinks screenshots and helps text recognition."
+    )]
+    pub grayscale: Option<bool>,
+    #[schemars(
+        description = "Optional: change the contrast by -100 to 100 percent after resizing and grayscale, positive values increase it"
+    )]
+    pub contrast: Option<f32>,
+    #[schemars(
+        description = "Optional: also return the text recognized in the processed image with Tesseract. Only available when the server was built with the ocr feature."
+    )]
+    pub ocr: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ResolveImportsParams {
+    #[schemars(description = "Absolute path to the source file whose imports should be resolved")]
+    pub path: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CheckUnchangedParams {
+    #[schemars(
+        description = "Absolute path to a file previously read with the text_editor view command"
+    )]
+    pub path: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CountStatsParams {
+    #[schemars(description = "Absolute path to a file, or to a directory when a glob is given")]
+    pub path: String,
+    #[schemars(
+        description = "Optional glob (e.g. `*.rs` or `src/**/*.ts`) selecting files under the directory to count and total"
+    )]
+    pub glob: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct FindFilesParams {
+    #[schemars(
+        description = "Gitignore-style glob such as `*.rs` or `src/**/mod.rs`; a bare file name like `Cargo.toml` matches at any depth"
+    )]
+    pub pattern: String,
+    #[schemars(
+        description = "Optional absolute path to the directory to search, defaults to the workspace root"
+    )]
+    pub root: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct GitStatusParams {
+    #[schemars(
+        description = "Optional directory inside the repository, defaults to the workspace root"
+    )]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SetCwdParams {
+    #[schemars(
+        description = "Absolute path to an existing directory to use as the working directory"
+    )]
+    pub path: String,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -80,28 +355,455 @@ pub struct WorkflowParams {
     )]
     pub branch_from_step: Option<i32>,
     #[schemars(
-        description = "A unique identifier for this branch (required when creating a branch)"
+        description = "A unique identifier for this branch (required when creating a branch). Pass an existing branch_id without branch_from_step to resume that branch."
     )]
     pub branch_id: Option<String>,
     #[schemars(
         description = "Indicates whether additional steps are required to complete the workflow"
     )]
     pub needs_more_steps: Option<bool>,
+    #[schemars(
+        description = "Optional status detail: \"compact\" (single-line summary), \"normal\" (default JSON status) or \"verbose\" (adds recent steps and branch contents)"
+    )]
+    pub verbosity: Option<String>,
 }
 
+pub mod config;
+pub mod find;
+pub mod git;
 pub mod image_processor;
+pub mod imports;
 pub mod lang;
+pub mod logging;
+pub mod metrics;
+pub mod ocr;
 pub mod screen_capture;
 pub mod shell;
+pub mod stats;
 pub mod text_editor;
+pub mod watch;
 pub mod workflow;
 
-pub use image_processor::ImageProcessor;
-pub use screen_capture::ScreenCapture;
-pub use shell::Shell;
-pub use text_editor::TextEditor;
+pub use config::Config;
+pub use find::FileFinder;
+pub use image_processor::{ImageProcessor, ProcessOptions};
+pub use imports::ImportResolver;
+pub use metrics::Metrics;
+pub use screen_capture::{
+    CaptureFormat, CaptureOptions, CapturePolicy, CaptureRegion, ScreenCapture, WindowMatch,
+};
+pub use shell::{
+    CommandOutput, CommandPolicy, CommandReport, CommandStatus, ExecuteOptions, OutputFormat,
+    Shell, Termination,
+};
+pub use stats::FileStats;
+pub use text_editor::{LineWindow, StrReplaceOptions, TextEditor, WriteOptions};
+pub use watch::FileWatcher;
 pub use workflow::Workflow;
 
+/// Default number of image/screen operations allowed to run at once
+const DEFAULT_MAX_CONCURRENT_IMAGE_OPS: usize = 2;
+
+/// Default width in pixels wider images and screenshots are downscaled to
+pub(crate) const DEFAULT_IMAGE_MAX_WIDTH: u32 = 768;
+
+/// JPEG quality of images and screenshots when none is requested, high but still compressed
+pub(crate) const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// Fail for a requested JPEG quality outside 1 to 100
+pub(crate) fn validate_jpeg_quality(quality: Option<u8>) -> Result<(), McpError> {
+    match quality.filter(|quality| !(1..=100).contains(quality)) {
+        Some(quality) => Err(McpError::invalid_params(
+            format!("Invalid quality {quality}, it must be between 1 and 100"),
+            None,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Default number of directory levels shown by the `file://workspace` resource
+const DEFAULT_WORKSPACE_TREE_DEPTH: usize = 3;
+
+/// Most entries the `file://workspace` resource lists before truncating
+const MAX_WORKSPACE_TREE_ENTRIES: usize = 500;
+
+/// Tools that need a display, left out on headless servers unless enabled explicitly
+const DISPLAY_TOOLS: &[&str] = &[
+    "screen_capture",
+    "list_windows",
+    "list_displays",
+    "save_reference_screenshot",
+    "compare_reference_screenshot",
+];
+
+/// Resolves once the request is cancelled, and never without a token
+pub(crate) async fn cancelled(cancel: Option<&CancellationToken>) {
+    match cancel {
+        Some(cancel) => cancel.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Fail when the request was cancelled, between steps that can't be interrupted
+pub(crate) fn check_cancelled(cancel: Option<&CancellationToken>) -> Result<(), McpError> {
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(cancelled_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn cancelled_error() -> McpError {
+    McpError::invalid_request("The request was cancelled", None)
+}
+
+// Wait for a slot in the shared image/screen operation limit, if one is configured
+pub(crate) async fn acquire_permit(
+    limit: &Option<Arc<Semaphore>>,
+) -> Result<Option<OwnedSemaphorePermit>, McpError> {
+    match limit {
+        Some(semaphore) => semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map(Some)
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to acquire operation slot: {e}"), None)
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Gitignore-style patterns restricting the paths tools may access. Clones share the
+/// patterns, so replacing them with `set` takes effect in every tool holding one
+#[derive(Clone, Default)]
+pub struct IgnorePatterns {
+    patterns: Arc<RwLock<Option<Arc<Gitignore>>>>,
+}
+
+impl IgnorePatterns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the patterns, e.g. after the working directory changed
+    pub fn set(&self, patterns: Arc<Gitignore>) {
+        *self.patterns.write().unwrap() = Some(patterns);
+    }
+
+    /// Whether `path` is restricted. Directory patterns such as `build/` only match when
+    /// `is_dir` is set
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.patterns
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|patterns| patterns.matched(path, is_dir).is_ignore())
+    }
+
+    /// Fail for a restricted path
+    pub fn check(&self, path: &Path, is_dir: bool) -> Result<(), McpError> {
+        if self.is_ignored(path, is_dir) {
+            return Err(McpError::invalid_request(
+                format!(
+                    "The path '{display}' is restricted by ignore patterns",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail for a restricted path, or one of the ignore and allow files the patterns are read
+    /// from, even when it doesn't exist yet
+    pub fn check_writable(&self, path: &Path, is_dir: bool) -> Result<(), McpError> {
+        self.check(path, is_dir)?;
+        if is_access_control_file(path) {
+            return Err(McpError::invalid_request(
+                format!(
+                    "The path '{display}' is an ignore or allow file, which can't be changed through the tools",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<Gitignore> for IgnorePatterns {
+    fn from(patterns: Gitignore) -> Self {
+        let ignore_patterns = Self::new();
+        ignore_patterns.set(Arc::new(patterns));
+        ignore_patterns
+    }
+}
+
+/// Ignore files read in each directory of a project, later ones taking precedence
+const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".developerignore"];
+
+/// Project-level file of paths to permit even though an ignore file restricts them
+const ALLOW_FILE_NAME: &str = ".developerallow";
+
+/// Whether `path` is named like a file access control is read from, so changing it would
+/// change what the tools may access
+pub(crate) fn is_access_control_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        name == ALLOW_FILE_NAME || IGNORE_FILE_NAMES.iter().any(|ignore| name == *ignore)
+    })
+}
+
+// Build access control patterns from the ignore files in a directory tree, plus an optional extra file
+pub(crate) fn build_ignore_patterns(root: &Path, ignore_file: Option<&Path>) -> Arc<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+
+    // The same files git reads before the tree's own, which the walk below honors as well
+    let git_excludes = ignore::gitignore::gitconfig_excludes_path()
+        .into_iter()
+        .chain([root.join(".git/info/exclude")]);
+    for path in git_excludes.filter(|path| path.is_file()) {
+        if let Some(e) = builder.add(&path) {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read ignore file");
+        }
+    }
+
+    // Parents are visited before their subdirectories, so deeper files take precedence like
+    // in git. The walk honors the ignore files too, so ignored trees aren't searched
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(".developerignore")
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|entry| {
+            entry.file_type().is_some_and(|t| t.is_dir()) && entry.file_name() != ".git"
+        })
+        .build();
+    for dir in walker.flatten() {
+        for name in IGNORE_FILE_NAMES {
+            let path = dir.path().join(name);
+            if path.is_file() {
+                add_ignore_file(&mut builder, root, &path);
+            }
+        }
+    }
+
+    if let Some((ignore_file, e)) =
+        ignore_file.and_then(|ignore_file| Some((ignore_file, builder.add(ignore_file)?)))
+    {
+        tracing::warn!(path = %ignore_file.display(), error = %e, "Failed to read ignore file");
+    }
+
+    // Added last so the allowlist overrides every ignore file
+    let allow_file = root.join(ALLOW_FILE_NAME);
+    if allow_file.is_file() {
+        add_allow_file(&mut builder, &allow_file);
+    }
+
+    // Build the ignore patterns
+    Arc::new(builder.build().unwrap_or_else(|_| {
+        // Fallback to empty gitignore if building fails
+        GitignoreBuilder::new(root)
+            .build()
+            .expect("Failed to create empty gitignore")
+    }))
+}
+
+// Add an ignore file's patterns, scoped to its directory when it is below the root
+fn add_ignore_file(builder: &mut GitignoreBuilder, root: &Path, path: &Path) {
+    let dir = path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(root).ok())
+        .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    if dir.is_empty() {
+        if let Some(e) = builder.add(path) {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read ignore file");
+        }
+        return;
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read ignore file");
+            return;
+        }
+    };
+    for pattern in content.lines().filter_map(|line| scope_pattern(line, &dir)) {
+        if let Err(e) = builder.add_line(Some(path.to_path_buf()), &pattern) {
+            tracing::warn!(path = %path.display(), error = %e, "Invalid ignore pattern");
+        }
+    }
+}
+
+// Add an allowlist's patterns as negations, a `!` line restricts a path the allowlist would permit
+fn add_allow_file(builder: &mut GitignoreBuilder, path: &Path) {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to read allow file");
+            return;
+        }
+    };
+    for line in content.lines().map(str::trim_end) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let pattern = match line.strip_prefix('!') {
+            Some(restricted) => restricted.to_string(),
+            None => format!("!{line}"),
+        };
+        if let Err(e) = builder.add_line(Some(path.to_path_buf()), &pattern) {
+            tracing::warn!(path = %path.display(), error = %e, "Invalid allow pattern");
+        }
+    }
+}
+
+// Building walks the whole tree, so it runs off the async runtime's threads
+async fn load_ignore_patterns(
+    root: PathBuf,
+    ignore_file: Option<PathBuf>,
+) -> Result<Arc<Gitignore>, McpError> {
+    tokio::task::spawn_blocking(move || build_ignore_patterns(&root, ignore_file.as_deref()))
+        .await
+        .map_err(|e| McpError::internal_error(format!("Failed to read ignore files: {e}"), None))
+}
+
+// Rewrite a gitignore line from a file in `dir` (relative to the root) to match from the root
+fn scope_pattern(line: &str, dir: &str) -> Option<String> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negation, pattern) = match line.strip_prefix('!') {
+        Some(pattern) => ("!", pattern),
+        None => ("", line),
+    };
+    // A slash anywhere but the end anchors the pattern to the file's directory,
+    // otherwise it matches at any depth below it
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    // The directory is a literal path, not a glob
+    let dir: String = dir
+        .chars()
+        .flat_map(|c| {
+            let escape = matches!(c, '*' | '?' | '[' | ']' | '\\').then_some('\\');
+            escape.into_iter().chain([c])
+        })
+        .collect();
+    Some(if anchored {
+        format!("{negation}/{dir}/{pattern}")
+    } else {
+        format!("{negation}/{dir}/**/{pattern}")
+    })
+}
+
+/// Column at which `list-tools` wraps descriptions
+const TOOL_TEXT_WIDTH: usize = 80;
+
+// Word-wrap each line of `text` to `width` columns after `indent`, keeping blank lines
+fn wrap_text(text: &str, width: usize, indent: &str) -> String {
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            if !current.is_empty() && indent.len() + current.len() + 1 + word.len() > width {
+                wrapped.push(format!("{indent}{current}"));
+                current.clear();
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        wrapped.push(if current.is_empty() {
+            String::new()
+        } else {
+            format!("{indent}{current}")
+        });
+    }
+    wrapped.join("\n")
+}
+
+// An indented listing of `root` down to `max_depth` levels, skipping hidden and ignored entries
+pub(crate) fn workspace_tree(
+    root: &Path,
+    ignore_patterns: IgnorePatterns,
+    max_depth: usize,
+) -> String {
+    let walker = ignore::WalkBuilder::new(root)
+        .max_depth(Some(max_depth))
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !ignore_patterns.is_ignored(entry.path(), is_dir)
+        })
+        .build();
+
+    let mut lines = Vec::new();
+    let mut entry_count = 0;
+    for entry in walker.flatten() {
+        if entry.depth() == 0 {
+            lines.push(format!("{}/", root.display()));
+            continue;
+        }
+        entry_count += 1;
+        if entry_count > MAX_WORKSPACE_TREE_ENTRIES {
+            continue;
+        }
+        let suffix = if entry.file_type().is_some_and(|t| t.is_dir()) {
+            "/"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "{indent}{name}{suffix}",
+            indent = "  ".repeat(entry.depth()),
+            name = entry.file_name().to_string_lossy()
+        ));
+    }
+    if entry_count > MAX_WORKSPACE_TREE_ENTRIES {
+        lines.push(format!(
+            "[Truncated: showing {MAX_WORKSPACE_TREE_ENTRIES} of {entry_count} entries]"
+        ));
+    }
+    lines.join("\n")
+}
+
+// The canonical form of `path` if it lies within `sandbox_root`, which must be canonical.
+// Paths that don't exist yet are judged by their deepest existing ancestor.
+pub(crate) fn confine_to_sandbox(path: &Path, sandbox_root: &Path) -> Result<PathBuf, McpError> {
+    let outside = || {
+        McpError::invalid_params(
+            format!(
+                "The path {} is outside the sandbox {}",
+                path.display(),
+                sandbox_root.display()
+            ),
+            None,
+        )
+    };
+
+    let (existing, canonical) = path
+        .ancestors()
+        .find_map(|ancestor| Some((ancestor, ancestor.canonicalize().ok()?)))
+        .ok_or_else(outside)?;
+    let rest = path.strip_prefix(existing).map_err(|_| outside())?;
+    // `..` below a missing directory can't be resolved, so it can't be trusted
+    if rest
+        .components()
+        .any(|component| !matches!(component, std::path::Component::Normal(_)))
+    {
+        return Err(outside());
+    }
+
+    let confined = canonical.join(rest);
+    if !confined.starts_with(sandbox_root) {
+        return Err(outside());
+    }
+    Ok(confined)
+}
+
 // Path utility functions
 pub(crate) fn expand_path(path_str: &str) -> String {
     if cfg!(windows) {
@@ -129,12 +831,41 @@ pub(crate) fn is_absolute_path(path_str: &str) -> bool {
 }
 
 pub(crate) fn normalize_line_endings(text: &str) -> String {
-    if cfg!(windows) {
-        // Ensure CRLF line endings on Windows
-        text.replace("\r\n", "\n").replace("\n", "\r\n")
-    } else {
-        // Ensure LF line endings on Unix
-        text.replace("\r\n", "\n")
+    LineEnding::platform().apply(text)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub(crate) fn platform() -> Self {
+        if cfg!(windows) { Self::Crlf } else { Self::Lf }
+    }
+
+    /// The ending used by most lines of `text`, `None` if it has no line breaks
+    pub(crate) fn detect(text: &str) -> Option<Self> {
+        let line_breaks = text.matches('\n').count();
+        if line_breaks == 0 {
+            return None;
+        }
+        let crlf = text.matches("\r\n").count();
+        Some(if crlf * 2 > line_breaks {
+            Self::Crlf
+        } else {
+            Self::Lf
+        })
+    }
+
+    /// Rewrite every line break in `text` to this ending
+    pub(crate) fn apply(self, text: &str) -> String {
+        let lf = text.replace("\r\n", "\n");
+        match self {
+            Self::Lf => lf,
+            Self::Crlf => lf.replace('\n', "\r\n"),
+        }
     }
 }
 
@@ -144,7 +875,32 @@ pub struct Developer {
     shell: Shell,
     screen_capture: ScreenCapture,
     image_processor: ImageProcessor,
+    import_resolver: ImportResolver,
+    file_stats: FileStats,
+    file_finder: FileFinder,
+    file_watcher: FileWatcher,
     workflow: Workflow,
+    // Access control patterns, shared with every tool that checks paths
+    ignore_patterns: IgnorePatterns,
+    // Extra ignore file from the config, applied again whenever the working directory changes
+    ignore_file: Option<PathBuf>,
+    // Base for relative paths, which are rejected when unset; follows set_cwd
+    workspace_root: Arc<RwLock<Option<PathBuf>>>,
+    // Canonical directory every resolved path must stay within, when sandboxed
+    sandbox_root: Option<PathBuf>,
+    // Tools removed from the router by configuration
+    disabled_tools: Arc<HashSet<String>>,
+    // Directory levels listed by the file://workspace resource
+    workspace_tree_depth: usize,
+    // Reloads the tracing filter when the client changes the log level
+    log_filter: Option<logging::LogFilterHandle>,
+    // Least severe level sent to this connection's client as log notifications
+    log_level: Arc<RwLock<LoggingLevel>>,
+    // Call counts and durations of each tool
+    metrics: Metrics,
+    // Identifies the client connection this instance serves, see `for_connection`
+    connection: u64,
+    next_connection: Arc<AtomicU64>,
     tool_router: ToolRouter<Developer>,
 }
 
@@ -157,39 +913,204 @@ impl Default for Developer {
 #[tool_router]
 impl Developer {
     pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Build the server from a config file's settings, environment variables take precedence
+    pub fn with_config(config: Config) -> Self {
         let cwd = std::env::current_dir().expect("should have a current working dir");
 
         // Initialize gitignore patterns from .gitignore files
-        let mut builder = GitignoreBuilder::new(&cwd);
+        let ignore_patterns = IgnorePatterns::new();
+        ignore_patterns.set(build_ignore_patterns(&cwd, config.ignore_file.as_deref()));
+
+        // Configure text editor history limit from environment or use default
+        let text_editor_max_history =
+            config::env_or("TEXT_EDITOR_MAX_HISTORY", config.text_editor.max_history).unwrap_or(10);
+
+        // Optionally refuse to replace existing files the agent hasn't viewed
+        let text_editor_require_view =
+            config::env_or("TEXT_EDITOR_REQUIRE_VIEW", config.text_editor.require_view)
+                .unwrap_or(false);
 
-        // Add .gitignore file if it exists
-        let gitignore_path = cwd.join(".gitignore");
-        if gitignore_path.exists() {
-            let _ = builder.add(&gitignore_path);
+        // Kill shell commands that run too long instead of hanging the server
+        let shell_timeout = config::env_or("SHELL_TIMEOUT_SECS", config.shell.timeout_secs)
+            .filter(|secs| *secs > 0)
+            .map(std::time::Duration::from_secs)
+            .unwrap_or(shell::DEFAULT_TIMEOUT);
+
+        // Truncate oversized shell output by default, or reject it
+        let shell_output_overflow = std::env::var("SHELL_OUTPUT_OVERFLOW")
+            .ok()
+            .and_then(|s| s.parse::<shell::OutputOverflow>().ok())
+            .unwrap_or_default();
+
+        // Close persistent shell sessions nobody has used for a while
+        let shell_session_idle_timeout = std::env::var("SHELL_SESSION_IDLE_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(std::time::Duration::from_secs)
+            .unwrap_or(shell::DEFAULT_SESSION_IDLE_TIMEOUT);
+
+        // Tidy blank lines in shell output unless exact output is wanted
+        let shell_trim_blank_lines =
+            config::env_or("SHELL_TRIM_BLANK_LINES", config.shell.trim_blank_lines).unwrap_or(true);
+
+        // Bound concurrent image decoding and screen captures, shared by both tools
+        let max_image_ops = std::env::var("IMAGE_MAX_CONCURRENT_OPS")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_IMAGE_OPS);
+        let image_ops_limit = Arc::new(Semaphore::new(max_image_ops));
+
+        // Downscale wide images for both tools, 0 returns them at full size
+        let image_max_width = config::env_or("IMAGE_MAX_WIDTH", config.image.max_width)
+            .map(|width| (width > 0).then_some(width))
+            .unwrap_or(Some(DEFAULT_IMAGE_MAX_WIDTH));
+
+        // Reference screenshots for visual comparisons, kept in the temp dir unless configured
+        let mut screen_capture = ScreenCapture::new()
+            .with_concurrency_limit(image_ops_limit.clone())
+            .with_max_width(image_max_width);
+        if let Ok(reference_dir) = std::env::var("SCREEN_CAPTURE_REFERENCE_DIR") {
+            screen_capture = screen_capture.with_reference_dir(reference_dir.into());
         }
 
-        // Build the ignore patterns
-        let ignore_patterns = Arc::new(builder.build().unwrap_or_else(|_| {
-            // Fallback to empty gitignore if building fails
-            GitignoreBuilder::new(&cwd)
-                .build()
-                .expect("Failed to create empty gitignore")
-        }));
+        // Restrict which displays and windows the capture tools can see
+        let env_list = |name: &str| {
+            std::env::var(name).ok().map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>()
+            })
+        };
+        screen_capture = screen_capture.with_policy(CapturePolicy {
+            allowed_displays: env_list("SCREEN_CAPTURE_ALLOWED_DISPLAYS").map(|displays| {
+                displays
+                    .iter()
+                    .filter_map(|display| display.parse::<usize>().ok())
+                    .collect()
+            }),
+            allowed_windows: env_list("SCREEN_CAPTURE_ALLOWED_WINDOWS"),
+            denied_windows: env_list("SCREEN_CAPTURE_DENIED_WINDOWS").unwrap_or_default(),
+        });
+
+        // How much of the project the file://workspace resource shows
+        let workspace_tree_depth = std::env::var("WORKSPACE_TREE_DEPTH")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|depth| *depth > 0)
+            .unwrap_or(DEFAULT_WORKSPACE_TREE_DEPTH);
+
+        // Keep every path inside one directory tree for hardened deployments
+        let sandbox_root = std::env::var("DEVELOPER_SANDBOX_ROOT")
+            .ok()
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from);
 
-        // Configure text editor history limit from environment or use default
-        let text_editor_max_history = std::env::var("TEXT_EDITOR_MAX_HISTORY")
+        // Bound the files clients can subscribe to, each one holds an OS watch handle
+        let max_watchers = std::env::var("RESOURCE_MAX_WATCHERS")
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
-            .unwrap_or(10);
+            .unwrap_or(watch::DEFAULT_MAX_WATCHERS);
 
-        Self {
-            text_editor: TextEditor::new_with_history_limit(text_editor_max_history)
+        // Keep workflow steps and branches across restarts when a state file is configured
+        let mut workflow = Workflow::new(true, None, true);
+        if let Ok(state_file) = std::env::var("WORKFLOW_STATE_FILE") {
+            workflow = workflow.with_state_file(state_file.into());
+        }
+
+        let mut text_editor = TextEditor::new_with_history_limit(text_editor_max_history)
+            .with_require_view_before_write(text_editor_require_view)
+            .with_ignore_patterns(ignore_patterns.clone());
+        if let Some(max_file_bytes) = config::env_or(
+            "TEXT_EDITOR_MAX_FILE_BYTES",
+            config.text_editor.max_file_bytes,
+        ) {
+            text_editor = text_editor.with_max_file_bytes(max_file_bytes);
+        }
+        if let Some(max_chars) =
+            config::env_or("TEXT_EDITOR_MAX_CHARS", config.text_editor.max_chars)
+        {
+            text_editor = text_editor.with_max_chars(max_chars);
+        }
+
+        // Only expose the tools the deployment asked for, the environment overriding the config
+        let enabled_tools = env_list("DEVELOPER_ENABLED_TOOLS").or(config.enabled_tools);
+        let disabled_tools = env_list("DEVELOPER_DISABLED_TOOLS")
+            .or(config.disabled_tools)
+            .unwrap_or_default();
+        let mut tool_router = Self::tool_router();
+        for name in enabled_tools.iter().flatten().chain(&disabled_tools) {
+            if !tool_router.has_route(name) {
+                tracing::warn!(
+                    tool = name.as_str(),
+                    "Unknown tool in the enabled or disabled tools"
+                );
+            }
+        }
+        // Without a display server the capture tools could only fail
+        let headless = !screen_capture::display_server_configured();
+        if headless {
+            tracing::info!("No display server configured, screen capture tools are disabled");
+        }
+        let mut removed_tools = HashSet::new();
+        tool_router.map.retain(|name, _| {
+            let explicitly_enabled = enabled_tools
+                .as_ref()
+                .map(|enabled_tools| enabled_tools.iter().any(|tool| tool == name));
+            let enabled = explicitly_enabled.unwrap_or(true)
+                && !disabled_tools.iter().any(|tool| tool == name)
+                && !(headless
+                    && DISPLAY_TOOLS.contains(&name.as_ref())
+                    && explicitly_enabled.is_none());
+            if !enabled {
+                removed_tools.insert(name.to_string());
+            }
+            enabled
+        });
+
+        let developer = Self {
+            text_editor,
+            shell: Shell::new_with_config(shell::ShellConfig::with_overrides(
+                config.shell.executable,
+                config.shell.arg,
+            ))
+            .with_timeout(shell_timeout)
+            .with_session_idle_timeout(shell_session_idle_timeout)
+            .with_output_overflow(shell_output_overflow)
+            .with_trim_blank_lines(shell_trim_blank_lines)
+            .with_ignore_patterns(ignore_patterns.clone()),
+            screen_capture,
+            image_processor: ImageProcessor::new()
+                .with_concurrency_limit(image_ops_limit)
+                .with_max_width(image_max_width)
                 .with_ignore_patterns(ignore_patterns.clone()),
-            shell: Shell::new().with_ignore_patterns(ignore_patterns),
-            screen_capture: ScreenCapture::new(),
-            image_processor: ImageProcessor::new(),
-            workflow: Workflow::new(true, None, true),
-            tool_router: Self::tool_router(),
+            import_resolver: ImportResolver::new().with_ignore_patterns(ignore_patterns.clone()),
+            file_stats: FileStats::new().with_ignore_patterns(ignore_patterns.clone()),
+            file_finder: FileFinder::new().with_ignore_patterns(ignore_patterns.clone()),
+            file_watcher: FileWatcher::new().with_max_watchers(max_watchers),
+            workflow,
+            ignore_patterns,
+            ignore_file: config.ignore_file,
+            workspace_root: Arc::new(RwLock::new(None)),
+            sandbox_root: None,
+            disabled_tools: Arc::new(removed_tools),
+            workspace_tree_depth,
+            log_filter: None,
+            log_level: Arc::new(RwLock::new(LoggingLevel::Info)),
+            metrics: Metrics::new(),
+            connection: 0,
+            next_connection: Arc::new(AtomicU64::new(1)),
+            tool_router,
+        };
+        match sandbox_root {
+            Some(root) => developer.with_sandbox_root(root),
+            None => developer,
         }
     }
 
@@ -203,6 +1124,141 @@ impl Developer {
         }
     }
 
+    /// Resolve relative paths against `root` and root the ignore patterns there instead of the working directory
+    pub fn with_workspace_root(self, root: PathBuf) -> Self {
+        self.ignore_patterns
+            .set(build_ignore_patterns(&root, self.ignore_file.as_deref()));
+        *self.workspace_root.write().unwrap() = Some(root);
+        self
+    }
+
+    /// A copy of the server for another client connection, sharing the tools and their state
+    /// but keeping its own resource subscriptions and log level
+    pub fn for_connection(&self) -> Self {
+        Self {
+            connection: self.next_connection.fetch_add(1, Ordering::Relaxed),
+            log_level: Arc::new(RwLock::new(LoggingLevel::Info)),
+            ..self.clone()
+        }
+    }
+
+    /// Apply levels set with `logging/setLevel` to the server's own tracing output
+    pub fn with_log_filter(mut self, log_filter: logging::LogFilterHandle) -> Self {
+        self.log_filter = Some(log_filter);
+        self
+    }
+
+    // Send a log notification to the client, unless it asked for more severe levels only
+    async fn notify_log(
+        &self,
+        peer: &Peer<RoleServer>,
+        level: LoggingLevel,
+        data: serde_json::Value,
+    ) {
+        if logging::severity(level) < logging::severity(*self.log_level.read().unwrap()) {
+            return;
+        }
+        let notification = LoggingMessageNotificationParam {
+            level,
+            logger: Some("developer".to_string()),
+            data,
+        };
+        if let Err(e) = peer.notify_logging_message(notification).await {
+            tracing::debug!("Failed to send log notification: {e}");
+        }
+    }
+
+    /// Reject any path that doesn't resolve to somewhere inside `root`, following symlinks
+    pub fn with_sandbox_root(mut self, root: PathBuf) -> Self {
+        self.sandbox_root = Some(root.canonicalize().unwrap_or(root));
+        self
+    }
+
+    // The server_capabilities report, tools sorted by name
+    fn capabilities(&self) -> serde_json::Value {
+        let mut tools: Vec<String> = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .collect();
+        tools.sort();
+        let mut disabled_tools: Vec<&String> = self.disabled_tools.iter().collect();
+        disabled_tools.sort();
+
+        json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "platform": {
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+            },
+            "tools": tools,
+            "disabled_tools": disabled_tools,
+            "limits": {
+                "max_file_bytes": self.text_editor.max_file_bytes(),
+                "max_chars": self.text_editor.max_chars(),
+                "max_history": self.text_editor.max_history(),
+                "shell_timeout_secs": self.shell.timeout().as_secs(),
+                "image_max_width": self.screen_capture.max_width(),
+            },
+            "workspace_root": self.workspace_root.read().unwrap().clone(),
+            "sandbox_root": self.sandbox_root,
+            "screen_capture": {
+                "display_available": ScreenCapture::display_available(),
+            },
+            "image_processor": {
+                "ocr_available": ocr::AVAILABLE,
+            },
+        })
+    }
+
+    // Disabled tools aren't listed, but a client calling one anyway learns why it failed
+    fn check_tool_enabled(&self, name: &str) -> Result<(), McpError> {
+        if self.disabled_tools.contains(name) {
+            return Err(McpError::invalid_request(
+                format!("The tool '{name}' is disabled on this server"),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    // Contents of a file:/// resource, with the path checks and limits of text_editor view
+    fn read_file_resource(&self, uri: &str) -> Result<String, McpError> {
+        let path = self.file_resource_path(uri)?;
+        self.text_editor.read_text(&path)
+    }
+
+    // The resolved path of a file:/// resource
+    fn file_resource_path(&self, uri: &str) -> Result<PathBuf, McpError> {
+        // Percent-decode the URI into a platform path
+        let path = url::Url::parse(uri)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| McpError::invalid_params(format!("Invalid file URI '{uri}'"), None))?;
+        self.resolve_path(&path.to_string_lossy())
+    }
+
+    /// Each tool's name and wrapped description, sorted by name, for reading in a terminal
+    pub fn get_tools_as_text() -> String {
+        let mut tools: Vec<rmcp::model::Tool> = Self::tool_router().list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+            .iter()
+            .map(|tool| {
+                let description = tool.description.as_deref().unwrap_or_default();
+                format!(
+                    "{}\n{}",
+                    tool.name,
+                    wrap_text(description, TOOL_TEXT_WIDTH, "    ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
     fn _create_resource_text(&self, uri: &str, name: &str) -> Resource {
         RawResource::new(uri, name.to_string()).no_annotation()
     }
@@ -215,41 +1271,229 @@ impl Developer {
 
         let suggestion = cwd.join(path);
 
-        match is_absolute_path(&expanded) {
-            true => Ok(path.to_path_buf()),
-            false => Err(McpError::invalid_params(
+        let resolved = if let Some(root) = self.workspace_root.read().unwrap().as_ref() {
+            match is_absolute_path(&expanded) {
+                true => path.to_path_buf(),
+                false => root.join(path),
+            }
+        } else {
+            match is_absolute_path(&expanded) {
+                true => path.to_path_buf(),
+                false => {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "The path {path_str} is not an absolute path, did you possibly mean {}?",
+                            suggestion.to_string_lossy(),
+                        ),
+                        None,
+                    ));
+                }
+            }
+        };
+
+        match &self.sandbox_root {
+            Some(sandbox_root) => confine_to_sandbox(&resolved, sandbox_root),
+            None => Ok(resolved),
+        }
+    }
+
+    // Working Directory Tools
+    #[tool(
+        description = "Report the server's current working directory",
+        annotations(read_only_hint = true)
+    )]
+    async fn get_cwd(&self) -> Result<CallToolResult, McpError> {
+        let cwd = std::env::current_dir().map_err(|e| {
+            McpError::internal_error(format!("Failed to get current directory: {e}"), None)
+        })?;
+        let content = format!("Current working directory: {}", cwd.display());
+
+        Ok(CallToolResult::success(vec![Content::text(content)]))
+    }
+
+    #[tool(
+        description = "Change the server's current working directory.\nThe target must be an absolute path to an existing directory. Gitignore patterns are reloaded from the new directory. Returns the old and new working directory.",
+        annotations(
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn set_cwd(
+        &self,
+        Parameters(SetCwdParams { path }): Parameters<SetCwdParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Validate and resolve the path
+        let resolved_path = self.resolve_path(&path)?;
+
+        if !resolved_path.is_dir() {
+            return Err(McpError::invalid_params(
                 format!(
-                    "The path {path_str} is not an absolute path, did you possibly mean {}?",
-                    suggestion.to_string_lossy(),
+                    "The path '{display}' does not exist or is not a directory.",
+                    display = resolved_path.display()
                 ),
                 None,
-            )),
+            ));
         }
+
+        let old_cwd = std::env::current_dir().map_err(|e| {
+            McpError::internal_error(format!("Failed to get current directory: {e}"), None)
+        })?;
+        std::env::set_current_dir(&resolved_path).map_err(|e| {
+            McpError::internal_error(format!("Failed to change directory: {e}"), None)
+        })?;
+        let new_cwd = std::env::current_dir().map_err(|e| {
+            McpError::internal_error(format!("Failed to get current directory: {e}"), None)
+        })?;
+
+        // Access control and relative paths follow the project we just moved into
+        self.ignore_patterns
+            .set(load_ignore_patterns(new_cwd.clone(), self.ignore_file.clone()).await?);
+        let mut workspace_root = self.workspace_root.write().unwrap();
+        if workspace_root.is_some() {
+            *workspace_root = Some(new_cwd.clone());
+        }
+        drop(workspace_root);
+
+        tracing::info!(old_cwd = %old_cwd.display(), new_cwd = %new_cwd.display(), "Changed working directory");
+
+        let content = format!(
+            "Changed working directory from {old} to {new}",
+            old = old_cwd.display(),
+            new = new_cwd.display()
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(content)]))
+    }
+
+    #[tool(
+        description = "Re-read the ignore files that restrict file access, e.g. after editing .gitignore.\nPatterns come from .gitignore, .ignore and .developerignore files in the workspace and its subdirectories, each applying below its own directory, plus the configured ignore file.",
+        annotations(idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn reload_ignore_patterns(&self) -> Result<CallToolResult, McpError> {
+        let root = match self.workspace_root.read().unwrap().clone() {
+            Some(root) => root,
+            None => std::env::current_dir().map_err(|e| {
+                McpError::internal_error(format!("Failed to get current directory: {e}"), None)
+            })?,
+        };
+        let ignore_patterns = load_ignore_patterns(root.clone(), self.ignore_file.clone()).await?;
+        let rules = ignore_patterns.num_ignores() + ignore_patterns.num_whitelists();
+        self.ignore_patterns.set(ignore_patterns);
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Reloaded {rules} ignore rules for {display}",
+            display = root.display()
+        ))]))
+    }
+
+    #[tool(
+        description = "Report what this server supports, as JSON.\nReturns the server version, platform, enabled and disabled tools, text editor, shell and image limits,\nthe workspace and sandbox roots if set, and whether a display is available for screen capture.\nUse it to adapt to the server before relying on optional features.",
+        annotations(read_only_hint = true)
+    )]
+    async fn server_capabilities(&self) -> Result<CallToolResult, McpError> {
+        let capabilities = self.capabilities();
+        let summary = format!(
+            "{name} {version} on {os}: {tools} tools enabled, screen capture {display}",
+            name = capabilities["name"].as_str().unwrap_or_default(),
+            version = capabilities["version"].as_str().unwrap_or_default(),
+            os = std::env::consts::OS,
+            tools = capabilities["tools"].as_array().map_or(0, Vec::len),
+            display = if capabilities["screen_capture"]["display_available"] == true {
+                "available"
+            } else {
+                "unavailable"
+            }
+        );
+
+        Ok(CallToolResult::success(vec![
+            Content::json(capabilities)?.with_audience(vec![Role::Assistant]),
+            Content::text(summary)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    #[tool(
+        description = "Report how long each tool has taken since the server started, as JSON.\nFor every tool called so far: the number of calls, how many failed, and the total, average and maximum duration in milliseconds.\nUse it to find which operations dominate latency.",
+        annotations(read_only_hint = true)
+    )]
+    async fn metrics(&self) -> Result<CallToolResult, McpError> {
+        let summary = self.metrics.summary();
+        let calls: u64 = summary.values().map(|tool| tool.calls).sum();
+        let slowest = summary
+            .iter()
+            .max_by(|a, b| a.1.total_ms.total_cmp(&b.1.total_ms))
+            .map(|(name, tool)| format!(", most time in {name} ({:.1} ms)", tool.total_ms))
+            .unwrap_or_default();
+
+        Ok(CallToolResult::success(vec![
+            Content::json(json!({ "tools": summary }))?.with_audience(vec![Role::Assistant]),
+            Content::text(format!(
+                "{calls} calls to {tools} tools{slowest}",
+                tools = summary.len()
+            ))
+            .with_audience(vec![Role::User])
+            .with_priority(0.0),
+        ]))
     }
 
     // Text Editor Tool
-    #[tool(description = "Text Editor Tool: File Content Manipulation
+    #[tool(
+        description = "Text Editor Tool: File Content Manipulation
 
 Provides commands to perform text editing operations on files, such as viewing, creating, overwriting, and modifying content, along with an undo capability for recent changes.
 
 Commands:
-- view: View the content of a file
+- view: View the content of a file, or only its first or last lines with head or tail
+- view_many: View several files in one call, skipping with a reason any that can't be viewed
+- stat: Report a file's size, line count and language without returning its content
 - write: Create or overwrite a file with the given content  
 - str_replace: Replace a specific string in a file with a new string
-- undo_edit: Undo the last edit made by write or str_replace to a file
+- regex_replace: Replace matches of a regex in a file, with $1-style capture groups in new_str
+- insert: Insert text after a given line of a file
+- search: Find lines matching a regex in a file or directory tree, respecting ignore patterns
+- undo_edit: Undo the last edit made by write, str_replace, regex_replace or insert to a file
+- undo_all: Revert a file to its oldest saved version in one step and clear its edit history
+- redo: Re-apply the last edit reverted by undo_edit
+- restore_version: List the saved versions of a file, or restore one to go back several edits at once
+- delete: Delete a file (undoable with undo_edit), or a directory when recursive is set
+- move: Move a file or directory to the destination given in new_str
+- copy: Copy a file to the destination given in new_str
+- append_jsonl: Append a JSON record from file_text to a JSONL file as one newline-terminated line
+- mkdir: Create a directory and any missing parents
+- list_dir: List a directory's entries with their type and size, skipping ignored entries
 
 Parameters:
-- command (required): One of view, write, str_replace, undo_edit
+- command (required): One of view, view_many, write, str_replace, regex_replace, insert, search, undo_edit, redo, restore_version, delete, move, copy, append_jsonl, stat, mkdir, list_dir, undo_all
 - path (required): Absolute path to the file to operate on
-- file_text (for write): The entire new content for the file
+- paths (optional, for view_many): Absolute paths of further files to view after path
+- head, tail (optional, for view): Return only the first or last N lines, streaming the file so logs over the size limit can be viewed
+- offset (optional, for view): Continue viewing a file over the size limit from the offset the previous chunk returned
+- file_text (for write, insert, append_jsonl): The entire new content for the file, the text to insert, or the JSON record to append
 - old_str (for str_replace): The exact string to be replaced (must be unique)
-- new_str (for str_replace): The string that will replace old_str
+- new_str (for str_replace, regex_replace, move, copy): The string that will replace old_str or the pattern's matches, or the absolute destination path
+- whitespace_insensitive (optional, for str_replace): Match old_str ignoring differences in runs of whitespace
+- occurrence (optional, for str_replace): Replace the Nth (1-based) match of old_str when it appears more than once
+- whole_word (optional, for str_replace): Ignore matches of old_str inside longer words, erroring if that leaves none
+- insert_line (for insert): Line after which file_text is inserted, 0 to insert at the start
+- pattern (for search, regex_replace): Regex matched against each line for search, results are capped at 200 matches; or against the whole file for regex_replace
+- all (optional, for regex_replace): Replace every match instead of requiring exactly one
+- version (optional, for restore_version): Version to restore, 1 being the most recent; omit to list versions
+- append (optional, for write): Add file_text to the end of the file instead of replacing its content
+- overwrite (optional, for write): Replace an existing file that wasn't viewed first, when the server requires views
+- recursive (optional, for delete): Allow deleting a directory and its contents
+- show_diff (optional, for str_replace): Show the user a unified diff of the change
+- context_lines (optional, for str_replace): Lines of context around the change in the returned snippet (default 4)
+- encoding (optional): The file's encoding such as utf-16le or latin1 (read as windows-1252), UTF-16 with a byte order mark is detected; edits keep the file's encoding
 
 Important Notes:
-- Files are limited to 400KB in size and 400,000 characters
-- write command completely replaces file content
-- str_replace requires exact and unique match of old_str
-- Undo history is maintained for recent changes per file")]
+- Files are limited to 400KB in size and 400,000 characters by default, use stat to check a file's size and line count first. Larger files are viewed a chunk at a time with offset
+- write command completely replaces file content, unless append is set
+- str_replace requires exact and unique match of old_str, unless occurrence is given
+- Undo history is maintained for recent changes per file",
+        annotations(destructive_hint = true, open_world_hint = false)
+    )]
     async fn text_editor(
         &self,
         Parameters(TextEditorParams {
@@ -258,19 +1502,76 @@ Important Notes:
             file_text,
             old_str,
             new_str,
+            whitespace_insensitive,
+            occurrence,
+            whole_word,
+            insert_line,
+            pattern,
+            version,
+            append,
+            overwrite,
+            recursive,
+            show_diff,
+            context_lines,
+            encoding,
+            paths,
+            all,
+            head,
+            tail,
+            offset,
         }): Parameters<TextEditorParams>,
     ) -> Result<CallToolResult, McpError> {
         // Validate and resolve the path
         let resolved_path = self.resolve_path(&path)?;
+        if let Some(encoding) = &encoding {
+            self.text_editor.set_encoding(&resolved_path, encoding)?;
+        }
         let path_str = resolved_path.to_string_lossy().to_string();
 
         match command.as_str() {
-            "view" => self.text_editor.view(path_str).await,
+            "view" => match (head, tail) {
+                _ if offset.is_some() && (head.is_some() || tail.is_some()) => Err(
+                    McpError::invalid_params("offset can't be combined with head or tail", None),
+                ),
+                (None, None) => match offset {
+                    Some(offset) => self.text_editor.view_chunk(path_str, offset).await,
+                    None => self.text_editor.view(path_str).await,
+                },
+                (Some(count), None) => {
+                    self.text_editor
+                        .view_lines(path_str, LineWindow::Head(count))
+                        .await
+                }
+                (None, Some(count)) => {
+                    self.text_editor
+                        .view_lines(path_str, LineWindow::Tail(count))
+                        .await
+                }
+                (Some(_), Some(_)) => Err(McpError::invalid_params(
+                    "Give either head or tail, not both",
+                    None,
+                )),
+            },
+            "stat" => self.text_editor.stat(path_str).await,
+            "view_many" => {
+                // A bad extra path is skipped with the reason, like any file that can't be read
+                let mut resolved_paths = vec![Ok(resolved_path)];
+                for path in paths.unwrap_or_default() {
+                    resolved_paths.push(self.resolve_path(&path).map_err(|e| (path, e)));
+                }
+                self.text_editor.view_many(resolved_paths).await
+            }
             "write" => {
                 let file_text = file_text.ok_or_else(|| {
                     McpError::invalid_params("file_text is required for write command", None)
                 })?;
-                self.text_editor.write(path_str, file_text).await
+                let options = WriteOptions {
+                    append: append.unwrap_or(false),
+                    overwrite: overwrite.unwrap_or(false),
+                };
+                self.text_editor
+                    .write_with_options(path_str, file_text, options)
+                    .await
             }
             "str_replace" => {
                 let old_str = old_str.ok_or_else(|| {
@@ -279,65 +1580,426 @@ Important Notes:
                 let new_str = new_str.ok_or_else(|| {
                     McpError::invalid_params("new_str is required for str_replace command", None)
                 })?;
+                let options = StrReplaceOptions {
+                    whitespace_insensitive: whitespace_insensitive.unwrap_or(false),
+                    occurrence,
+                    show_diff: show_diff.unwrap_or(false),
+                    context_lines,
+                    whole_word: whole_word.unwrap_or(false),
+                };
+                self.text_editor
+                    .str_replace_with_options(path_str, old_str, new_str, options)
+                    .await
+            }
+            "regex_replace" => {
+                let pattern = pattern.ok_or_else(|| {
+                    McpError::invalid_params("pattern is required for regex_replace command", None)
+                })?;
+                let new_str = new_str.ok_or_else(|| {
+                    McpError::invalid_params("new_str is required for regex_replace command", None)
+                })?;
+                self.text_editor
+                    .regex_replace(path_str, pattern, new_str, all.unwrap_or(false))
+                    .await
+            }
+            "insert" => {
+                let insert_line = insert_line.ok_or_else(|| {
+                    McpError::invalid_params("insert_line is required for insert command", None)
+                })?;
+                let file_text = file_text.ok_or_else(|| {
+                    McpError::invalid_params("file_text is required for insert command", None)
+                })?;
                 self.text_editor
-                    .str_replace(path_str, old_str, new_str)
+                    .insert(path_str, insert_line, file_text)
                     .await
             }
+            "search" => {
+                let pattern = pattern.ok_or_else(|| {
+                    McpError::invalid_params("pattern is required for search command", None)
+                })?;
+                self.text_editor.search(path_str, pattern).await
+            }
             "undo_edit" => self.text_editor.undo_edit(path_str).await,
+            "undo_all" => self.text_editor.undo_all(path_str).await,
+            "redo" => self.text_editor.redo(path_str).await,
+            "restore_version" => self.text_editor.restore_version(path_str, version).await,
+            "append_jsonl" => {
+                let record = file_text.ok_or_else(|| {
+                    McpError::invalid_params("file_text is required for append_jsonl command", None)
+                })?;
+                self.text_editor.append_jsonl(path_str, record).await
+            }
+            "delete" => {
+                self.text_editor
+                    .delete(path_str, recursive.unwrap_or(false))
+                    .await
+            }
+            "move" | "copy" => {
+                let destination = new_str.ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!("new_str is required as the destination for {command} command"),
+                        None,
+                    )
+                })?;
+                // The destination gets the same validation as the source
+                let destination = self
+                    .resolve_path(&destination)?
+                    .to_string_lossy()
+                    .to_string();
+                if command == "move" {
+                    self.text_editor.move_path(path_str, destination).await
+                } else {
+                    self.text_editor.copy_file(path_str, destination).await
+                }
+            }
+            "mkdir" => self.text_editor.mkdir(path_str).await,
+            "list_dir" => self.text_editor.list_dir(path_str).await,
             _ => Err(McpError::invalid_params(
-                "Unknown command. Allowed commands are: view, write, str_replace, undo_edit",
+                "Unknown command. Allowed commands are: view, view_many, write, str_replace, regex_replace, insert, search, undo_edit, redo, restore_version, delete, move, copy, append_jsonl, stat, mkdir, list_dir, undo_all",
                 None,
             )),
         }
     }
 
+    #[tool(
+        description = "Check whether a file changed since it was last viewed with text_editor in this session.\nUse it before editing a file read a while ago, to notice external modifications and re-read instead of clobbering them.\nEdits made through text_editor don't count as changes.",
+        annotations(read_only_hint = true)
+    )]
+    async fn check_unchanged(
+        &self,
+        Parameters(CheckUnchangedParams { path }): Parameters<CheckUnchangedParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Validate and resolve the path
+        let resolved_path = self.resolve_path(&path)?;
+        let path_str = resolved_path.to_string_lossy().to_string();
+
+        self.text_editor.check_unchanged(path_str).await
+    }
+
     // Shell Tool
-    #[tool(description = "Execute shell commands on the system")]
+    #[tool(
+        description = "Execute shell commands on the system",
+        annotations(destructive_hint = true, open_world_hint = true)
+    )]
     async fn shell(
         &self,
-        Parameters(ShellParams { command }): Parameters<ShellParams>,
+        Parameters(ShellParams {
+            command,
+            trim_blank_lines,
+            cwd,
+            separate_streams,
+            session_id,
+            env,
+            dry_run,
+            stdin,
+            output_format,
+        }): Parameters<ShellParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        // Validate and resolve the working directory, commands run in the workspace by default
+        let cwd = match cwd {
+            Some(cwd) => Some(self.resolve_path(&cwd)?),
+            None => self.default_command_dir(),
+        };
+        let options = ExecuteOptions {
+            trim_blank_lines,
+            cwd,
+            progress: Self::forward_progress(&context),
+            separate_streams: separate_streams.unwrap_or(false),
+            session_id,
+            env: env.unwrap_or_default(),
+            cancel: Some(context.ct.clone()),
+            dry_run: dry_run.unwrap_or(false),
+            stdin,
+            output_format: output_format
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e: String| McpError::invalid_params(e, None))?
+                .unwrap_or_default(),
+        };
+        self.shell.execute_with_options(command, options).await
+    }
+
+    /// Where commands run without an explicit directory, the process cwd when None
+    fn default_command_dir(&self) -> Option<PathBuf> {
+        self.workspace_root.read().unwrap().clone().or_else(|| {
+            // A sandboxed server launched elsewhere runs commands inside the sandbox
+            let sandbox_root = self.sandbox_root.as_ref()?;
+            let cwd = std::env::current_dir().ok()?.canonicalize().ok()?;
+            (!cwd.starts_with(sandbox_root)).then(|| sandbox_root.clone())
+        })
+    }
+
+    // The default command directory, falling back to the process's own
+    fn working_dir(&self) -> Result<PathBuf, McpError> {
+        match self.default_command_dir() {
+            Some(dir) => Ok(dir),
+            None => std::env::current_dir().map_err(|e| {
+                McpError::internal_error(format!("Failed to get current directory: {e}"), None)
+            }),
+        }
+    }
+
+    #[tool(
+        description = "Report the git status of the workspace as JSON.\nReturns the current branch and commit, the upstream with ahead/behind counts, and the staged, unstaged, untracked and conflicted files.\nRuns `git status` through the shell, so the shell's command policy applies.",
+        annotations(read_only_hint = true)
+    )]
+    async fn git_status(
+        &self,
+        Parameters(GitStatusParams { path }): Parameters<GitStatusParams>,
     ) -> Result<CallToolResult, McpError> {
-        self.shell.execute(command).await
+        let dir = match path {
+            Some(path) => self.resolve_path(&path)?,
+            None => self.working_dir()?,
+        };
+        git::status(&self.shell, dir).await
+    }
+
+    /// Relay lines sent on the returned channel to the client as progress notifications,
+    /// when the request asked for progress by including a token
+    fn forward_progress(
+        context: &RequestContext<RoleServer>,
+    ) -> Option<tokio::sync::mpsc::UnboundedSender<String>> {
+        let progress_token = context.meta.get_progress_token()?;
+        let peer = context.peer.clone();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            let mut progress = 0;
+            // Ends once the sender is dropped with the finished command
+            while let Some(line) = receiver.recv().await {
+                progress += 1;
+                let notification = ProgressNotificationParam {
+                    progress_token: progress_token.clone(),
+                    progress,
+                    total: None,
+                    message: Some(line),
+                };
+                if peer.notify_progress(notification).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Some(sender)
     }
 
     // Screen Capture Tools
     #[tool(
-        description = "List all available window titles that can be used with screen_capture.\nReturns a list of window titles that can be used with the window_title parameter\nof the screen_capture tool."
+        description = "List the windows that can be captured with screen_capture.\nReturns JSON with each window's id, title, owning application name, process id\nand whether it has focus. Titles can be used with the window_title parameter\nof the screen_capture tool.",
+        annotations(read_only_hint = true)
     )]
     async fn list_windows(&self) -> Result<CallToolResult, McpError> {
         self.screen_capture.list_windows().await
     }
 
     #[tool(
-        description = "Capture a screenshot of a specified display or window.\nYou can capture either:\n1. A full display (monitor) using the display parameter\n2. A specific window by its title using the window_title parameter\n\nOnly one of display or window_title should be specified."
+        description = "List the displays (monitors) that can be captured.\nReturns a table of each display's index for the display parameter of screen_capture,\nits resolution, its position in the virtual desktop and whether it is the primary display.",
+        annotations(read_only_hint = true)
+    )]
+    async fn list_displays(&self) -> Result<CallToolResult, McpError> {
+        self.screen_capture.list_displays().await
+    }
+
+    #[tool(
+        description = "Capture a screenshot of a specified display or window.\nYou can capture either:\n1. A full display (monitor) using the display parameter\n2. A specific window by its id (window_id) or title (window_title), both from list_windows\n\nOnly one of display or window_title should be specified, window_id takes precedence over window_title.\nGive x, y, width and height to capture only that region of the display or window; it is clamped to the captured image.\nSet all_displays to capture every monitor at once.",
+        annotations(read_only_hint = true)
     )]
     async fn screen_capture(
         &self,
         Parameters(ScreenCaptureParams {
             display,
             window_title,
+            window_id,
+            x,
+            y,
+            width,
+            height,
+            format,
+            quality,
+            all_displays,
+            match_mode,
+            include_cursor,
         }): Parameters<ScreenCaptureParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let region = match (x, y, width, height) {
+            (Some(x), Some(y), Some(width), Some(height)) => Some(CaptureRegion {
+                x,
+                y,
+                width,
+                height,
+            }),
+            (None, None, None, None) => None,
+            _ => {
+                return Err(McpError::invalid_params(
+                    "x, y, width and height must be given together to capture a region",
+                    None,
+                ));
+            }
+        };
+        let options = CaptureOptions {
+            region,
+            format: CaptureFormat::parse(format.as_deref(), quality)?,
+            all_displays: all_displays.unwrap_or(false),
+            window_match: WindowMatch::parse(match_mode.as_deref())?,
+            window_id,
+            include_cursor: include_cursor.unwrap_or(false),
+            cancel: Some(context.ct),
+        };
+        self.screen_capture
+            .capture_with_options(display, window_title, options)
+            .await
+    }
+
+    #[tool(
+        description = "Capture a display, or a region of it, and save it as a named reference screenshot.\nUse compare_reference_screenshot later to check whether that area still looks the same,\ne.g. for visual regression checks after a UI change.",
+        annotations(destructive_hint = false, open_world_hint = false)
+    )]
+    async fn save_reference_screenshot(
+        &self,
+        Parameters(SaveReferenceScreenshotParams {
+            name,
+            display,
+            region,
+        }): Parameters<SaveReferenceScreenshotParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.screen_capture
+            .save_reference(name, display, region, Some(&context.ct))
+            .await
+    }
+
+    #[tool(
+        description = "Capture the same display area as a saved reference screenshot and compare them.\nReturns the percentage of pixels that differ and a diff image where changed pixels are red\nover a dimmed grayscale of the current screen.",
+        annotations(read_only_hint = true)
+    )]
+    async fn compare_reference_screenshot(
+        &self,
+        Parameters(CompareReferenceScreenshotParams { name }): Parameters<
+            CompareReferenceScreenshotParams,
+        >,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        self.screen_capture.capture(display, window_title).await
+        self.screen_capture
+            .compare_reference(name, Some(&context.ct))
+            .await
     }
 
     // Image Processor Tool
     #[tool(
-        description = "Process an image file from disk. The image will be:\n1. Resized if larger than max width while maintaining aspect ratio\n2. Optionally resized further by 1/2 or 1/4 to reduce file size\n3. Preserved in original format (JPEG stays JPEG, PNG stays PNG) for optimal compression\n4. Returned as base64 encoded data\n\nThis allows processing image files for use in the conversation."
+        description = "Process an image file from disk. The image will be:\n1. Optionally turned upright from its EXIF orientation, then cropped and rotated by a multiple of 90 degrees\n2. Resized if larger than max width while maintaining aspect ratio\n3. Optionally resized further by 1/2 or 1/4 to reduce file size, or to an exact width and/or height instead\n4. Preserved in original format (JPEG stays JPEG, PNG stays PNG) for optimal compression, unless output_format asks for png, jpeg or webp\n5. Returned as base64 encoded data, or written to output_path in the format of its extension\n\nSeveral files can be processed at once with paths, returning one image per file after a summary.\n\nThis allows processing image files for use in the conversation.",
+        annotations(destructive_hint = true, open_world_hint = false)
     )]
     async fn image_processor(
         &self,
-        Parameters(ImageProcessorParams { path, resize }): Parameters<ImageProcessorParams>,
+        Parameters(ImageProcessorParams {
+            path,
+            paths,
+            resize,
+            width,
+            height,
+            output_path,
+            overwrite,
+            crop,
+            rotate,
+            output_format,
+            quality,
+            read_metadata,
+            auto_orient,
+            grayscale,
+            contrast,
+            ocr,
+        }): Parameters<ImageProcessorParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Validate and resolve the paths
+        let output_path = output_path
+            .map(|output_path| self.resolve_path(&output_path))
+            .transpose()?;
+
+        let options = ProcessOptions {
+            resize,
+            width,
+            height,
+            output_path,
+            overwrite: overwrite.unwrap_or(false),
+            crop,
+            rotate,
+            output_format,
+            quality,
+            read_metadata: read_metadata.unwrap_or(false),
+            auto_orient: auto_orient.unwrap_or(false),
+            grayscale: grayscale.unwrap_or(false),
+            contrast,
+            ocr: ocr.unwrap_or(false),
+        };
+        match (path, paths) {
+            (Some(path), None) => {
+                let path_str = self.resolve_path(&path)?.to_string_lossy().to_string();
+                self.image_processor
+                    .process_with_options(path_str, options)
+                    .await
+            }
+            (None, Some(paths)) => {
+                let paths = paths
+                    .iter()
+                    .map(|path| Ok(self.resolve_path(path)?.to_string_lossy().to_string()))
+                    .collect::<Result<Vec<_>, McpError>>()?;
+                self.image_processor.process_batch(paths, options).await
+            }
+            _ => Err(McpError::invalid_params("Give either path or paths", None)),
+        }
+    }
+
+    // Import Resolver Tool
+    #[tool(
+        description = "Resolve the local files referenced by a source file's imports.\nParses import/include statements (Rust `mod`, Python `import`/`from`, JavaScript/TypeScript relative imports and `require`, C/C++ quoted `#include`) and returns the absolute paths of referenced files that exist in the workspace.\nExternal and standard library references are skipped, as are files restricted by ignore patterns.",
+        annotations(read_only_hint = true)
+    )]
+    async fn resolve_imports(
+        &self,
+        Parameters(ResolveImportsParams { path }): Parameters<ResolveImportsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Validate and resolve the path
+        let resolved_path = self.resolve_path(&path)?;
+        let path_str = resolved_path.to_string_lossy().to_string();
+
+        self.import_resolver.resolve(path_str).await
+    }
+
+    // File Stats Tool
+    #[tool(
+        description = "Count lines, words, characters and bytes of a file, like `wc`, without shelling out.\nLines are newline characters and words are whitespace-separated runs.\nPass a directory with a glob (e.g. `**/*.rs`) to list counts per matching file along with a total.\nBinary files and files restricted by ignore patterns are skipped.",
+        annotations(read_only_hint = true)
+    )]
+    async fn count_stats(
+        &self,
+        Parameters(CountStatsParams { path, glob }): Parameters<CountStatsParams>,
     ) -> Result<CallToolResult, McpError> {
         // Validate and resolve the path
         let resolved_path = self.resolve_path(&path)?;
         let path_str = resolved_path.to_string_lossy().to_string();
 
-        self.image_processor.process(path_str, resize).await
+        self.file_stats.count(path_str, glob).await
+    }
+
+    #[tool(
+        description = "Find files by glob or name, without platform-specific shell commands.\nWalks the directory tree and returns matching absolute paths sorted by path, capped at 500.\nFiles restricted by ignore patterns, gitignored files and hidden files are skipped.",
+        annotations(read_only_hint = true)
+    )]
+    async fn find_files(
+        &self,
+        Parameters(FindFilesParams { pattern, root }): Parameters<FindFilesParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let root = match root {
+            Some(root) => self.resolve_path(&root)?,
+            None => self.working_dir()?,
+        };
+        self.file_finder.find(root, pattern).await
     }
 
     // Workflow Tools
-    #[tool(description = "Workflow Tool: Guiding Complex Problem-Solving
+    #[tool(
+        description = "Workflow Tool: Guiding Complex Problem-Solving
 
 Manages multi-step problem-solving processes with support for sequential progression, branching paths, and step revisions. This tool helps structure reasoning, explore alternatives, and adapt approaches as understanding evolves.
 
@@ -364,8 +2026,11 @@ Parameters:
 - is_step_revision (optional): True if this step revises a previous step
 - revises_step (optional): Step number being revised if is_step_revision is true
 - branch_from_step (optional): Step number from which new branch originates
-- branch_id (optional): Unique identifier for the branch
-- needs_more_steps (optional): True if more steps needed for overall problem")]
+- branch_id (optional): Unique identifier for the branch; an existing one alone resumes that branch
+- needs_more_steps (optional): True if more steps needed for overall problem
+- verbosity (optional): compact, normal (default) or verbose status output",
+        annotations(read_only_hint = false, destructive_hint = false)
+    )]
     async fn workflow(
         &self,
         Parameters(WorkflowParams {
@@ -378,9 +2043,22 @@ Parameters:
             branch_from_step,
             branch_id,
             needs_more_steps,
+            verbosity,
         }): Parameters<WorkflowParams>,
     ) -> Result<CallToolResult, McpError> {
-        use workflow::WorkflowStep;
+        use workflow::{StatusVerbosity, WorkflowStep};
+
+        let verbosity = match verbosity.as_deref() {
+            Some(value) => StatusVerbosity::parse(value).ok_or_else(|| {
+                McpError::invalid_params(
+                    format!(
+                        "Invalid verbosity '{value}'. Allowed values: compact, normal, verbose"
+                    ),
+                    None,
+                )
+            })?,
+            None => StatusVerbosity::default(),
+        };
 
         let step = WorkflowStep {
             step_description,
@@ -394,18 +2072,106 @@ Parameters:
             needs_more_steps,
         };
 
-        self.workflow.execute_step(step).await
+        self.workflow
+            .execute_step_with_verbosity(step, verbosity)
+            .await
+    }
+
+    #[tool(
+        description = "Return the complete workflow history as JSON without changing it.\nIncludes every step on the main history and on each branch with all of its fields, so revisions and branch points are visible, plus the current branch.\nUse it to re-orient after a long session.",
+        annotations(read_only_hint = true)
+    )]
+    async fn workflow_history(&self) -> Result<CallToolResult, McpError> {
+        self.workflow.history().await
+    }
+
+    #[tool(
+        description = "Clear the workflow to start a new task.\nRemoves every step, branch and the current branch, including any saved state, and reports how many steps were cleared.",
+        annotations(
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn workflow_reset(&self) -> Result<CallToolResult, McpError> {
+        self.workflow.reset().await
     }
 }
 
-#[tool_handler]
 impl ServerHandler for Developer {
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_tool_enabled(&request.name)?;
+        let name = request.name.clone();
+        let command = request
+            .arguments
+            .as_ref()
+            .and_then(|arguments| arguments.get("command"))
+            .cloned();
+        let peer = context.peer.clone();
+        let tcc = ToolCallContext::new(self, request, context);
+        let started = std::time::Instant::now();
+        let result = self.tool_router.call(tcc).await;
+        let failed = result
+            .as_ref()
+            .map_or(true, |result| result.is_error == Some(true));
+        // Names the router doesn't know would grow the metrics without bound
+        if self.tool_router.has_route(&name) {
+            self.metrics.record(&name, started.elapsed(), failed);
+        }
+
+        // Failures are worth surfacing to whoever is debugging the client
+        match &result {
+            Err(error) => {
+                let data = json!({ "tool": name, "error": error.message });
+                self.notify_log(&peer, LoggingLevel::Error, data).await;
+            }
+            // Only shell results carry a command's exit code
+            Ok(result) if name == "shell" => {
+                if let Some(exit_code) = logging::failed_exit_code(result) {
+                    let data = json!({ "tool": name, "command": command, "exit_code": exit_code });
+                    self.notify_log(&peer, LoggingLevel::Warning, data).await;
+                }
+            }
+            Ok(_) => {}
+        }
+        result
+    }
+
+    async fn set_level(
+        &self,
+        SetLevelRequestParam { level }: SetLevelRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        *self.log_level.write().unwrap() = level;
+        if let Some(log_filter) = &self.log_filter {
+            log_filter.reload(logging::filter_for(level)).map_err(|e| {
+                McpError::internal_error(format!("Failed to change the log level: {e}"), None)
+            })?;
+        }
+        tracing::info!("Log level set to {level:?}");
+        Ok(())
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2024_11_05,
             capabilities: ServerCapabilities::builder()
+                .enable_logging()
                 .enable_prompts()
                 .enable_resources()
+                .enable_resources_subscribe()
                 .enable_tools()
                 .build(),
             server_info: Implementation::from_build_env(),
@@ -434,10 +2200,33 @@ impl ServerHandler for Developer {
     ) -> Result<ReadResourceResult, McpError> {
         match uri.as_str() {
             "file://workspace" => {
-                let workspace_info =
-                    "Developer workspace with text editing, shell, and screen capture tools";
+                let root = match self.workspace_root.read().unwrap().clone() {
+                    Some(root) => root,
+                    None => std::env::current_dir().map_err(|e| {
+                        McpError::internal_error(
+                            format!("Failed to get current directory: {e}"),
+                            None,
+                        )
+                    })?,
+                };
+                // The patterns in effect, rather than ones read again for every listing
+                let ignore_patterns = self.ignore_patterns.clone();
+                let depth = self.workspace_tree_depth;
+                let tree = tokio::task::spawn_blocking(move || {
+                    workspace_tree(&root, ignore_patterns, depth)
+                })
+                .await
+                .map_err(|e| {
+                    McpError::internal_error(format!("Failed to list the workspace: {e}"), None)
+                })?;
                 Ok(ReadResourceResult {
-                    contents: vec![ResourceContents::text(workspace_info, uri)],
+                    contents: vec![ResourceContents::text(tree, uri)],
+                })
+            }
+            uri if uri.starts_with("file:///") => {
+                let content = self.read_file_resource(uri)?;
+                Ok(ReadResourceResult {
+                    contents: vec![ResourceContents::text(content, uri)],
                 })
             }
             "shell://history" => {
@@ -455,6 +2244,27 @@ impl ServerHandler for Developer {
         }
     }
 
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        // Only file:/// resources change on their own, under the same access rules as reading
+        let path = self.file_resource_path(&uri)?;
+        self.text_editor.check_ignore_patterns(&path)?;
+        self.file_watcher
+            .watch(self.connection, uri, path, context.peer)
+    }
+
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.file_watcher.unwatch(self.connection, &uri);
+        Ok(())
+    }
+
     async fn list_prompts(
         &self,
         _request: Option<PaginatedRequestParam>,
@@ -509,7 +2319,18 @@ impl ServerHandler for Developer {
     ) -> Result<ListResourceTemplatesResult, McpError> {
         Ok(ListResourceTemplatesResult {
             next_cursor: None,
-            resource_templates: Vec::new(),
+            resource_templates: vec![
+                RawResourceTemplate {
+                    uri_template: "file:///{path}".to_string(),
+                    name: "file".to_string(),
+                    description: Some(
+                        "Text contents of a file by absolute path, under the same ignore patterns and size limits as text_editor view"
+                            .to_string(),
+                    ),
+                    mime_type: Some("text/plain".to_string()),
+                }
+                .no_annotation(),
+            ],
         })
     }
 
@@ -576,6 +2397,227 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_workspace_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::write(root.join(".gitignore"), "secret.txt\n").unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let developer = Developer::new().with_workspace_root(root.clone());
+        assert_eq!(
+            developer.resolve_path("src/lib.rs").unwrap(),
+            root.join("src/lib.rs")
+        );
+        if !cfg!(windows) {
+            assert_eq!(
+                developer.resolve_path("/test/file.txt").unwrap(),
+                PathBuf::from("/test/file.txt")
+            );
+        }
+
+        // The workspace's .gitignore restricts access
+        let view = developer
+            .text_editor
+            .view(root.join("secret.txt").to_string_lossy().to_string())
+            .await;
+        assert!(view.is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        let sandbox = root.join("sandbox");
+        std::fs::create_dir_all(sandbox.join("src")).unwrap();
+        std::fs::write(root.join("outside.txt"), "secret").unwrap();
+
+        let developer = Developer::new().with_sandbox_root(sandbox.clone());
+
+        // Existing and not-yet-created paths inside the root are fine
+        let inside = developer
+            .resolve_path(&sandbox.join("src").to_string_lossy())
+            .unwrap();
+        assert_eq!(inside, sandbox.join("src"));
+        let new_file = developer
+            .resolve_path(&sandbox.join("src/new/file.rs").to_string_lossy())
+            .unwrap();
+        assert_eq!(new_file, sandbox.join("src/new/file.rs"));
+
+        // `..` traversal can't climb out
+        let escape = sandbox.join("src/../../outside.txt");
+        let err = developer
+            .resolve_path(&escape.to_string_lossy())
+            .unwrap_err();
+        assert!(err.message.contains("outside the sandbox"));
+        let escape = sandbox.join("missing/../../outside.txt");
+        assert!(developer.resolve_path(&escape.to_string_lossy()).is_err());
+        assert!(developer.resolve_path("/etc/passwd").is_err());
+
+        // Neither can a symlink pointing elsewhere
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("outside.txt"), sandbox.join("link")).unwrap();
+            assert!(
+                developer
+                    .resolve_path(&sandbox.join("link").to_string_lossy())
+                    .is_err()
+            );
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_disabled_tools() {
+        let developer = Developer::with_config(Config {
+            enabled_tools: Some(vec!["text_editor".to_string(), "shell".to_string()]),
+            disabled_tools: Some(vec!["shell".to_string()]),
+            ..Default::default()
+        });
+
+        let tools: Vec<_> = developer
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        assert_eq!(tools, vec!["text_editor"]);
+
+        assert!(developer.check_tool_enabled("text_editor").is_ok());
+        let err = developer.check_tool_enabled("shell").unwrap_err();
+        assert!(err.message.contains("disabled"));
+        assert!(developer.check_tool_enabled("screen_capture").is_err());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let developer = Developer::with_config(Config {
+            disabled_tools: Some(vec!["shell".to_string()]),
+            ..Default::default()
+        })
+        .with_sandbox_root(std::env::temp_dir());
+
+        let capabilities = developer.capabilities();
+        let tools = capabilities["tools"].as_array().unwrap();
+        assert!(tools.contains(&json!("server_capabilities")));
+        assert!(!tools.contains(&json!("shell")));
+        let disabled_tools = capabilities["disabled_tools"].as_array().unwrap();
+        assert!(disabled_tools.contains(&json!("shell")));
+        assert_eq!(capabilities["platform"]["os"], std::env::consts::OS);
+        assert!(capabilities["limits"]["max_file_bytes"].as_u64().unwrap() > 0);
+        assert!(capabilities["sandbox_root"].is_string());
+        assert!(capabilities["workspace_root"].is_null());
+        assert!(capabilities["screen_capture"]["display_available"].is_boolean());
+        assert_eq!(
+            capabilities["image_processor"]["ocr_available"],
+            json!(ocr::AVAILABLE)
+        );
+        // Headless servers don't offer the capture tools
+        if !screen_capture::display_server_configured() {
+            assert!(!tools.contains(&json!("screen_capture")));
+            assert!(disabled_tools.contains(&json!("list_windows")));
+        }
+    }
+
+    #[test]
+    fn test_read_file_resource() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::write(root.join(".gitignore"), "secret.txt\n").unwrap();
+        std::fs::write(root.join("my notes.txt"), "hello").unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let developer = Developer::new().with_workspace_root(root.clone());
+        let uri = url::Url::from_file_path(root.join("my notes.txt")).unwrap();
+        assert_eq!(developer.read_file_resource(uri.as_str()).unwrap(), "hello");
+
+        let uri = url::Url::from_file_path(root.join("secret.txt")).unwrap();
+        assert!(developer.read_file_resource(uri.as_str()).is_err());
+        let uri = url::Url::from_file_path(root.join("missing.txt")).unwrap();
+        assert!(developer.read_file_resource(uri.as_str()).is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_workspace_tree() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(root.join("src/deep/deeper")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("src/deep/deeper/hidden.rs"), "").unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+
+        let patterns = IgnorePatterns::new();
+        patterns.set(build_ignore_patterns(root, None));
+        let tree = workspace_tree(root, patterns.clone(), 2);
+        let lines: Vec<_> = tree.lines().collect();
+        assert_eq!(lines[0], format!("{}/", root.display()));
+        assert_eq!(
+            &lines[1..],
+            ["  Cargo.toml", "  src/", "    deep/", "    main.rs"]
+        );
+
+        // Many entries are cut off with a note
+        for i in 0..MAX_WORKSPACE_TREE_ENTRIES {
+            std::fs::write(root.join(format!("file{i}.txt")), "").unwrap();
+        }
+        let tree = workspace_tree(root, patterns, 1);
+        assert_eq!(tree.lines().count(), MAX_WORKSPACE_TREE_ENTRIES + 2);
+        assert!(tree.ends_with(&format!(
+            "[Truncated: showing {MAX_WORKSPACE_TREE_ENTRIES} of {} entries]",
+            MAX_WORKSPACE_TREE_ENTRIES + 2
+        )));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_get_tools_as_text() {
+        let text = Developer::get_tools_as_text();
+        assert!(text.lines().any(|line| line == "shell"));
+        assert!(text.lines().any(|line| line == "text_editor"));
+        assert!(
+            text.lines()
+                .all(|line| line.len() <= TOOL_TEXT_WIDTH || !line.trim().contains(' '))
+        );
+
+        assert_eq!(
+            wrap_text("one two three\n\nfour", 12, "  "),
+            "  one two\n  three\n\n  four"
+        );
+    }
+
+    #[test]
+    fn test_tool_annotations() {
+        let schema: serde_json::Value =
+            serde_json::from_str(&Developer::get_tools_schema_as_json()).unwrap();
+        let annotations = |name: &str| {
+            schema
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|tool| tool["name"] == name)
+                .unwrap()["annotations"]
+                .clone()
+        };
+        assert_eq!(annotations("shell")["destructiveHint"], true);
+        assert_eq!(annotations("shell")["openWorldHint"], true);
+        assert_eq!(annotations("text_editor")["destructiveHint"], true);
+        assert_eq!(annotations("screen_capture")["readOnlyHint"], true);
+        // output_path with overwrite replaces existing files
+        assert_eq!(annotations("image_processor")["destructiveHint"], true);
+        // The workflow changes server state and its state file, without destroying anything
+        assert_eq!(annotations("workflow")["readOnlyHint"], false);
+        assert_eq!(annotations("workflow")["destructiveHint"], false);
+        assert_eq!(annotations("workflow_history")["readOnlyHint"], true);
+    }
+
     #[test]
     fn test_expand_path() {
         if cfg!(windows) {
@@ -615,6 +2657,217 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_line_ending_detection() {
+        assert_eq!(LineEnding::detect("a\nb\r\nc\n"), Some(LineEnding::Lf));
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), Some(LineEnding::Crlf));
+        assert_eq!(LineEnding::detect("no breaks"), None);
+        assert_eq!(LineEnding::Crlf.apply("a\nb\r\n"), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\r\nb\n"), "a\nb\n");
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_set_cwd_reloads_ignore_patterns() {
+        let original_cwd = std::env::current_dir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::write(root.join(".gitignore"), "secret.txt\n").unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let developer = Developer::new();
+        let result = developer
+            .set_cwd(Parameters(SetCwdParams {
+                path: root.to_string_lossy().to_string(),
+            }))
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(std::env::current_dir().unwrap(), root);
+
+        let text = result.unwrap().content[0].as_text().unwrap().text.clone();
+        assert!(text.contains(&original_cwd.to_string_lossy().to_string()));
+
+        // The new directory's .gitignore now restricts access
+        let view = developer
+            .text_editor
+            .view(root.join("secret.txt").to_string_lossy().to_string())
+            .await;
+        assert!(view.is_err());
+
+        std::env::set_current_dir(&original_cwd).unwrap();
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("sub/deep")).unwrap();
+        std::fs::create_dir_all(root.join("ignored")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\nignored/\n").unwrap();
+        std::fs::write(
+            root.join("sub/.gitignore"),
+            "# comment\nsecret.txt\n/local.txt\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("sub/.ignore"), "!keep.log\n").unwrap();
+        std::fs::write(root.join("sub/deep/.developerignore"), "notes.md\n").unwrap();
+        // Ignore files inside ignored directories don't count
+        std::fs::write(root.join("ignored/.gitignore"), "!*.log\n").unwrap();
+        std::fs::create_dir_all(root.join(".git/info")).unwrap();
+        std::fs::write(root.join(".git/info/exclude"), "local.env\n").unwrap();
+        std::fs::create_dir_all(root.join("[draft]")).unwrap();
+        std::fs::write(root.join("[draft]/.gitignore"), "*.tmp\n").unwrap();
+
+        let patterns = build_ignore_patterns(&root, None);
+        let ignored = |path: &str| patterns.matched(root.join(path), false).is_ignore();
+        assert!(ignored("app.log"));
+        assert!(ignored("local.env"));
+        assert!(ignored("[draft]/a.tmp"));
+        assert!(!ignored("d/a.tmp"));
+        assert!(ignored("sub/secret.txt"));
+        assert!(ignored("sub/deep/secret.txt"));
+        assert!(!ignored("secret.txt"));
+        assert!(ignored("sub/local.txt"));
+        assert!(!ignored("sub/deep/local.txt"));
+        assert!(!ignored("sub/keep.log"));
+        assert!(ignored("sub/deep/notes.md"));
+        assert!(!ignored("sub/notes.md"));
+
+        assert_eq!(scope_pattern("  ", "a"), None);
+        assert_eq!(
+            scope_pattern("!/build/", "a/b").as_deref(),
+            Some("!/a/b/build/")
+        );
+        assert_eq!(scope_pattern("*.tmp", "a").as_deref(), Some("/a/**/*.tmp"));
+        // Glob characters in directory names match literally
+        assert_eq!(
+            scope_pattern("*.tmp", "[draft]").as_deref(),
+            Some("/\\[draft\\]/**/*.tmp")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_allow_file_overrides_ignore_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("dist")).unwrap();
+        std::fs::write(root.join(".gitignore"), "dist/*\n*.pem\n").unwrap();
+        std::fs::write(root.join(".developerignore"), "notes.md\n").unwrap();
+        std::fs::write(
+            root.join(".developerallow"),
+            "# built artifacts the agent may read\ndist/*.js\n!dist/vendor.js\n",
+        )
+        .unwrap();
+        for name in ["dist/app.js", "dist/vendor.js", "dist/app.css", "key.pem"] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+
+        let developer = Developer::new().with_workspace_root(root.clone());
+        let path_of = |name: &str| root.join(name).to_string_lossy().to_string();
+
+        // The allowlist wins over .gitignore, its own `!` lines stay restricted
+        let editor = &developer.text_editor;
+        assert!(editor.view(path_of("dist/app.js")).await.is_ok());
+        assert!(editor.view(path_of("dist/vendor.js")).await.is_err());
+        assert!(editor.view(path_of("dist/app.css")).await.is_err());
+        assert!(editor.view(path_of("key.pem")).await.is_err());
+
+        // The shell applies the same patterns to the paths a command names
+        let options = || ExecuteOptions {
+            cwd: Some(root.clone()),
+            ..Default::default()
+        };
+        let shell = &developer.shell;
+        assert!(
+            shell
+                .execute_with_options("cat dist/app.js".to_string(), options())
+                .await
+                .is_ok()
+        );
+        let result = shell
+            .execute_with_options("cat dist/app.css".to_string(), options())
+            .await;
+        assert!(result.unwrap_err().to_string().contains("ignore patterns"));
+
+        // Ignore and allow files can be read but not changed, or created where none exist yet
+        assert!(editor.view(path_of(".developerallow")).await.is_ok());
+        for name in [".developerallow", ".gitignore", "src/.ignore"] {
+            let result = editor.write(path_of(name), "*\n".to_string()).await;
+            assert!(
+                result
+                    .unwrap_err()
+                    .to_string()
+                    .contains("ignore or allow file")
+            );
+        }
+        let result = editor
+            .move_path(path_of("dist/app.js"), path_of("dist/.developerignore"))
+            .await;
+        assert!(result.is_err());
+        assert!(editor.delete(path_of("dist"), true).await.is_err());
+        for command in ["echo '!*' >> .developerallow", "rm dist/.gitignore"] {
+            let result = shell
+                .execute_with_options(command.to_string(), options())
+                .await;
+            assert!(
+                result
+                    .unwrap_err()
+                    .to_string()
+                    .contains("ignore or allow file")
+            );
+        }
+        assert!(!root.join("dist/.gitignore").exists());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_connections_keep_their_own_log_level() {
+        let server = Developer::new();
+        let first = server.for_connection();
+        let second = server.for_connection();
+        assert_ne!(first.connection, second.connection);
+
+        *first.log_level.write().unwrap() = LoggingLevel::Error;
+        assert_eq!(*second.log_level.read().unwrap(), LoggingLevel::Info);
+        assert_eq!(*server.log_level.read().unwrap(), LoggingLevel::Info);
+    }
+
+    #[tokio::test]
+    async fn test_reload_ignore_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+        let developer = Developer::new().with_workspace_root(root.clone());
+        let secret = root.join("secret.txt").to_string_lossy().to_string();
+        assert!(developer.text_editor.view(secret.clone()).await.is_ok());
+
+        // Edits to ignore files apply once reloaded
+        std::fs::write(root.join(".developerignore"), "secret.txt\n").unwrap();
+        let result = developer.reload_ignore_patterns().await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Reloaded 1 ignore rules"));
+        assert!(developer.text_editor.view(secret).await.is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_set_cwd_rejects_missing_directory() {
+        let developer = Developer::new();
+        let result = developer
+            .set_cwd(Parameters(SetCwdParams {
+                path: "/nonexistent/directory/for/test".to_string(),
+            }))
+            .await;
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("not a directory"));
+        }
+    }
+
     // Note: RequestContext tests are complex due to the structure requirements
     // These would need proper setup in integration tests
 }
@@ -0,0 +1,68 @@
+use rmcp::Error as McpError;
+use xcap::image::DynamicImage;
+
+/// Whether this build can recognize text, set by the `ocr` feature
+pub const AVAILABLE: bool = cfg!(feature = "ocr");
+
+// Tesseract language data used for recognition
+#[cfg(feature = "ocr")]
+const LANGUAGE: &str = "eng";
+
+/// Fail before any image work when text recognition was requested from a build without it
+#[cfg(not(feature = "ocr"))]
+pub fn check_available() -> Result<(), McpError> {
+    Err(McpError::invalid_request(
+        "Text recognition isn't available, this server was built without the `ocr` feature. Rebuild with `--features ocr`, which needs the Tesseract library installed",
+        None,
+    ))
+}
+
+#[cfg(feature = "ocr")]
+pub fn check_available() -> Result<(), McpError> {
+    Ok(())
+}
+
+/// The text Tesseract recognizes in `image`, read from its grayscale pixels
+#[cfg(feature = "ocr")]
+pub fn recognize(image: &DynamicImage) -> Result<String, McpError> {
+    let pixels = image.to_luma8();
+    // Tesseract takes the dimensions and the buffer size as C ints
+    let too_large = || {
+        McpError::invalid_params(
+            format!(
+                "The {}x{} image is too large to recognize text in, resize it first",
+                pixels.width(),
+                pixels.height()
+            ),
+            None,
+        )
+    };
+    if u64::from(pixels.width()) * u64::from(pixels.height()) > i32::MAX as u64 {
+        return Err(too_large());
+    }
+    let width = i32::try_from(pixels.width()).map_err(|_| too_large())?;
+    let height = i32::try_from(pixels.height()).map_err(|_| too_large())?;
+
+    // Missing language data fails here rather than aborting the server
+    let mut tesseract = tesseract::Tesseract::new(None, Some(LANGUAGE))
+        .map_err(|e| {
+            McpError::internal_error(
+                format!(
+                    "Failed to start Tesseract: {e}. Install Tesseract with its '{LANGUAGE}' language data, or set TESSDATA_PREFIX to the directory holding it"
+                ),
+                None,
+            )
+        })?
+        .set_frame(pixels.as_raw(), width, height, 1, width)
+        .map_err(|e| {
+            McpError::internal_error(format!("Failed to pass the image to Tesseract: {e}"), None)
+        })?;
+    tesseract
+        .get_text()
+        .map_err(|e| McpError::internal_error(format!("Failed to recognize text: {e}"), None))
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn recognize(_image: &DynamicImage) -> Result<String, McpError> {
+    check_available().map(|()| String::new())
+}
@@ -1,14 +1,209 @@
 use base64::Engine;
+use regex::Regex;
 use rmcp::{
     Error as McpError,
     model::CallToolResult,
     model::{Content, Role},
+    schemars,
 };
-use std::io::Cursor;
-use xcap::{Monitor, Window};
+use std::{collections::HashMap, io::Cursor, path::PathBuf, sync::Arc};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use xcap::{
+    Monitor, Window,
+    image::{Rgba, RgbaImage},
+};
+
+use crate::developer::{
+    DEFAULT_IMAGE_MAX_WIDTH, DEFAULT_JPEG_QUALITY, acquire_permit, cancelled, cancelled_error,
+    check_cancelled, validate_jpeg_quality,
+};
+
+// Per-channel difference below which two pixels are considered the same, absorbing
+// antialiasing and compression noise
+const PIXEL_DIFF_THRESHOLD: u8 = 16;
+
+/// A rectangle of a display, in physical pixels from its top-left corner
+#[derive(
+    Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Image encoding of a returned screenshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureFormat {
+    #[default]
+    Png,
+    /// Lossy and much smaller for photo-heavy screens, quality from 1 to 100
+    Jpeg { quality: u8 },
+}
+
+impl CaptureFormat {
+    /// Parse the tool's `format` and `quality` parameters, PNG when no format is given
+    pub fn parse(format: Option<&str>, quality: Option<u8>) -> Result<Self, McpError> {
+        validate_jpeg_quality(quality)?;
+        match format.map(|format| format.to_ascii_lowercase()).as_deref() {
+            None | Some("png") => Ok(Self::Png),
+            Some("jpeg") | Some("jpg") => Ok(Self::Jpeg {
+                quality: quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            }),
+            Some(other) => Err(McpError::invalid_params(
+                format!("Unsupported format '{other}', use 'png' or 'jpeg'"),
+                None,
+            )),
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+        }
+    }
+}
+
+/// How `window_title` is matched against window titles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowMatch {
+    /// The title must be identical
+    #[default]
+    Exact,
+    /// The title contains the text, ignoring case
+    Contains,
+    /// The title matches the regular expression
+    Regex,
+}
+
+impl WindowMatch {
+    pub fn parse(mode: Option<&str>) -> Result<Self, McpError> {
+        match mode.map(|mode| mode.to_ascii_lowercase()).as_deref() {
+            None | Some("exact") => Ok(Self::Exact),
+            Some("contains") => Ok(Self::Contains),
+            Some("regex") => Ok(Self::Regex),
+            Some(other) => Err(McpError::invalid_params(
+                format!("Unknown match_mode '{other}', use 'exact', 'contains' or 'regex'"),
+                None,
+            )),
+        }
+    }
+
+    // Index of the only title matching `pattern`, listing the candidates when it's ambiguous
+    fn select(&self, pattern: &str, titles: &[&str]) -> Result<usize, McpError> {
+        let matcher: Box<dyn Fn(&str) -> bool> = match self {
+            Self::Exact => Box::new(|title| title == pattern),
+            Self::Contains => {
+                let pattern = pattern.to_lowercase();
+                Box::new(move |title| title.to_lowercase().contains(&pattern))
+            }
+            Self::Regex => {
+                let regex = Regex::new(pattern).map_err(|e| {
+                    McpError::invalid_params(format!("Invalid regex '{pattern}': {e}"), None)
+                })?;
+                Box::new(move |title| regex.is_match(title))
+            }
+        };
+
+        let matches: Vec<usize> = (0..titles.len())
+            .filter(|index| matcher(titles[*index]))
+            .collect();
+        match matches.as_slice() {
+            [] => Err(McpError::invalid_params(
+                format!("No window found matching '{pattern}'"),
+                None,
+            )),
+            [index] => Ok(*index),
+            _ => Err(McpError::invalid_params(
+                format!(
+                    "{count} windows match '{pattern}', use a more specific title:\n{titles}",
+                    count = matches.len(),
+                    titles = matches
+                        .iter()
+                        .map(|index| titles[*index])
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+                None,
+            )),
+        }
+    }
+}
+
+/// Optional behaviors for `capture`
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Crop the display or window to this rectangle, clamped to the captured image
+    pub region: Option<CaptureRegion>,
+    /// Encoding of the returned image
+    pub format: CaptureFormat,
+    /// Capture every display the policy allows, one image each
+    pub all_displays: bool,
+    /// How the window title is matched
+    pub window_match: WindowMatch,
+    /// Capture the window with this id from list_windows, taking precedence over the title
+    pub window_id: Option<u32>,
+    /// Draw a marker where the mouse pointer is
+    pub include_cursor: bool,
+    /// Stops waiting and capturing when the client cancels the request
+    pub cancel: Option<CancellationToken>,
+}
+
+// What was captured for a reference, so the comparison can capture the same area again
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ReferenceMetadata {
+    display: Option<i32>,
+    region: Option<CaptureRegion>,
+}
+
+/// Which displays and windows the capture tools may see. Window patterns are
+/// case-insensitive substrings of a window's title or application name.
+#[derive(Debug, Clone, Default)]
+pub struct CapturePolicy {
+    /// Display indices that may be captured, all displays when `None`
+    pub allowed_displays: Option<Vec<usize>>,
+    /// Only windows matching one of these patterns are visible, all windows when `None`
+    pub allowed_windows: Option<Vec<String>>,
+    /// Windows matching any of these patterns are never visible, even if allowed
+    pub denied_windows: Vec<String>,
+}
+
+impl CapturePolicy {
+    pub fn display_allowed(&self, display: usize) -> bool {
+        self.allowed_displays
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&display))
+    }
+
+    pub fn window_allowed(&self, title: &str, app_name: &str) -> bool {
+        let title = title.to_lowercase();
+        let app_name = app_name.to_lowercase();
+        let matches = |pattern: &String| {
+            let pattern = pattern.to_lowercase();
+            title.contains(&pattern) || app_name.contains(&pattern)
+        };
+        let allowed = self
+            .allowed_windows
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(matches));
+        allowed && !self.denied_windows.iter().any(matches)
+    }
+}
 
 #[derive(Clone)]
-pub struct ScreenCapture;
+pub struct ScreenCapture {
+    // Optional limit on concurrent captures, shared with image processing
+    concurrency_limit: Option<Arc<Semaphore>>,
+    // Directory where named reference screenshots are stored
+    reference_dir: PathBuf,
+    // Restricts which displays and windows can be listed or captured
+    policy: CapturePolicy,
+    // Wider captures are downscaled before they are returned, None keeps them as is
+    max_width: Option<u32>,
+}
 
 impl Default for ScreenCapture {
     fn default() -> Self {
@@ -18,7 +213,64 @@ impl Default for ScreenCapture {
 
 impl ScreenCapture {
     pub fn new() -> Self {
-        Self
+        Self {
+            concurrency_limit: None,
+            reference_dir: std::env::temp_dir().join("developer-screen-references"),
+            policy: CapturePolicy::default(),
+            max_width: Some(DEFAULT_IMAGE_MAX_WIDTH),
+        }
+    }
+
+    /// Cap returned image widths at `max_width` pixels, or never downscale with None
+    pub fn with_max_width(mut self, max_width: Option<u32>) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Widest image returned before downscaling, None when images keep their size
+    pub fn max_width(&self) -> Option<u32> {
+        self.max_width
+    }
+
+    /// Whether there is a display to capture, false on headless machines
+    pub fn display_available() -> bool {
+        Self::require_display().is_ok()
+    }
+
+    // Fail early and clearly without a display, rather than deep inside a capture
+    fn require_display() -> Result<(), McpError> {
+        if !display_server_configured() {
+            return Err(McpError::invalid_request(
+                "No display is available for screen capture: neither DISPLAY nor WAYLAND_DISPLAY is set, so this server looks headless",
+                None,
+            ));
+        }
+        match Monitor::all() {
+            Ok(monitors) if !monitors.is_empty() => Ok(()),
+            Ok(_) => Err(McpError::invalid_request(
+                "No display is available for screen capture: no monitors were found",
+                None,
+            )),
+            Err(e) => Err(McpError::invalid_request(
+                format!("No display is available for screen capture: {e}"),
+                None,
+            )),
+        }
+    }
+
+    pub fn with_concurrency_limit(mut self, concurrency_limit: Arc<Semaphore>) -> Self {
+        self.concurrency_limit = Some(concurrency_limit);
+        self
+    }
+
+    pub fn with_reference_dir(mut self, reference_dir: PathBuf) -> Self {
+        self.reference_dir = reference_dir;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CapturePolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     pub async fn capture(
@@ -26,57 +278,367 @@ impl ScreenCapture {
         display: Option<i32>,
         window_title: Option<String>,
     ) -> Result<CallToolResult, McpError> {
-        let mut image = if let Some(window_title) = window_title {
-            // Try to find and capture the specified window
-            let windows = Window::all().map_err(|_| {
-                McpError::internal_error("Failed to list windows".to_string(), None)
-            })?;
+        self.capture_with_options(display, window_title, CaptureOptions::default())
+            .await
+    }
 
-            let window = windows
-                .into_iter()
-                .find(|w| w.title() == window_title)
-                .ok_or_else(|| {
-                    McpError::invalid_params(
-                        format!("No window found with title '{window_title}'"),
-                        None,
-                    )
-                })?;
+    pub async fn capture_with_options(
+        &self,
+        display: Option<i32>,
+        window_title: Option<String>,
+        options: CaptureOptions,
+    ) -> Result<CallToolResult, McpError> {
+        // Queue behind other image/screen operations when the limit is reached
+        let cancel = options.cancel.as_ref();
+        let _permit = tokio::select! {
+            permit = acquire_permit(&self.concurrency_limit) => permit?,
+            _ = cancelled(cancel) => return Err(cancelled_error()),
+        };
+
+        if options.all_displays {
+            if window_title.is_some()
+                || options.window_id.is_some()
+                || display.is_some()
+                || options.region.is_some()
+            {
+                return Err(McpError::invalid_params(
+                    "all_displays can't be combined with display, window_id, window_title or a region",
+                    None,
+                ));
+            }
+            return self.capture_all_displays(options.format, cancel);
+        }
 
-            window.capture_image().map_err(|e| {
+        // The captured image's top-left corner on the virtual desktop, to place the cursor
+        let window = match (options.window_id, window_title) {
+            (Some(window_id), _) => Some(self.find_window_by_id(window_id)?),
+            (None, Some(window_title)) => {
+                Some(self.find_window_by_title(&window_title, options.window_match)?)
+            }
+            (None, None) => None,
+        };
+        let (mut image, origin) = if let Some(window) = window {
+            let window_title = window.title();
+            if !self.policy.window_allowed(window_title, window.app_name()) {
+                return Err(McpError::invalid_request(
+                    format!(
+                        "Capturing window '{window_title}' is not allowed by the capture policy"
+                    ),
+                    None,
+                ));
+            }
+
+            let image = window.capture_image().map_err(|e| {
                 McpError::internal_error(
                     format!("Failed to capture window '{window_title}': {e}"),
                     None,
                 )
-            })?
+            })?;
+            (image, (window.x(), window.y()))
         } else {
             // Default to display capture if no window title is specified
-            let display_num = display.unwrap_or(0) as usize;
+            self.capture_display_at(display)?
+        };
 
-            let monitors = Monitor::all().map_err(|_| {
-                McpError::internal_error("Failed to access monitors".to_string(), None)
-            })?;
-            let monitor = monitors.get(display_num).ok_or_else(|| {
+        let mut message = "Screenshot captured".to_string();
+        if options.include_cursor {
+            match cursor_position() {
+                Some((x, y)) => draw_cursor(&mut image, x - origin.0, y - origin.1),
+                None => message.push_str(" (the cursor position isn't available on this platform)"),
+            }
+        }
+
+        let image = match options.region {
+            Some(region) => crop_region(&image, clamp_region(&image, region)?)?,
+            None => image,
+        };
+
+        check_cancelled(cancel)?;
+        let data = Self::encode_for_output(image, options.format, self.max_width)?;
+
+        Ok(CallToolResult::success(vec![
+            Content::text(message).with_audience(vec![Role::Assistant]),
+            Content::image(data, options.format.mime_type()).with_priority(0.0),
+        ]))
+    }
+
+    // Ids stay the same while a window is open, unlike titles of e.g. browser tabs. A window the
+    // policy hides gets the same error as a closed one, so probing ids reveals nothing
+    fn find_window_by_id(&self, window_id: u32) -> Result<Window, McpError> {
+        Self::all_windows()?
+            .into_iter()
+            .filter(|w| self.policy.window_allowed(w.title(), w.app_name()))
+            .find(|w| w.id() == window_id)
+            .ok_or_else(|| {
                 McpError::invalid_params(
                     format!(
-                        "{} was not an available monitor, {} found.",
-                        display_num,
-                        monitors.len()
+                        "No open window has id {window_id}, use list_windows to find current ids"
                     ),
                     None,
                 )
-            })?;
+            })
+    }
 
-            monitor.capture_image().map_err(|e| {
-                McpError::internal_error(
-                    format!("Failed to capture display {display_num}: {e}"),
-                    None,
+    fn find_window_by_title(
+        &self,
+        window_title: &str,
+        window_match: WindowMatch,
+    ) -> Result<Window, McpError> {
+        // Only windows the policy allows are candidates, so none are revealed
+        let mut candidates: Vec<Window> = Self::all_windows()?
+            .into_iter()
+            .filter(|w| self.policy.window_allowed(w.title(), w.app_name()))
+            .collect();
+        match window_match {
+            WindowMatch::Exact => candidates
+                .into_iter()
+                .find(|w| w.title() == window_title)
+                .ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!("No window found with title '{window_title}'"),
+                        None,
+                    )
+                }),
+            mode => {
+                let titles: Vec<&str> = candidates.iter().map(|w| w.title()).collect();
+                let index = mode.select(window_title, &titles)?;
+                Ok(candidates.swap_remove(index))
+            }
+        }
+    }
+
+    fn all_windows() -> Result<Vec<Window>, McpError> {
+        Self::require_display()?;
+        Window::all()
+            .map_err(|_| McpError::internal_error("Failed to list windows".to_string(), None))
+    }
+
+    // One labeled image per allowed display
+    fn capture_all_displays(
+        &self,
+        format: CaptureFormat,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        Self::require_display()?;
+        let monitors = Monitor::all()
+            .map_err(|_| McpError::internal_error("Failed to access monitors".to_string(), None))?;
+
+        let mut content = Vec::new();
+        for (index, monitor) in monitors.iter().enumerate() {
+            if !self.policy.display_allowed(index) {
+                continue;
+            }
+            check_cancelled(cancel)?;
+            let image = monitor.capture_image().map_err(|e| {
+                McpError::internal_error(format!("Failed to capture display {index}: {e}"), None)
+            })?;
+            let primary = if monitor.is_primary() {
+                ", primary"
+            } else {
+                ""
+            };
+            content.push(
+                Content::text(format!(
+                    "Display {index} ({width}x{height}{primary}):",
+                    width = monitor.width(),
+                    height = monitor.height()
+                ))
+                .with_audience(vec![Role::Assistant]),
+            );
+            content.push(
+                Content::image(
+                    Self::encode_for_output(image, format, self.max_width)?,
+                    format.mime_type(),
                 )
-            })?
+                .with_priority(0.0),
+            );
+        }
+
+        if content.is_empty() {
+            return Err(McpError::invalid_request(
+                format!(
+                    "None of the {} displays may be captured under the capture policy",
+                    monitors.len()
+                ),
+                None,
+            ));
+        }
+        Ok(CallToolResult::success(content))
+    }
+
+    /// Capture a display (or a region of it) and store it as a named reference
+    pub async fn save_reference(
+        &self,
+        name: String,
+        display: Option<i32>,
+        region: Option<CaptureRegion>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let image_path = self.reference_path(&name, "png")?;
+
+        // Queue behind other image/screen operations when the limit is reached
+        let _permit = tokio::select! {
+            permit = acquire_permit(&self.concurrency_limit) => permit?,
+            _ = cancelled(cancel) => return Err(cancelled_error()),
+        };
+
+        let image = self.capture_area(display, region)?;
+        check_cancelled(cancel)?;
+
+        std::fs::create_dir_all(&self.reference_dir).map_err(|e| {
+            McpError::internal_error(format!("Failed to create reference directory: {e}"), None)
+        })?;
+        // Store at full resolution so comparisons aren't blurred by downscaling
+        image.save(&image_path).map_err(|e| {
+            McpError::internal_error(format!("Failed to save reference '{name}': {e}"), None)
+        })?;
+        let metadata =
+            serde_json::to_string(&ReferenceMetadata { display, region }).map_err(|e| {
+                McpError::internal_error(format!("Failed to encode metadata: {e}"), None)
+            })?;
+        std::fs::write(self.reference_path(&name, "json")?, metadata).map_err(|e| {
+            McpError::internal_error(format!("Failed to save reference '{name}': {e}"), None)
+        })?;
+
+        let data = Self::encode_for_output(image.clone(), CaptureFormat::Png, self.max_width)?;
+        Ok(CallToolResult::success(vec![
+            Content::text(format!(
+                "Saved reference '{name}' ({width}x{height}) to {display}",
+                width = image.width(),
+                height = image.height(),
+                display = image_path.display()
+            ))
+            .with_audience(vec![Role::Assistant]),
+            Content::image(data, "image/png").with_priority(0.0),
+        ]))
+    }
+
+    /// Capture the area of a named reference again and report how much it changed
+    pub async fn compare_reference(
+        &self,
+        name: String,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let image_path = self.reference_path(&name, "png")?;
+        let metadata_path = self.reference_path(&name, "json")?;
+        if !image_path.is_file() || !metadata_path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "No reference named '{name}' exists, save one with save_reference_screenshot first"
+                ),
+                None,
+            ));
+        }
+
+        // Queue behind other image/screen operations when the limit is reached
+        let _permit = tokio::select! {
+            permit = acquire_permit(&self.concurrency_limit) => permit?,
+            _ = cancelled(cancel) => return Err(cancelled_error()),
         };
 
+        let metadata: ReferenceMetadata = std::fs::read_to_string(&metadata_path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to read reference '{name}': {e}"), None)
+            })?;
+        let reference = xcap::image::open(&image_path)
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to open reference '{name}': {e}"), None)
+            })?
+            .to_rgba8();
+
+        let current = self.capture_area(metadata.display, metadata.region)?;
+        check_cancelled(cancel)?;
+        let (difference, diff_image) = diff_images(&reference, &current)?;
+
+        let data = Self::encode_for_output(diff_image, CaptureFormat::Png, self.max_width)?;
+        Ok(CallToolResult::success(vec![
+            Content::text(format!(
+                "Compared against reference '{name}': {difference:.2}% of pixels differ. Changed pixels are highlighted in red on the diff image."
+            ))
+            .with_audience(vec![Role::Assistant]),
+            Content::image(data, "image/png").with_priority(0.0),
+        ]))
+    }
+
+    // Only plain names, so a reference can't be written outside the reference directory
+    fn reference_path(&self, name: &str, extension: &str) -> Result<PathBuf, McpError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(McpError::invalid_params(
+                format!("Invalid reference name '{name}'. Use only letters, digits, '-' and '_'"),
+                None,
+            ));
+        }
+        Ok(self.reference_dir.join(format!("{name}.{extension}")))
+    }
+
+    fn capture_display(&self, display: Option<i32>) -> Result<RgbaImage, McpError> {
+        self.capture_display_at(display).map(|(image, _)| image)
+    }
+
+    // A display's image and its position on the virtual desktop
+    fn capture_display_at(
+        &self,
+        display: Option<i32>,
+    ) -> Result<(RgbaImage, (i32, i32)), McpError> {
+        let display_num = display.unwrap_or(0) as usize;
+
+        if !self.policy.display_allowed(display_num) {
+            return Err(McpError::invalid_request(
+                format!("Capturing display {display_num} is not allowed by the capture policy"),
+                None,
+            ));
+        }
+
+        Self::require_display()?;
+        let monitors = Monitor::all()
+            .map_err(|_| McpError::internal_error("Failed to access monitors".to_string(), None))?;
+        let monitor = monitors.get(display_num).ok_or_else(|| {
+            McpError::invalid_params(
+                format!(
+                    "{} was not an available monitor, {} found.",
+                    display_num,
+                    monitors.len()
+                ),
+                None,
+            )
+        })?;
+
+        let image = monitor.capture_image().map_err(|e| {
+            McpError::internal_error(
+                format!("Failed to capture display {display_num}: {e}"),
+                None,
+            )
+        })?;
+        Ok((image, (monitor.x(), monitor.y())))
+    }
+
+    fn capture_area(
+        &self,
+        display: Option<i32>,
+        region: Option<CaptureRegion>,
+    ) -> Result<RgbaImage, McpError> {
+        // Clamped like screen_capture regions, so both tools accept the same areas
+        let image = self.capture_display(display)?;
+        match region {
+            Some(region) => crop_region(&image, clamp_region(&image, region)?),
+            None => Ok(image),
+        }
+    }
+
+    // Downscale to a reasonable width and encode as base64
+    fn encode_for_output(
+        mut image: RgbaImage,
+        format: CaptureFormat,
+        max_width: Option<u32>,
+    ) -> Result<String, McpError> {
         // Resize the image to a reasonable width while maintaining aspect ratio
-        let max_width = 768;
-        if image.width() > max_width {
+        if let Some(max_width) = max_width.filter(|max_width| image.width() > *max_width) {
             let scale = max_width as f32 / image.width() as f32;
             let new_height = (image.height() as f32 * scale) as u32;
             image = xcap::image::imageops::resize(
@@ -88,26 +650,80 @@ impl ScreenCapture {
         };
 
         let mut bytes: Vec<u8> = Vec::new();
-        image
-            .write_to(&mut Cursor::new(&mut bytes), xcap::image::ImageFormat::Png)
-            .map_err(|e| {
-                McpError::internal_error(format!("Failed to write image buffer {e}"), None)
-            })?;
+        match format {
+            CaptureFormat::Png => image
+                .write_to(&mut Cursor::new(&mut bytes), xcap::image::ImageFormat::Png)
+                .map_err(|e| {
+                    McpError::internal_error(format!("Failed to write image buffer {e}"), None)
+                })?,
+            CaptureFormat::Jpeg { quality } => {
+                // JPEG has no alpha channel
+                let rgb_image = xcap::image::DynamicImage::ImageRgba8(image).to_rgb8();
+                xcap::image::codecs::jpeg::JpegEncoder::new_with_quality(
+                    &mut Cursor::new(&mut bytes),
+                    quality,
+                )
+                .encode(
+                    &rgb_image,
+                    rgb_image.width(),
+                    rgb_image.height(),
+                    xcap::image::ColorType::Rgb8.into(),
+                )
+                .map_err(|e| {
+                    McpError::internal_error(format!("Failed to encode JPEG: {e}"), None)
+                })?
+            }
+        }
 
         // Convert to base64
-        let data = base64::prelude::BASE64_STANDARD.encode(bytes);
+        Ok(base64::prelude::BASE64_STANDARD.encode(bytes))
+    }
+
+    /// Table of the displays that may be captured, with their index, size and position
+    pub async fn list_displays(&self) -> Result<CallToolResult, McpError> {
+        Self::require_display()?;
+        let monitors = Monitor::all()
+            .map_err(|_| McpError::internal_error("Failed to access monitors".to_string(), None))?;
+
+        let rows: Vec<String> = monitors
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.policy.display_allowed(*index))
+            .map(|(index, monitor)| {
+                format_display_row(
+                    index,
+                    monitor.width(),
+                    monitor.height(),
+                    monitor.x(),
+                    monitor.y(),
+                    monitor.is_primary(),
+                )
+            })
+            .collect();
+
+        let content = if rows.is_empty() {
+            "No displays found".to_string()
+        } else {
+            format!(
+                "display | width | height | x | y | primary\n{}",
+                rows.join("\n")
+            )
+        };
 
         Ok(CallToolResult::success(vec![
-            Content::text("Screenshot captured").with_audience(vec![Role::Assistant]),
-            Content::image(data, "image/png").with_priority(0.0),
+            Content::text(content.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(content)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
         ]))
     }
 
     pub async fn list_windows(&self) -> Result<CallToolResult, McpError> {
-        let windows = Window::all()
-            .map_err(|_| McpError::internal_error("Failed to list windows".to_string(), None))?;
+        let windows = Self::all_windows()?;
 
-        let mut window_info: Vec<String> = Vec::new();
+        let ids: Vec<u32> = windows.iter().map(|window| window.id()).collect();
+        let (focused, process_ids) = window_details(&ids);
+        let mut window_info: Vec<WindowInfo> = Vec::new();
 
         for window in windows.iter() {
             // Skip minimized windows as they can't be captured anyway
@@ -115,22 +731,46 @@ impl ScreenCapture {
                 continue;
             }
 
+            // Hide windows the capture policy doesn't allow
+            if !self
+                .policy
+                .window_allowed(window.title(), window.app_name())
+            {
+                continue;
+            }
+
             let title = window.title();
 
             // Only add non-empty titles
             if !title.is_empty() && title != "<No Title>" {
-                window_info.push(title.to_string());
+                window_info.push(WindowInfo {
+                    id: window.id(),
+                    title: title.to_string(),
+                    app_name: window.app_name().to_string(),
+                    process_id: window_process_id(window, &process_ids),
+                    focused: focused == Some(window.id()),
+                });
             }
         }
 
         let content = if window_info.is_empty() {
             "No windows found".to_string()
         } else {
-            format!("Available windows:\n{}", window_info.join("\n"))
+            let lines: Vec<String> = window_info
+                .iter()
+                .map(|window| {
+                    let focused = if window.focused { ", focused" } else { "" };
+                    format!(
+                        "{} ({}, id {}{focused})",
+                        window.title, window.app_name, window.id
+                    )
+                })
+                .collect();
+            format!("Available windows:\n{}", lines.join("\n"))
         };
 
         Ok(CallToolResult::success(vec![
-            Content::text(content.clone()).with_audience(vec![Role::Assistant]),
+            Content::json(&window_info)?.with_audience(vec![Role::Assistant]),
             Content::text(content)
                 .with_audience(vec![Role::User])
                 .with_priority(0.0),
@@ -138,6 +778,266 @@ impl ScreenCapture {
     }
 }
 
+/// A capturable window as reported by list_windows
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct WindowInfo {
+    /// Stable for the window's lifetime, the X11 window or platform window handle
+    pub id: u32,
+    pub title: String,
+    /// Name of the owning application
+    pub app_name: String,
+    /// None where neither the platform nor the window reports it
+    pub process_id: Option<u32>,
+    /// Whether the window has input focus, false where the platform doesn't say
+    pub focused: bool,
+}
+
+/// The focused window and the owning process of each of `ids`, where the platform exposes them
+#[cfg(target_os = "linux")]
+fn window_details(ids: &[u32]) -> (Option<u32>, HashMap<u32, u32>) {
+    // X11 only, read from the EWMH properties window managers maintain
+    let Ok((connection, screen_num)) = xcb::Connection::connect(None) else {
+        return (None, HashMap::new());
+    };
+    let root = usize::try_from(screen_num)
+        .ok()
+        .and_then(|screen| connection.get_setup().roots().nth(screen))
+        .map(|screen| screen.root());
+    let focused = root
+        .zip(x11_atom(&connection, b"_NET_ACTIVE_WINDOW"))
+        .and_then(|(root, property)| x11_cardinal(&connection, root, property, xcb::x::ATOM_WINDOW))
+        // No window is focused
+        .filter(|window| *window != 0);
+    let Some(pid_property) = x11_atom(&connection, b"_NET_WM_PID") else {
+        return (focused, HashMap::new());
+    };
+    let process_ids = ids
+        .iter()
+        .filter_map(|id| {
+            let window = xcb::XidNew::new(*id);
+            x11_cardinal(&connection, window, pid_property, xcb::x::ATOM_CARDINAL)
+                .map(|pid| (*id, pid))
+        })
+        .collect();
+    (focused, process_ids)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn window_details(_ids: &[u32]) -> (Option<u32>, HashMap<u32, u32>) {
+    (None, HashMap::new())
+}
+
+/// The atom named `name`, None if no client has interned it yet
+#[cfg(target_os = "linux")]
+fn x11_atom(connection: &xcb::Connection, name: &[u8]) -> Option<xcb::x::Atom> {
+    let cookie = connection.send_request(&xcb::x::InternAtom {
+        only_if_exists: true,
+        name,
+    });
+    let atom = connection.wait_for_reply(cookie).ok()?.atom();
+    (atom != xcb::x::ATOM_NONE).then_some(atom)
+}
+
+/// The first 32-bit value of a window property, None if the window doesn't have it
+#[cfg(target_os = "linux")]
+fn x11_cardinal(
+    connection: &xcb::Connection,
+    window: xcb::x::Window,
+    property: xcb::x::Atom,
+    r#type: xcb::x::Atom,
+) -> Option<u32> {
+    let cookie = connection.send_request(&xcb::x::GetProperty {
+        delete: false,
+        window,
+        property,
+        r#type,
+        long_offset: 0,
+        long_length: 1,
+    });
+    let reply = connection.wait_for_reply(cookie).ok()?;
+    reply.value::<u32>().first().copied()
+}
+
+#[cfg(target_os = "windows")]
+fn window_process_id(window: &Window, _process_ids: &HashMap<u32, u32>) -> Option<u32> {
+    Some(window.process_id())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn window_process_id(window: &Window, process_ids: &HashMap<u32, u32>) -> Option<u32> {
+    process_ids.get(&window.id()).copied()
+}
+
+/// Whether a display server is configured for this process, on Linux an X11 or Wayland display.
+/// Other platforms always have one
+#[cfg(target_os = "linux")]
+pub fn display_server_configured() -> bool {
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn display_server_configured() -> bool {
+    true
+}
+
+/// The pointer's position on the virtual desktop, where the platform exposes it
+#[cfg(target_os = "linux")]
+fn cursor_position() -> Option<(i32, i32)> {
+    // X11 only, Wayland doesn't let clients query the pointer
+    let (connection, screen_num) = xcb::Connection::connect(None).ok()?;
+    let root = connection
+        .get_setup()
+        .roots()
+        .nth(usize::try_from(screen_num).ok()?)?
+        .root();
+    let cookie = connection.send_request(&xcb::x::QueryPointer { window: root });
+    let reply = connection.wait_for_reply(cookie).ok()?;
+    Some((i32::from(reply.root_x()), i32::from(reply.root_y())))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cursor_position() -> Option<(i32, i32)> {
+    None
+}
+
+/// Draw a simple arrow pointer with its tip at (x, y), clipped to the image
+fn draw_cursor(image: &mut RgbaImage, x: i32, y: i32) {
+    const HEIGHT: i32 = 18;
+    for dy in 0..HEIGHT {
+        // The arrow widens toward the bottom, a triangle with a vertical left edge
+        let width = dy * 2 / 3;
+        for dx in 0..=width {
+            let (px, py) = (x + dx, y + dy);
+            if px < 0 || py < 0 || px >= image.width() as i32 || py >= image.height() as i32 {
+                continue;
+            }
+            let outline = dx == 0 || dx == width || dy == HEIGHT - 1;
+            let color = if outline {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            };
+            image.put_pixel(px as u32, py as u32, color);
+        }
+    }
+}
+
+fn format_display_row(
+    index: usize,
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    primary: bool,
+) -> String {
+    let primary = if primary { "yes" } else { "no" };
+    format!("{index} | {width} | {height} | {x} | {y} | {primary}")
+}
+
+fn crop_region(image: &RgbaImage, region: CaptureRegion) -> Result<RgbaImage, McpError> {
+    let fits = region.width > 0
+        && region.height > 0
+        && region
+            .x
+            .checked_add(region.width)
+            .is_some_and(|right| right <= image.width())
+        && region
+            .y
+            .checked_add(region.height)
+            .is_some_and(|bottom| bottom <= image.height());
+    if !fits {
+        return Err(McpError::invalid_params(
+            format!(
+                "Region {width}x{height} at ({x}, {y}) is outside the {image_width}x{image_height} display",
+                width = region.width,
+                height = region.height,
+                x = region.x,
+                y = region.y,
+                image_width = image.width(),
+                image_height = image.height()
+            ),
+            None,
+        ));
+    }
+    Ok(
+        xcap::image::imageops::crop_imm(image, region.x, region.y, region.width, region.height)
+            .to_image(),
+    )
+}
+
+/// The part of `region` that lies within the image, an error when there is none
+fn clamp_region(image: &RgbaImage, region: CaptureRegion) -> Result<CaptureRegion, McpError> {
+    let right = region.x.saturating_add(region.width).min(image.width());
+    let bottom = region.y.saturating_add(region.height).min(image.height());
+    if region.x >= right || region.y >= bottom {
+        return Err(McpError::invalid_params(
+            format!(
+                "Region {width}x{height} at ({x}, {y}) is entirely outside the {image_width}x{image_height} capture",
+                width = region.width,
+                height = region.height,
+                x = region.x,
+                y = region.y,
+                image_width = image.width(),
+                image_height = image.height()
+            ),
+            None,
+        ));
+    }
+    Ok(CaptureRegion {
+        x: region.x,
+        y: region.y,
+        width: right - region.x,
+        height: bottom - region.y,
+    })
+}
+
+/// Percentage of differing pixels and a diff image: the current capture dimmed to
+/// grayscale with changed pixels in red
+fn diff_images(reference: &RgbaImage, current: &RgbaImage) -> Result<(f64, RgbaImage), McpError> {
+    if reference.dimensions() != current.dimensions() {
+        return Err(McpError::invalid_params(
+            format!(
+                "The capture is {}x{} but the reference is {}x{}, the display resolution may have changed",
+                current.width(),
+                current.height(),
+                reference.width(),
+                reference.height()
+            ),
+            None,
+        ));
+    }
+
+    let mut changed = 0u64;
+    let diff = RgbaImage::from_fn(current.width(), current.height(), |x, y| {
+        let before = reference.get_pixel(x, y);
+        let after = current.get_pixel(x, y);
+        let differs = before
+            .0
+            .iter()
+            .zip(after.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > PIXEL_DIFF_THRESHOLD);
+        if differs {
+            changed += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = after.0;
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8;
+            let dimmed = luma / 3 + 128;
+            Rgba([dimmed, dimmed, dimmed, 255])
+        }
+    });
+
+    let total = u64::from(current.width()) * u64::from(current.height());
+    let percentage = if total == 0 {
+        0.0
+    } else {
+        changed as f64 * 100.0 / total as f64
+    };
+    Ok((percentage, diff))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,38 +1046,31 @@ mod tests {
     async fn test_list_windows() {
         let screen_capture = ScreenCapture::new();
         let result = screen_capture.list_windows().await;
+        if !ScreenCapture::display_available() {
+            // Headless machines get a clear error instead of an empty or failed listing
+            let err = result.unwrap_err();
+            assert!(err.message.contains("No display is available"));
+            return;
+        }
         assert!(result.is_ok());
         let call_result = result.unwrap();
-        assert!(!call_result.content.is_empty());
-
-        // Print the window list to see what's detected
-        // Re-create the window list for printing
-        let windows = Window::all().unwrap();
-        let mut window_titles: Vec<String> = Vec::new();
+        assert_eq!(call_result.content.len(), 2);
 
-        for window in windows.iter() {
-            if window.is_minimized() {
-                continue;
-            }
-            let title = window.title();
-            if !title.is_empty() && title != "<No Title>" {
-                window_titles.push(title.to_string());
-            }
+        // The assistant gets one JSON entry per window, at most one of them focused
+        let windows: Vec<serde_json::Value> =
+            serde_json::from_str(&call_result.content[0].as_text().unwrap().text).unwrap();
+        for window in &windows {
+            assert!(window["id"].is_u64());
+            assert!(window["title"].is_string());
+            assert!(window["app_name"].is_string());
+            assert!(window["process_id"].is_u64() || window["process_id"].is_null());
         }
+        let focused = windows.iter().filter(|window| window["focused"] == true);
+        assert!(focused.count() <= 1);
 
         println!("=== WINDOW LIST ===");
-        if window_titles.is_empty() {
-            println!("No windows found");
-        } else {
-            println!("Available windows:");
-            for title in window_titles {
-                println!("{title}");
-            }
-        }
+        println!("{}", call_result.content[1].as_text().unwrap().text);
         println!("=== END WINDOW LIST ===");
-
-        // Check that the content includes window information
-        assert!(!call_result.content.is_empty());
     }
 
     #[tokio::test]
@@ -206,7 +1099,281 @@ mod tests {
             .await;
         assert!(result.is_err());
         if let Err(e) = result {
-            assert!(e.to_string().contains("No window found"));
+            let expected = if ScreenCapture::display_available() {
+                "No window found"
+            } else {
+                "No display is available"
+            };
+            assert!(e.to_string().contains(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_unknown_window_id() {
+        let screen_capture = ScreenCapture::new();
+        let options = CaptureOptions {
+            window_id: Some(u32::MAX),
+            ..Default::default()
+        };
+        // The id wins over a title that would otherwise be looked up
+        let result = screen_capture
+            .capture_with_options(None, Some("NonExistentWindow12345".to_string()), options)
+            .await;
+        let err = result.unwrap_err();
+        // Without a display server the windows can't be listed at all
+        if !err.message.contains("No display is available") {
+            assert!(
+                err.message
+                    .contains(&format!("No open window has id {}", u32::MAX))
+            );
+        }
+
+        let options = CaptureOptions {
+            window_id: Some(1),
+            all_displays: true,
+            ..Default::default()
+        };
+        assert!(
+            screen_capture
+                .capture_with_options(None, None, options)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_diff_images() {
+        let reference = RgbaImage::from_pixel(10, 10, Rgba([10, 20, 30, 255]));
+        let mut current = reference.clone();
+        // Small noise is tolerated, a real change is not
+        current.put_pixel(0, 0, Rgba([15, 20, 30, 255]));
+        for x in 0..10 {
+            current.put_pixel(x, 5, Rgba([200, 20, 30, 255]));
+        }
+
+        let (difference, diff) = diff_images(&reference, &current).unwrap();
+        assert!((difference - 10.0).abs() < f64::EPSILON);
+        assert_eq!(diff.get_pixel(3, 5), &Rgba([255, 0, 0, 255]));
+        assert_ne!(diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+
+        let smaller = RgbaImage::new(5, 5);
+        let result = diff_images(&reference, &smaller);
+        assert!(result.unwrap_err().to_string().contains("resolution"));
+    }
+
+    #[test]
+    fn test_crop_region_and_reference_names() {
+        let image = RgbaImage::new(100, 50);
+        let region = CaptureRegion {
+            x: 10,
+            y: 10,
+            width: 20,
+            height: 30,
+        };
+        assert_eq!(crop_region(&image, region).unwrap().dimensions(), (20, 30));
+
+        let outside = CaptureRegion {
+            x: 90,
+            y: 0,
+            width: 20,
+            height: 10,
+        };
+        assert!(crop_region(&image, outside).is_err());
+
+        let screen_capture = ScreenCapture::new().with_reference_dir(PathBuf::from("/refs"));
+        assert_eq!(
+            screen_capture.reference_path("login_page", "png").unwrap(),
+            PathBuf::from("/refs/login_page.png")
+        );
+        assert!(screen_capture.reference_path("../escape", "png").is_err());
+    }
+
+    #[test]
+    fn test_capture_region_is_clamped() {
+        let mut image = RgbaImage::new(100, 50);
+        image.put_pixel(95, 45, Rgba([255, 0, 0, 255]));
+
+        // A region hanging off the bottom-right corner keeps the part inside
+        let region = CaptureRegion {
+            x: 90,
+            y: 40,
+            width: 50,
+            height: 50,
+        };
+        let clamped = clamp_region(&image, region).unwrap();
+        assert_eq!(
+            clamped,
+            CaptureRegion {
+                x: 90,
+                y: 40,
+                width: 10,
+                height: 10,
+            }
+        );
+        let cropped = crop_region(&image, clamped).unwrap();
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert_eq!(cropped.get_pixel(5, 5), &Rgba([255, 0, 0, 255]));
+
+        let outside = CaptureRegion {
+            x: 100,
+            y: 0,
+            width: 10,
+            height: 10,
+        };
+        let result = clamp_region(&image, outside);
+        assert!(result.unwrap_err().to_string().contains("entirely outside"));
+    }
+
+    #[test]
+    fn test_capture_format() {
+        assert_eq!(
+            CaptureFormat::parse(None, None).unwrap(),
+            CaptureFormat::Png
+        );
+        assert_eq!(
+            CaptureFormat::parse(Some("JPEG"), None).unwrap(),
+            CaptureFormat::Jpeg { quality: 85 }
+        );
+        let jpeg = CaptureFormat::parse(Some("jpeg"), Some(40)).unwrap();
+        assert_eq!(jpeg.mime_type(), "image/jpeg");
+        assert!(CaptureFormat::parse(Some("jpeg"), Some(0)).is_err());
+        assert!(CaptureFormat::parse(Some("jpeg"), Some(101)).is_err());
+        assert!(CaptureFormat::parse(Some("gif"), None).is_err());
+
+        let image = RgbaImage::from_pixel(16, 16, Rgba([200, 100, 50, 255]));
+        let data = ScreenCapture::encode_for_output(image, jpeg, Some(768)).unwrap();
+        let bytes = base64::prelude::BASE64_STANDARD.decode(data).unwrap();
+        assert_eq!(
+            xcap::image::guess_format(&bytes).unwrap(),
+            xcap::image::ImageFormat::Jpeg
+        );
+    }
+
+    #[test]
+    fn test_window_match() {
+        let titles = [
+            "main.rs - Visual Studio Code",
+            "lib.rs - Visual Studio Code",
+            "Terminal",
+        ];
+        assert_eq!(WindowMatch::Exact.select("Terminal", &titles).unwrap(), 2);
+        assert!(WindowMatch::Exact.select("terminal", &titles).is_err());
+        assert_eq!(WindowMatch::Contains.select("MAIN.RS", &titles).unwrap(), 0);
+        assert_eq!(WindowMatch::Regex.select(r"^lib\.rs", &titles).unwrap(), 1);
+
+        let result = WindowMatch::Contains.select("Visual Studio", &titles);
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("2 windows match"));
+        assert!(error.contains("lib.rs - Visual Studio Code"));
+
+        assert!(WindowMatch::Regex.select("(", &titles).is_err());
+        assert_eq!(
+            WindowMatch::parse(Some("Contains")).unwrap(),
+            WindowMatch::Contains
+        );
+        assert!(WindowMatch::parse(Some("fuzzy")).is_err());
+    }
+
+    #[test]
+    fn test_draw_cursor() {
+        let background = Rgba([0, 128, 0, 255]);
+        let mut image = RgbaImage::from_pixel(40, 40, background);
+        draw_cursor(&mut image, 10, 10);
+        // Outlined tip, white body, untouched surroundings
+        assert_eq!(image.get_pixel(10, 10), &Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(12, 20), &Rgba([255, 255, 255, 255]));
+        assert_eq!(image.get_pixel(9, 10), &background);
+        assert_eq!(image.get_pixel(30, 10), &background);
+
+        // A pointer partly or entirely off the image is clipped
+        draw_cursor(&mut image, 35, 35);
+        draw_cursor(&mut image, -5, -50);
+        assert_eq!(image.get_pixel(35, 35), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_capture_policy() {
+        let policy = CapturePolicy {
+            allowed_displays: Some(vec![1]),
+            allowed_windows: None,
+            denied_windows: vec!["password".to_string()],
+        };
+        assert!(policy.display_allowed(1));
+        assert!(!policy.display_allowed(0));
+        assert!(policy.window_allowed("Terminal", "kitty"));
+        assert!(!policy.window_allowed("Vault", "Password Manager"));
+
+        let policy = CapturePolicy {
+            allowed_windows: Some(vec!["firefox".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.display_allowed(0));
+        assert!(policy.window_allowed("Docs - Mozilla Firefox", "firefox"));
+        assert!(!policy.window_allowed("Terminal", "kitty"));
+    }
+
+    #[tokio::test]
+    async fn test_capture_all_displays() {
+        let screen_capture = ScreenCapture::new();
+        let options = CaptureOptions {
+            all_displays: true,
+            ..Default::default()
+        };
+
+        // Mixing modes is rejected before anything is captured
+        let result = screen_capture
+            .capture_with_options(Some(0), None, options.clone())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("can't be combined")
+        );
+
+        // Headless environments have no displays to capture
+        if let Ok(call_result) = screen_capture
+            .capture_with_options(None, None, options)
+            .await
+        {
+            assert_eq!(call_result.content.len() % 2, 0);
+            assert!(
+                call_result.content[0]
+                    .as_text()
+                    .unwrap()
+                    .text
+                    .starts_with("Display ")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_displays() {
+        assert_eq!(
+            format_display_row(1, 2560, 1440, -2560, 0, false),
+            "1 | 2560 | 1440 | -2560 | 0 | no"
+        );
+
+        // Every display is hidden when none may be captured
+        let screen_capture = ScreenCapture::new().with_policy(CapturePolicy {
+            allowed_displays: Some(vec![]),
+            ..Default::default()
+        });
+        if let Ok(result) = screen_capture.list_displays().await {
+            assert_eq!(
+                result.content[0].as_text().unwrap().text,
+                "No displays found"
+            );
         }
     }
+
+    #[tokio::test]
+    async fn test_capture_disallowed_display() {
+        let screen_capture = ScreenCapture::new().with_policy(CapturePolicy {
+            allowed_displays: Some(vec![]),
+            ..Default::default()
+        });
+        let result = screen_capture.capture(Some(0), None).await;
+        assert!(result.unwrap_err().to_string().contains("not allowed"));
+    }
 }
@@ -1,16 +1,29 @@
 use anyhow::Result;
-use ignore::gitignore::Gitignore;
 use rmcp::{
     Error as McpError,
     model::CallToolResult,
     model::{Content, Role},
 };
 
-use std::{env, path::Path, process::Stdio, sync::Arc};
-use tokio::process::Command;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc::UnboundedSender,
+};
+use tokio_util::sync::CancellationToken;
 
 // Import utilities from parent module
-use crate::developer::normalize_line_endings;
+use crate::developer::{
+    IgnorePatterns, cancelled, cancelled_error, is_access_control_file, normalize_line_endings,
+};
 
 #[derive(Debug, Clone)]
 pub struct ShellConfig {
@@ -21,6 +34,29 @@ pub struct ShellConfig {
 
 impl Default for ShellConfig {
     fn default() -> Self {
+        Self::with_overrides(None, None)
+    }
+}
+
+impl ShellConfig {
+    /// The platform shell with configured replacements, which the environment overrides in turn
+    pub fn with_overrides(executable: Option<String>, arg: Option<String>) -> Self {
+        let mut config = Self::platform_default();
+        // Let containers and users pick their shell, e.g. `/bin/sh` where bash isn't installed
+        if let Some(executable) = env::var("SHELL_EXECUTABLE")
+            .ok()
+            .or(executable)
+            .filter(|s| !s.is_empty())
+        {
+            config.executable = executable;
+        }
+        if let Some(arg) = env::var("SHELL_ARG").ok().or(arg) {
+            config.arg = arg;
+        }
+        config
+    }
+
+    fn platform_default() -> Self {
         if cfg!(windows) {
             // Execute PowerShell commands directly
             Self {
@@ -40,12 +76,638 @@ impl Default for ShellConfig {
     }
 }
 
+/// Optional behaviors for `execute`
+#[derive(Debug, Clone, Default)]
+pub struct ExecuteOptions {
+    /// Override the shell's blank-line trimming for this command
+    pub trim_blank_lines: Option<bool>,
+    /// Run the command in this directory instead of the server's working directory
+    pub cwd: Option<PathBuf>,
+    /// Receives each output line as it is produced, for streaming progress to the client
+    pub progress: Option<UnboundedSender<String>>,
+    /// Capture stdout and stderr separately and report them as JSON with the exit code
+    pub separate_streams: bool,
+    /// Run the command in the named long-lived shell, keeping its cwd and variables
+    pub session_id: Option<String>,
+    /// Extra environment variables for this command only, layered over the inherited ones
+    pub env: HashMap<String, String>,
+    /// Kills the command when the client cancels the request
+    pub cancel: Option<CancellationToken>,
+    /// Check the command and report what would run, without spawning anything
+    pub dry_run: bool,
+    /// Written to the command's stdin, which is closed afterwards
+    pub stdin: Option<String>,
+    /// Return readable text or a single JSON report of the run
+    pub output_format: OutputFormat,
+}
+
+/// How `execute` reports a finished command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The combined output as it would appear in a terminal, then the exit status
+    #[default]
+    Text,
+    /// One JSON block with the command, both streams, the exit code and the duration
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "Unknown output_format '{other}', use 'text' or 'json'"
+            )),
+        }
+    }
+}
+
+/// Why a command stopped running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    /// The command exited on its own
+    #[default]
+    Exited,
+    /// A signal terminated the command
+    Signaled,
+    /// The request was cancelled and the command killed
+    Cancelled,
+}
+
+/// How a command finished, for programmatic control flow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CommandStatus {
+    /// None when the process was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// The terminating signal on Unix
+    pub signal: Option<i32>,
+    /// Whether the command exited, was killed by a signal or was cancelled
+    pub termination: Termination,
+}
+
+impl CommandStatus {
+    fn of(status: &std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(status);
+        #[cfg(not(unix))]
+        let signal = None;
+        let termination = if signal.is_some() {
+            Termination::Signaled
+        } else {
+            Termination::Exited
+        };
+        Self {
+            exit_code: status.code(),
+            signal,
+            termination,
+        }
+    }
+}
+
+/// Machine-readable result of a command run with separate streams
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(flatten)]
+    pub status: CommandStatus,
+}
+
+/// Result of a command run with the JSON output format
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommandReport {
+    pub command: String,
+    #[serde(flatten)]
+    pub output: CommandOutput,
+    /// Time from spawning the command to collecting its exit status
+    pub duration_ms: u128,
+}
+
+/// What `execute` would run for a dry run, for approving the exact command first
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DryRun {
+    /// The command line passed to the shell, including any redirection added
+    pub command: String,
+    pub shell: String,
+    pub shell_arg: String,
+    pub cwd: Option<PathBuf>,
+    pub session_id: Option<String>,
+    /// Names of the extra environment variables, values are left out
+    pub env: Vec<String>,
+}
+
+/// Which programs the shell may run. Every simple command of a command line is checked,
+/// looking through `;`, `&&`, pipes, wrappers such as `sudo` or `env`, `sh -c`, `eval` and
+/// `find -exec`. It is a guard rail rather than a sandbox: interpreters such as `python -c`
+/// and scripts can still run anything
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    /// When set and not empty, only these programs may run
+    pub allowed: Option<Vec<String>>,
+    /// Programs that may never run, checked before the allowlist
+    pub denied: Vec<String>,
+}
+
+// Environment variables that make the shell or the dynamic linker run code the policy never sees
+const UNCHECKED_ENV: &[&str] = &[
+    "BASH_ENV",
+    "ENV",
+    "PROMPT_COMMAND",
+    "SHELLOPTS",
+    "BASHOPTS",
+    "IFS",
+    "PATH",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+];
+
+// Programs that run the command in their arguments, with the options that take a value
+const COMMAND_WRAPPERS: &[(&str, &[&str])] = &[
+    (
+        "sudo",
+        &[
+            "-u",
+            "-g",
+            "-C",
+            "-D",
+            "-h",
+            "-p",
+            "-r",
+            "-t",
+            "-T",
+            "-U",
+            "--user",
+            "--group",
+            "--chdir",
+            "--host",
+            "--prompt",
+            "--role",
+            "--type",
+            "--other-user",
+        ],
+    ),
+    ("doas", &["-u", "-C"]),
+    ("env", &["-u", "-C", "--unset", "--chdir"]),
+    ("nice", &["-n", "--adjustment"]),
+    ("nohup", &[]),
+    ("time", &["-f", "-o", "--format", "--output"]),
+    ("timeout", &["-s", "-k", "--signal", "--kill-after"]),
+    ("command", &[]),
+    ("exec", &["-a"]),
+    ("builtin", &[]),
+    (
+        "xargs",
+        &[
+            "-a",
+            "-d",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "-P",
+            "-s",
+            "--arg-file",
+            "--delimiter",
+            "--max-args",
+            "--max-lines",
+            "--max-procs",
+            "--max-chars",
+        ],
+    ),
+    (
+        "stdbuf",
+        &["-i", "-o", "-e", "--input", "--output", "--error"],
+    ),
+    ("setsid", &[]),
+    (
+        "ionice",
+        &["-c", "-n", "-p", "-P", "-u", "--class", "--classdata"],
+    ),
+];
+
+// Shells whose `-c` argument is a command line of its own
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh", "mksh", "ash", "fish"];
+
+// Builtins that set variables, checked for the ones in UNCHECKED_ENV
+const DECLARATION_BUILTINS: &[&str] = &["export", "declare", "typeset", "readonly", "local"];
+
+// Reserved words that may precede a command's program
+const RESERVED_WORDS: &[&str] = &[
+    "!", "{", "}", "if", "then", "else", "elif", "fi", "do", "done", "while", "until",
+];
+
+// `sh -c "bash -c '...'"` is followed this many levels deep
+const MAX_POLICY_NESTING: usize = 8;
+
+impl CommandPolicy {
+    /// Read `SHELL_ALLOW` and `SHELL_DENY`, comma-separated program names
+    pub fn from_env() -> Self {
+        let list = |name: &str| {
+            env::var(name).ok().map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>()
+            })
+        };
+        Self {
+            // An empty SHELL_ALLOW is the same as leaving it unset
+            allowed: list("SHELL_ALLOW").filter(|allowed| !allowed.is_empty()),
+            denied: list("SHELL_DENY").unwrap_or_default(),
+        }
+    }
+
+    fn allowed_programs(&self) -> Option<&[String]> {
+        self.allowed
+            .as_deref()
+            .filter(|allowed| !allowed.is_empty())
+    }
+
+    fn check(&self, command: &str, env: &HashMap<String, String>) -> Result<(), McpError> {
+        // Without a policy commands aren't parsed at all
+        if self.allowed_programs().is_none() && self.denied.is_empty() {
+            return Ok(());
+        }
+        if let Some(name) = env
+            .keys()
+            .find(|name| UNCHECKED_ENV.contains(&name.as_str()))
+        {
+            return Err(unchecked(&format!("the environment variable {name}")));
+        }
+        self.check_command_line(command, 0)
+    }
+
+    fn check_command_line(&self, command: &str, depth: usize) -> Result<(), McpError> {
+        if depth > MAX_POLICY_NESTING {
+            return Err(unchecked("deeply nested shells"));
+        }
+        for words in split_commands(command)? {
+            self.check_simple_command(&words, depth)?;
+        }
+        Ok(())
+    }
+
+    // Check the program of one simple command and whatever it runs in turn
+    fn check_simple_command(&self, words: &[String], depth: usize) -> Result<(), McpError> {
+        let mut rest = words;
+        loop {
+            rest = skip_command_prefix(rest)?;
+            let Some((first, args)) = rest.split_first() else {
+                return Ok(());
+            };
+            let program = program_name(first)?;
+            self.check_denied(&program)?;
+
+            if let Some((_, value_options)) = COMMAND_WRAPPERS
+                .iter()
+                .find(|(wrapper, _)| *wrapper == program)
+            {
+                if program == "env"
+                    && args
+                        .iter()
+                        .any(|arg| arg.starts_with("-S") || arg.starts_with("--split-string"))
+                {
+                    return Err(unchecked("env -S"));
+                }
+                rest = skip_options(args, value_options);
+                if program == "timeout" {
+                    // The duration comes before the command
+                    rest = rest.get(1..).unwrap_or_default();
+                }
+                if rest.is_empty() && (program == "sudo" || program == "doas") {
+                    return Err(unchecked(&format!("a shell opened by {program}")));
+                }
+                continue;
+            }
+
+            if SHELLS.contains(&program.as_str()) {
+                return match shell_command_argument(args) {
+                    Some(script) => self.check_command_line(script, depth + 1),
+                    // Commands read from stdin or a script file can't be seen
+                    None => self.check_listed_shell(&program, first),
+                };
+            }
+            match program.as_str() {
+                "eval" => return self.check_command_line(&args.join(" "), depth + 1),
+                "source" | "." => return self.check_listed_shell(&program, first),
+                "find" => self.check_find_commands(args, depth)?,
+                _ => {}
+            }
+            if DECLARATION_BUILTINS.contains(&program.as_str()) {
+                check_assignments(args)?;
+            }
+            return self.check_allowed(&program, first);
+        }
+    }
+
+    // Each `find -exec`, `-execdir`, `-ok` and `-okdir` runs the command up to `;` or `+`
+    fn check_find_commands(&self, args: &[String], depth: usize) -> Result<(), McpError> {
+        let mut rest = args;
+        while let Some(start) = rest
+            .iter()
+            .position(|arg| matches!(arg.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir"))
+        {
+            let command = &rest[start + 1..];
+            let end = command
+                .iter()
+                .position(|arg| arg == ";" || arg == "+")
+                .unwrap_or(command.len());
+            self.check_simple_command(&command[..end], depth + 1)?;
+            rest = &command[end..];
+        }
+        Ok(())
+    }
+
+    fn check_denied(&self, program: &str) -> Result<(), McpError> {
+        if self.denied.iter().any(|item| item == program) {
+            return Err(McpError::invalid_request(
+                format!("The command '{program}' is denied by the shell policy (SHELL_DENY)"),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    // `/bin/rm` is still `rm`, but an allowlist may also name the full path
+    fn check_allowed(&self, program: &str, word: &str) -> Result<(), McpError> {
+        let Some(allowed) = self.allowed_programs() else {
+            return Ok(());
+        };
+        if allowed.iter().any(|item| item == program || item == word) {
+            return Ok(());
+        }
+        Err(McpError::invalid_request(
+            format!(
+                "The command '{program}' is not allowed by the shell policy (SHELL_ALLOW). Allowed commands are: {}",
+                allowed.join(", ")
+            ),
+            None,
+        ))
+    }
+
+    // A shell reading commands from stdin or a script may only run when explicitly allowed
+    fn check_listed_shell(&self, program: &str, word: &str) -> Result<(), McpError> {
+        let listed = self
+            .allowed_programs()
+            .is_some_and(|allowed| allowed.iter().any(|item| item == program || item == word));
+        if !listed {
+            return Err(unchecked(&format!(
+                "'{program}' reading commands from stdin or a script"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn unchecked(what: &str) -> McpError {
+    McpError::invalid_request(
+        format!("The command uses {what}, which the shell policy can't check"),
+        None,
+    )
+}
+
+// The words of each simple command in a command line, with quotes removed. Commands are
+// separated by `;`, `&`, `|`, newlines and parentheses outside quotes
+fn split_commands(command: &str) -> Result<Vec<Vec<String>>, McpError> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let (mut single, mut double) = (false, false);
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        if single {
+            if c == '\'' {
+                single = false;
+            } else {
+                word.get_or_insert_default().push(c);
+            }
+            continue;
+        }
+        // Redirections such as `2>&1`, `&>` and `>|` aren't separators
+        let in_redirection = word
+            .as_ref()
+            .is_some_and(|word| !double && (word.ends_with('>') || word.ends_with('<')));
+        match c {
+            '\\' => {
+                // A backslash before a newline continues the line
+                if let Some(next) = chars.next().filter(|next| *next != '\n') {
+                    word.get_or_insert_default().push(next);
+                }
+            }
+            '`' => return Err(unchecked("command substitution")),
+            '$' if chars.peek() == Some(&'(') => return Err(unchecked("command substitution")),
+            '"' => {
+                double = !double;
+                word.get_or_insert_default();
+            }
+            _ if double => word.get_or_insert_default().push(c),
+            '\'' => {
+                single = true;
+                word.get_or_insert_default();
+            }
+            '<' | '>' if chars.peek() == Some(&'(') => {
+                return Err(unchecked("process substitution"));
+            }
+            '#' if word.is_none() => while chars.next_if(|next| *next != '\n').is_some() {},
+            '&' | '|' if in_redirection => word.get_or_insert_default().push(c),
+            '&' if chars.peek() == Some(&'>') => word.get_or_insert_default().push(c),
+            ';' | '&' | '|' | '\n' | '(' | ')' => {
+                words.extend(word.take());
+                if !words.is_empty() {
+                    commands.push(std::mem::take(&mut words));
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    if !words.is_empty() {
+        commands.push(words);
+    }
+    Ok(commands)
+}
+
+// Drop the reserved words, variable assignments and redirections before a command's program
+fn skip_command_prefix(mut words: &[String]) -> Result<&[String], McpError> {
+    while let Some((first, rest)) = words.split_first() {
+        if RESERVED_WORDS.contains(&first.as_str()) {
+            words = rest;
+        } else if assignment_name(first).is_some() {
+            check_assignments(std::slice::from_ref(first))?;
+            words = rest;
+        } else if let Some(operand_follows) = redirection(first) {
+            words = if operand_follows {
+                rest.get(1..).unwrap_or_default()
+            } else {
+                rest
+            };
+        } else {
+            break;
+        }
+    }
+    Ok(words)
+}
+
+// Skip a wrapper's options, and the values of those that take one, up to its command
+fn skip_options<'a>(mut args: &'a [String], value_options: &[&str]) -> &'a [String] {
+    while let Some((first, rest)) = args.split_first() {
+        if first == "--" {
+            return rest;
+        }
+        if assignment_name(first).is_some() {
+            // `env NAME=value cmd`, the assignment is checked with the command's prefix
+            break;
+        }
+        if !first.starts_with('-') || first.len() == 1 {
+            break;
+        }
+        args = if value_options.contains(&first.as_str()) {
+            rest.get(1..).unwrap_or_default()
+        } else {
+            rest
+        };
+    }
+    args
+}
+
+// The command line a shell runs with `-c`, the first operand after the options
+fn shell_command_argument(args: &[String]) -> Option<&str> {
+    let mut has_command = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if matches!(arg.as_str(), "-o" | "+o" | "-O" | "+O") {
+            // Option names such as `-o pipefail`
+            args.next();
+        } else if arg.starts_with("--") {
+            continue;
+        } else if (arg.starts_with('-') || arg.starts_with('+')) && arg.len() > 1 {
+            has_command |= arg.contains('c');
+        } else {
+            return has_command.then_some(arg.as_str());
+        }
+    }
+    None
+}
+
+// Reject assignments to the variables that run unchecked code
+fn check_assignments(words: &[String]) -> Result<(), McpError> {
+    match words
+        .iter()
+        .filter_map(|word| assignment_name(word))
+        .find(|name| UNCHECKED_ENV.contains(name))
+    {
+        Some(name) => Err(unchecked(&format!("the environment variable {name}"))),
+        None => Ok(()),
+    }
+}
+
+// The variable a `NAME=value` word assigns
+fn assignment_name(word: &str) -> Option<&str> {
+    let (name, _) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(name)
+}
+
+// Whether a word is a redirection, and if so whether its target is the next word
+fn redirection(word: &str) -> Option<bool> {
+    let operator = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    if !(operator.starts_with(['<', '>']) || operator.starts_with("&>")) {
+        return None;
+    }
+    Some(operator.trim_start_matches(['<', '>', '&', '|']).is_empty())
+}
+
+// The name a command's first word runs, rejecting names only known once the shell expands them
+fn program_name(word: &str) -> Result<String, McpError> {
+    if word.is_empty() || word.contains(['$', '*', '?', '[']) {
+        return Err(unchecked(&format!("the program name '{word}'")));
+    }
+    Ok(Path::new(word)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| word.to_string()))
+}
+
+/// What to do with output longer than the character limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputOverflow {
+    /// Keep the end of the output, where errors usually are, and note what was dropped
+    #[default]
+    Truncate,
+    /// Fail the call without returning any output
+    Error,
+}
+
+impl std::str::FromStr for OutputOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "error" => Ok(Self::Error),
+            other => Err(format!("Unknown output overflow behavior '{other}'")),
+        }
+    }
+}
+
+// Most characters of output returned from a command
+const MAX_OUTPUT_CHARS: usize = 400_000; // 400KB
+
+/// How long a command may run before it is killed
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a shell session may sit unused before it is closed
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+// Upper bound on concurrently open shell sessions
+const MAX_SESSIONS: usize = 16;
+
+// How long to keep reading a cancelled command's pipes after killing it
+const CANCEL_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+// A long-lived shell fed commands over stdin
+struct ShellSession {
+    // Killed on drop
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    last_used: Instant,
+    commands_run: u64,
+}
+
+type SessionMap = Mutex<HashMap<String, Arc<tokio::sync::Mutex<ShellSession>>>>;
+type Sessions = Arc<SessionMap>;
+
 #[derive(Clone)]
 pub struct Shell {
     // Shell configuration
     config: ShellConfig,
-    // Optional gitignore patterns for file access control
-    ignore_patterns: Option<Arc<Gitignore>>,
+    // Commands running longer than this are killed
+    timeout: Duration,
+    // Strip leading/trailing blank lines and collapse runs of them in output
+    trim_blank_lines: bool,
+    // Programs the shell may or may not run
+    policy: CommandPolicy,
+    // Truncate or reject output over the character limit
+    output_overflow: OutputOverflow,
+    // Persistent shells by session id
+    sessions: Sessions,
+    // Sessions unused for this long are closed
+    session_idle_timeout: Duration,
+    // Gitignore patterns for file access control, shared with the other tools
+    ignore_patterns: IgnorePatterns,
 }
 
 impl Default for Shell {
@@ -56,14 +718,60 @@ impl Default for Shell {
 
 impl Shell {
     pub fn new() -> Self {
+        Self::new_with_config(ShellConfig::default())
+    }
+
+    pub fn new_with_config(config: ShellConfig) -> Self {
+        if !executable_exists(&config.executable) {
+            tracing::warn!(
+                executable = %config.executable,
+                "Shell executable not found, commands will fail to spawn. Set SHELL_EXECUTABLE to a shell that exists."
+            );
+        }
         Self {
-            config: ShellConfig::default(),
-            ignore_patterns: None,
+            config,
+            timeout: DEFAULT_TIMEOUT,
+            trim_blank_lines: true,
+            policy: CommandPolicy::from_env(),
+            output_overflow: OutputOverflow::default(),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            ignore_patterns: IgnorePatterns::new(),
         }
     }
 
-    pub fn with_ignore_patterns(mut self, ignore_patterns: Arc<Gitignore>) -> Self {
-        self.ignore_patterns = Some(ignore_patterns);
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long a command may run before it is killed
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn with_trim_blank_lines(mut self, trim_blank_lines: bool) -> Self {
+        self.trim_blank_lines = trim_blank_lines;
+        self
+    }
+
+    pub fn with_session_idle_timeout(mut self, session_idle_timeout: Duration) -> Self {
+        self.session_idle_timeout = session_idle_timeout;
+        self
+    }
+
+    pub fn with_output_overflow(mut self, output_overflow: OutputOverflow) -> Self {
+        self.output_overflow = output_overflow;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CommandPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
         self
     }
 
@@ -81,59 +789,89 @@ impl Shell {
         }
     }
 
-    fn check_ignore_patterns(&self, command: &str) -> Result<(), McpError> {
-        if let Some(ignore_patterns) = &self.ignore_patterns {
-            // Check if command might access ignored files and return early if it does
-            let cmd_parts: Vec<&str> = command.split_whitespace().collect();
-            for arg in &cmd_parts[1..] {
-                // Skip command flags
-                if arg.starts_with('-') {
-                    continue;
-                }
-                // Skip invalid paths
-                let path = Path::new(arg);
-                if !path.exists() {
-                    continue;
-                }
+    fn check_ignore_patterns(&self, command: &str, cwd: Option<&Path>) -> Result<(), McpError> {
+        // Check if command might access ignored files and return early if it does
+        for arg in command.split_whitespace().skip(1) {
+            // Skip command flags
+            if arg.starts_with('-') {
+                continue;
+            }
+            // Ignore and allow files can't be named at all, whether or not they exist, since the
+            // command might create or change one. Redirections such as `>>.gitignore` and
+            // quotes are looked through
+            let named = arg
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start_matches(['>', '<', '&', '|'])
+                .trim_matches(['\'', '"']);
+            if is_access_control_file(Path::new(named)) {
+                return Err(McpError::invalid_request(
+                    format!(
+                        "The command names '{arg}', an ignore or allow file. Commands can't tell reading it from changing it, view it with text_editor instead"
+                    ),
+                    None,
+                ));
+            }
+            // Skip invalid paths, relative ones are resolved where the command runs
+            let path = match cwd {
+                Some(cwd) => cwd.join(arg),
+                None => PathBuf::from(arg),
+            };
+            if !path.exists() {
+                continue;
+            }
 
-                if ignore_patterns.matched(path, false).is_ignore() {
-                    return Err(McpError::invalid_request(
-                        format!(
-                            "The command attempts to access '{arg}' which is restricted by ignore patterns"
-                        ),
-                        None,
-                    ));
-                }
+            if self.ignore_patterns.is_ignored(&path, false) {
+                return Err(McpError::invalid_request(
+                    format!(
+                        "The command attempts to access '{arg}' which is restricted by ignore patterns"
+                    ),
+                    None,
+                ));
             }
         }
         Ok(())
     }
 
     pub async fn execute(&self, command: String) -> Result<CallToolResult, McpError> {
-        // Check ignore patterns if configured
-        self.check_ignore_patterns(&command)?;
+        self.execute_with_options(command, ExecuteOptions::default())
+            .await
+    }
 
-        // Get platform-specific shell configuration
-        let cmd_with_redirect = self.format_command_for_platform(&command);
+    pub async fn execute_with_options(
+        &self,
+        command: String,
+        mut options: ExecuteOptions,
+    ) -> Result<CallToolResult, McpError> {
+        // JSON reports keep the streams apart
+        if options.output_format == OutputFormat::Json {
+            options.separate_streams = true;
+        }
+        self.validate(&command, &options)?;
 
-        // Execute the command using platform-specific shell
-        let child = Command::new(&self.config.executable)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .stdin(Stdio::null())
-            .kill_on_drop(true)
-            .arg(&self.config.arg)
-            .arg(cmd_with_redirect)
-            .spawn()
-            .map_err(|e| McpError::internal_error(format!("Failed to spawn command: {e}"), None))?;
+        if options.dry_run {
+            return self.dry_run(&command, &options);
+        }
 
-        // Wait for the command to complete and get output
-        let output = child.wait_with_output().await.map_err(|e| {
-            McpError::internal_error(format!("Failed to wait for command: {e}"), None)
-        })?;
+        if let Some(session_id) = &options.session_id {
+            return self
+                .execute_in_session(session_id, &command, &options)
+                .await;
+        }
+
+        let started = Instant::now();
+        let (status, stdout_bytes, stderr_bytes) = self.spawn(&command, &options).await?;
+        let duration = started.elapsed();
+        let stdout_str = String::from_utf8_lossy(&stdout_bytes);
+        let stderr_str = String::from_utf8_lossy(&stderr_bytes);
 
-        let stdout_str = String::from_utf8_lossy(&output.stdout);
-        let stderr_str = String::from_utf8_lossy(&output.stderr);
+        if status.termination == Termination::Cancelled {
+            let output = CommandOutput {
+                stdout: stdout_str.into_owned(),
+                stderr: stderr_str.into_owned(),
+                status,
+            };
+            return self.cancelled_result(command, output, duration, &options, None);
+        }
 
         // Combine stdout and stderr as they would appear in terminal
         let combined_output = if stderr_str.is_empty() {
@@ -144,62 +882,692 @@ impl Shell {
             format!("{stdout_str}{stderr_str}")
         };
 
-        let normalized_output = normalize_line_endings(&combined_output);
+        let exit_code = status.exit_code;
+        let normalized_output = self.fit_output(
+            &command,
+            self.tidy_output(&combined_output, &options),
+            exit_code,
+        )?;
 
-        // Check the character count of the output
-        const MAX_CHAR_COUNT: usize = 400_000; // 400KB
-        let char_count = normalized_output.chars().count();
-        if char_count > MAX_CHAR_COUNT {
-            return Err(McpError::invalid_params(
-                format!(
-                    "Shell output from command '{command}' has too many characters ({char_count}). Maximum character count is {MAX_CHAR_COUNT}."
-                ),
-                None,
-            ));
+        if options.separate_streams {
+            let report = CommandOutput {
+                stdout: self.fit_output(
+                    &command,
+                    self.tidy_output(&stdout_str, &options),
+                    exit_code,
+                )?,
+                stderr: self.fit_output(
+                    &command,
+                    self.tidy_output(&stderr_str, &options),
+                    exit_code,
+                )?,
+                status,
+            };
+            if options.output_format == OutputFormat::Json {
+                let report = CommandReport {
+                    command,
+                    output: report,
+                    duration_ms: duration.as_millis(),
+                };
+                return Ok(CallToolResult::success(vec![Content::json(report)?]));
+            }
+            return Ok(CallToolResult::success(vec![
+                Content::json(report)?.with_audience(vec![Role::Assistant]),
+                Content::text(normalized_output)
+                    .with_audience(vec![Role::User])
+                    .with_priority(0.0),
+            ]));
         }
 
-        Ok(CallToolResult::success(vec![
-            Content::text(normalized_output.clone()).with_audience(vec![Role::Assistant]),
-            Content::text(normalized_output)
+        Self::output_result(normalized_output, status)
+    }
+
+    // Report a command killed on cancellation as an error result with its partial output,
+    // which is always truncated to fit since the command can't be run again to shorten it
+    fn cancelled_result(
+        &self,
+        command: String,
+        output: CommandOutput,
+        duration: Duration,
+        options: &ExecuteOptions,
+        closed_session: Option<&str>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut summary = format!(
+            "Command '{command}' was cancelled and killed after {} ms.",
+            duration.as_millis()
+        );
+        if let Some(session_id) = closed_session {
+            summary.push_str(&format!(" Session '{session_id}' was closed."));
+        }
+        let fit = |output: &str| truncate_output(self.tidy_output(output, options), None);
+        if options.output_format == OutputFormat::Json {
+            let report = CommandReport {
+                output: CommandOutput {
+                    stdout: fit(&output.stdout),
+                    stderr: fit(&output.stderr),
+                    status: output.status,
+                },
+                command,
+                duration_ms: duration.as_millis(),
+            };
+            return Ok(CallToolResult::error(vec![Content::json(report)?]));
+        }
+
+        let captured = format!("{}{}", output.stdout, output.stderr);
+        let text = if captured.trim().is_empty() {
+            format!("{summary} No output was captured before cancellation.")
+        } else {
+            format!(
+                "{summary} Output captured before cancellation:\n{}",
+                fit(&captured)
+            )
+        };
+        Ok(CallToolResult::error(vec![
+            Content::text(text).with_audience(vec![Role::Assistant]),
+            Content::json(output.status)?.with_audience(vec![Role::Assistant]),
+            Content::text(summary)
                 .with_audience(vec![Role::User])
                 .with_priority(0.0),
         ]))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ignore::gitignore::GitignoreBuilder;
-    use serial_test::serial;
-    use tempfile;
-
-    #[tokio::test]
-    #[serial]
-    async fn test_shell_basic_execution() {
-        let shell = Shell::new();
 
-        let result = shell.execute("echo hello".to_string()).await;
+    /// Run a command and return its raw, untrimmed output, for tools that parse it
+    pub async fn output(
+        &self,
+        command: &str,
+        options: ExecuteOptions,
+    ) -> Result<CommandOutput, McpError> {
+        let options = ExecuteOptions {
+            separate_streams: true,
+            ..options
+        };
+        self.validate(command, &options)?;
+        let (status, stdout_bytes, stderr_bytes) = self.spawn(command, &options).await?;
+        if status.termination == Termination::Cancelled {
+            return Err(cancelled_error());
+        }
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout_bytes).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr_bytes).into_owned(),
+            status,
+        })
+    }
 
-        assert!(result.is_ok());
+    // Report the command line `spawn` would build once validation passed
+    fn dry_run(&self, command: &str, options: &ExecuteOptions) -> Result<CallToolResult, McpError> {
+        let command = if options.separate_streams {
+            command.to_string()
+        } else {
+            self.format_command_for_platform(command)
+        };
+        let mut env: Vec<String> = options.env.keys().cloned().collect();
+        env.sort();
+        let report = DryRun {
+            command,
+            shell: self.config.executable.clone(),
+            shell_arg: self.config.arg.clone(),
+            cwd: options.cwd.clone(),
+            session_id: options.session_id.clone(),
+            env,
+        };
+        let summary = format!(
+            "Would run: {} {} {}",
+            report.shell,
+            report.shell_arg,
+            shell_quote(&report.command)
+        );
+        Ok(CallToolResult::success(vec![
+            Content::json(report)?.with_audience(vec![Role::Assistant]),
+            Content::text(summary)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_shell_with_ignore_patterns() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
+    // Reject commands the policy, ignore patterns or options don't allow
+    fn validate(&self, command: &str, options: &ExecuteOptions) -> Result<(), McpError> {
+        if let Some(cwd) = options.cwd.as_ref().filter(|cwd| !cwd.is_dir()) {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The working directory '{display}' does not exist or is not a directory",
+                    display = cwd.display()
+                ),
+                None,
+            ));
+        }
 
-        // Create ignore patterns
-        let mut builder = GitignoreBuilder::new(temp_dir.path());
-        builder.add_line(None, "secret.txt").unwrap();
-        let ignore_patterns = Arc::new(builder.build().unwrap());
+        // Check the command policy and ignore patterns if configured
+        self.policy.check(command, &options.env)?;
+        self.check_ignore_patterns(command, options.cwd.as_deref())?;
 
-        let shell = Shell::new().with_ignore_patterns(ignore_patterns);
+        if options.output_format == OutputFormat::Json && options.session_id.is_some() {
+            return Err(McpError::invalid_params(
+                "output_format 'json' can't be combined with session_id, session output is always merged",
+                None,
+            ));
+        }
 
-        // Create an ignored file
-        let secret_file_path = temp_dir.path().join("secret.txt");
-        std::fs::write(&secret_file_path, "secret content").unwrap();
+        if options.stdin.is_some() && options.session_id.is_some() {
+            return Err(McpError::invalid_params(
+                "stdin can't be used with session_id, a session's stdin carries its commands",
+                None,
+            ));
+        }
+
+        if let Some(name) = options
+            .env
+            .keys()
+            .find(|name| name.is_empty() || name.contains(['=', '\0']))
+        {
+            return Err(McpError::invalid_params(
+                format!("Invalid environment variable name '{name}'"),
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Spawn a one-off command and collect its status, stdout and stderr. A cancelled
+    // command is killed and reported with whatever output it wrote until then
+    async fn spawn(
+        &self,
+        command: &str,
+        options: &ExecuteOptions,
+    ) -> Result<(CommandStatus, Vec<u8>, Vec<u8>), McpError> {
+        // Get platform-specific shell configuration, merging stderr into stdout unless
+        // the streams are reported separately
+        let cmd_with_redirect = if options.separate_streams {
+            command.to_string()
+        } else {
+            self.format_command_for_platform(command)
+        };
+
+        // Execute the command using platform-specific shell
+        let mut child = Command::new(&self.config.executable);
+        if let Some(cwd) = &options.cwd {
+            child.current_dir(cwd);
+        }
+        let mut child = child
+            .envs(&options.env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(if options.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .kill_on_drop(true)
+            .arg(&self.config.arg)
+            .arg(cmd_with_redirect)
+            .spawn()
+            .map_err(|e| McpError::internal_error(format!("Failed to spawn command: {e}"), None))?;
+
+        // Read output as it arrives so whatever was captured survives a timeout
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let mut stdout_bytes = Vec::new();
+        let mut stderr_bytes = Vec::new();
+        // Written alongside reading the output, so a command that fills its stdout pipe
+        // before consuming all of its input can't deadlock
+        let stdin = child.stdin.take();
+        let write_stdin = async {
+            if let (Some(mut stdin), Some(input)) = (stdin, options.stdin.as_deref()) {
+                // A command that exits without reading its input closes the pipe early
+                let _ = stdin.write_all(input.as_bytes()).await;
+            }
+            // Dropping the pipe closes stdin so the command sees end of input
+        };
+        let completed = tokio::select! {
+            completed = tokio::time::timeout(self.timeout, async {
+                let (stdout_read, stderr_read, status, ()) = tokio::join!(
+                    read_output(&mut stdout, &mut stdout_bytes, options.progress.as_ref()),
+                    read_output(&mut stderr, &mut stderr_bytes, options.progress.as_ref()),
+                    child.wait(),
+                    write_stdin
+                );
+                stdout_read.and(stderr_read).and(status)
+            }) => Some(completed),
+            _ = cancelled(options.cancel.as_ref()) => None,
+        };
+        let Some(completed) = completed else {
+            let _ = child.kill().await;
+            // Collect what was still in the pipes, bounded since a background process
+            // the command started may hold them open
+            let _ = tokio::time::timeout(CANCEL_DRAIN_TIMEOUT, async {
+                tokio::join!(
+                    read_output(&mut stdout, &mut stdout_bytes, None),
+                    read_output(&mut stderr, &mut stderr_bytes, None),
+                )
+            })
+            .await;
+            let mut status = match child.wait().await {
+                Ok(status) => CommandStatus::of(&status),
+                Err(_) => CommandStatus::default(),
+            };
+            status.termination = Termination::Cancelled;
+            return Ok((status, stdout_bytes, stderr_bytes));
+        };
+
+        match completed {
+            Ok(result) => result
+                .map(|status| (CommandStatus::of(&status), stdout_bytes, stderr_bytes))
+                .map_err(|e| {
+                    McpError::internal_error(format!("Failed to wait for command: {e}"), None)
+                }),
+            Err(_) => {
+                // Don't leave the hung process behind
+                let _ = child.kill().await;
+                let partial_output = format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&stdout_bytes),
+                    String::from_utf8_lossy(&stderr_bytes)
+                );
+                let partial_output = if partial_output.trim().is_empty() {
+                    "No output was captured before the timeout.".to_string()
+                } else {
+                    format!("Output captured before the timeout:\n{partial_output}")
+                };
+                Err(McpError::invalid_request(
+                    format!(
+                        "Command '{command}' timed out after {secs} seconds and was killed. {partial_output}",
+                        secs = self.timeout.as_secs()
+                    ),
+                    None,
+                ))
+            }
+        }
+    }
+
+    // Run a command in a persistent shell, starting the session on first use
+    async fn execute_in_session(
+        &self,
+        session_id: &str,
+        command: &str,
+        options: &ExecuteOptions,
+    ) -> Result<CallToolResult, McpError> {
+        if cfg!(windows) {
+            return Err(McpError::invalid_params(
+                "Shell sessions are not supported on Windows",
+                None,
+            ));
+        }
+        if options.separate_streams {
+            return Err(McpError::invalid_params(
+                "separate_streams can't be combined with session_id, session output is always merged",
+                None,
+            ));
+        }
+        if !options.env.is_empty() {
+            return Err(McpError::invalid_params(
+                "env can't be combined with session_id, export variables in the session instead",
+                None,
+            ));
+        }
+
+        let session = self.session(session_id)?;
+        let mut session = session.lock().await;
+        session.commands_run += 1;
+
+        // The marker line tells where this command's output ends and carries its exit code
+        let marker = format!(
+            "__DEVELOPER_SESSION_DONE_{}_{}__",
+            std::process::id(),
+            session.commands_run
+        );
+        let mut script = String::new();
+        if let Some(cwd) = &options.cwd {
+            script.push_str(&format!("cd {}\n", shell_quote(&cwd.to_string_lossy())));
+        }
+        // stdin is the command channel, so commands must not read from it
+        script.push_str(&format!(
+            "{{\n{command}\n}} </dev/null 2>&1\nprintf '\\n{marker} %d\\n' \"$?\"\n"
+        ));
+        session
+            .stdin
+            .write_all(script.as_bytes())
+            .await
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to send command to session: {e}"), None)
+            })?;
+
+        let started = Instant::now();
+        let mut output = Vec::new();
+        let completed = tokio::select! {
+            completed = tokio::time::timeout(self.timeout, async {
+                let mut line = Vec::new();
+                loop {
+                    line.clear();
+                    if session.stdout.read_until(b'\n', &mut line).await? == 0 {
+                        // The shell exited, e.g. the command ran `exit`
+                        return Ok::<_, std::io::Error>(None);
+                    }
+                    let text = String::from_utf8_lossy(&line);
+                    if let Some(code) = text.trim_end().strip_prefix(&marker) {
+                        return Ok(Some(code.trim().parse::<i32>().unwrap_or_default()));
+                    }
+                    output.extend_from_slice(&line);
+                    if let Some(progress) = &options.progress {
+                        let _ = progress.send(text.trim_end().to_string());
+                    }
+                }
+            }) => Some(completed),
+            _ = cancelled(options.cancel.as_ref()) => None,
+        };
+        let Some(completed) = completed else {
+            // Like a timeout, this leaves the session in an unknown state, so discard it
+            drop(session);
+            self.sessions.lock().unwrap().remove(session_id);
+            let output = CommandOutput {
+                stdout: String::from_utf8_lossy(&output).into_owned(),
+                stderr: String::new(),
+                status: CommandStatus {
+                    termination: Termination::Cancelled,
+                    ..Default::default()
+                },
+            };
+            return self.cancelled_result(
+                command.to_string(),
+                output,
+                started.elapsed(),
+                options,
+                Some(session_id),
+            );
+        };
+
+        let (ended, exit_code) = match completed {
+            Ok(Ok(Some(exit_code))) => {
+                session.last_used = Instant::now();
+                // Drop the newline printed ahead of the marker
+                output.pop();
+                (false, Some(exit_code))
+            }
+            Ok(Ok(None)) => (true, None),
+            Ok(Err(e)) => {
+                drop(session);
+                self.sessions.lock().unwrap().remove(session_id);
+                return Err(McpError::internal_error(
+                    format!("Failed to read session output: {e}"),
+                    None,
+                ));
+            }
+            Err(_) => {
+                // The session's state is unknown after a hung command, so discard it
+                drop(session);
+                self.sessions.lock().unwrap().remove(session_id);
+                return Err(McpError::invalid_request(
+                    format!(
+                        "Command '{command}' timed out after {secs} seconds. Session '{session_id}' was closed. Output captured before the timeout:\n{output}",
+                        secs = self.timeout.as_secs(),
+                        output = String::from_utf8_lossy(&output)
+                    ),
+                    None,
+                ));
+            }
+        };
+        if ended {
+            drop(session);
+            self.sessions.lock().unwrap().remove(session_id);
+        }
+
+        let mut normalized_output = self.fit_output(
+            command,
+            self.tidy_output(&String::from_utf8_lossy(&output), options),
+            exit_code,
+        )?;
+        if ended {
+            normalized_output.push_str(&format!("\n[session '{session_id}' ended]"));
+        }
+        Self::output_result(
+            normalized_output,
+            CommandStatus {
+                exit_code,
+                ..Default::default()
+            },
+        )
+    }
+
+    // The named session, started if it doesn't exist yet
+    fn session(&self, session_id: &str) -> Result<Arc<tokio::sync::Mutex<ShellSession>>, McpError> {
+        self.close_idle_sessions();
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(session_id) {
+            return Ok(session.clone());
+        }
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(McpError::invalid_request(
+                format!(
+                    "Too many open shell sessions ({MAX_SESSIONS}). Sessions close after {secs} seconds without use.",
+                    secs = self.session_idle_timeout.as_secs()
+                ),
+                None,
+            ));
+        }
+
+        let mut child = Command::new(&self.config.executable)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                McpError::internal_error(format!("Failed to start shell session: {e}"), None)
+            })?;
+        let session = ShellSession {
+            stdin: child.stdin.take().expect("stdin is piped"),
+            stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+            _child: child,
+            last_used: Instant::now(),
+            commands_run: 0,
+        };
+        let session = Arc::new(tokio::sync::Mutex::new(session));
+
+        // The first session starts a reaper that lives as long as the shell does
+        if sessions.is_empty() {
+            Self::spawn_session_reaper(Arc::downgrade(&self.sessions), self.session_idle_timeout);
+        }
+        sessions.insert(session_id.to_string(), session.clone());
+        Ok(session)
+    }
+
+    fn close_idle_sessions(&self) {
+        Self::retain_active_sessions(&self.sessions, self.session_idle_timeout);
+    }
+
+    // Drop sessions idle for longer than the timeout, which kills their shells.
+    // Sessions running a command are locked and always kept.
+    fn retain_active_sessions(sessions: &SessionMap, idle_timeout: Duration) {
+        sessions.lock().unwrap().retain(|_, session| {
+            session
+                .try_lock()
+                .map(|session| session.last_used.elapsed() < idle_timeout)
+                .unwrap_or(true)
+        });
+    }
+
+    fn spawn_session_reaper(sessions: Weak<SessionMap>, idle_timeout: Duration) {
+        // A zero timeout still closes sessions on their next use, without the reaper spinning
+        let interval = idle_timeout.clamp(Duration::from_secs(1), Duration::from_secs(60));
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(sessions) = sessions.upgrade() else {
+                    return;
+                };
+                Self::retain_active_sessions(&sessions, idle_timeout);
+                if sessions.lock().unwrap().is_empty() {
+                    return;
+                }
+            }
+        });
+    }
+
+    // Apply blank-line trimming and platform line endings
+    fn tidy_output(&self, output: &str, options: &ExecuteOptions) -> String {
+        let output = if options.trim_blank_lines.unwrap_or(self.trim_blank_lines) {
+            trim_blank_lines(output)
+        } else {
+            output.to_string()
+        };
+        normalize_line_endings(&output)
+    }
+
+    // Keep output within the character limit, truncating or failing as configured
+    fn fit_output(
+        &self,
+        command: &str,
+        output: String,
+        exit_code: Option<i32>,
+    ) -> Result<String, McpError> {
+        let char_count = output.chars().count();
+        if char_count <= MAX_OUTPUT_CHARS {
+            return Ok(output);
+        }
+
+        match self.output_overflow {
+            OutputOverflow::Error => Err(McpError::invalid_params(
+                format!(
+                    "Shell output from command '{command}' has too many characters ({char_count}). Maximum character count is {MAX_OUTPUT_CHARS}."
+                ),
+                None,
+            )),
+            OutputOverflow::Truncate => Ok(truncate_output(output, exit_code)),
+        }
+    }
+
+    fn output_result(output: String, status: CommandStatus) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::json(status)?.with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+}
+
+// Keep the last MAX_OUTPUT_CHARS characters of `output`, noting how many were dropped
+fn truncate_output(output: String, exit_code: Option<i32>) -> String {
+    let char_count = output.chars().count();
+    if char_count <= MAX_OUTPUT_CHARS {
+        return output;
+    }
+    let dropped = char_count - MAX_OUTPUT_CHARS;
+    let start = output
+        .char_indices()
+        .nth(dropped)
+        .map_or(output.len(), |(index, _)| index);
+    let status = match exit_code {
+        Some(code) => format!("exit code {code}"),
+        None => "no exit code".to_string(),
+    };
+    format!(
+        "[Output truncated: the first {dropped} of {char_count} characters were dropped, {status}]\n{}",
+        &output[start..]
+    )
+}
+
+/// Whether `executable` is a path to an existing file or a program found on `PATH`
+fn executable_exists(executable: &str) -> bool {
+    let path = Path::new(executable);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    let Some(search_path) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&search_path).any(|dir| {
+        let candidate = dir.join(executable);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+/// Quote a string as a single POSIX shell word
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Read a child's output to the end, forwarding each line to `progress` when streaming
+async fn read_output<R: AsyncRead + Unpin>(
+    reader: R,
+    output: &mut Vec<u8>,
+    progress: Option<&UnboundedSender<String>>,
+) -> std::io::Result<()> {
+    let Some(progress) = progress else {
+        let mut reader = reader;
+        return reader.read_to_end(output).await.map(|_| ());
+    };
+
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+        output.extend_from_slice(&line);
+        // A closed receiver only means nobody is listening anymore
+        let _ = progress.send(String::from_utf8_lossy(&line).trim_end().to_string());
+    }
+}
+
+/// Drop leading and trailing blank lines and collapse runs of blank lines into one,
+/// keeping indentation and all other lines intact
+fn trim_blank_lines(output: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut previous_blank = true;
+    for line in output.lines() {
+        let blank = line.trim().is_empty();
+        if !(blank && previous_blank) {
+            lines.push(line);
+        }
+        previous_blank = blank;
+    }
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let mut trimmed = lines.join("\n");
+    if !trimmed.is_empty() && output.ends_with('\n') {
+        trimmed.push('\n');
+    }
+    trimmed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::gitignore::GitignoreBuilder;
+    use serial_test::serial;
+    use tempfile;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_basic_execution() {
+        let shell = Shell::new();
+
+        let result = shell.execute("echo hello".to_string()).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_with_ignore_patterns() {
+        let original_cwd = std::env::current_dir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        // Create ignore patterns
+        let mut builder = GitignoreBuilder::new(temp_dir.path());
+        builder.add_line(None, "secret.txt").unwrap();
+        let ignore_patterns = IgnorePatterns::from(builder.build().unwrap());
+
+        let shell = Shell::new().with_ignore_patterns(ignore_patterns);
+
+        // Create an ignored file
+        let secret_file_path = temp_dir.path().join("secret.txt");
+        std::fs::write(&secret_file_path, "secret content").unwrap();
 
         // Try to cat the ignored file
         let result = shell
@@ -207,10 +1575,12 @@ mod tests {
             .await;
         assert!(result.is_err(), "Should not be able to cat ignored file");
 
+        std::env::set_current_dir(&original_cwd).unwrap();
         temp_dir.close().unwrap();
     }
 
     #[test]
+    #[serial]
     fn test_shell_config_creation() {
         let shell = Shell::new();
         let config = shell.get_shell_config();
@@ -225,4 +1595,666 @@ mod tests {
             assert_eq!(config.arg, "-c");
         }
     }
+
+    #[test]
+    fn test_trim_blank_lines() {
+        assert_eq!(
+            trim_blank_lines("\n\n  \nfirst\n\n\n\n  indented\n\nlast\n\n\n"),
+            "first\n\n  indented\n\nlast\n"
+        );
+        assert_eq!(trim_blank_lines("exact"), "exact");
+        assert_eq!(trim_blank_lines("\n\n"), "");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_blank_line_trimming_can_be_disabled() {
+        let command = if cfg!(windows) {
+            "Write-Output ''; Write-Output 'hello'; Write-Output ''".to_string()
+        } else {
+            "printf '\\n\\nhello\\n\\n'".to_string()
+        };
+
+        let shell = Shell::new();
+        let result = shell.execute(command.clone()).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("hello"));
+
+        let result = shell
+            .execute_with_options(
+                command,
+                ExecuteOptions {
+                    trim_blank_lines: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.starts_with("hello"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_timeout() {
+        let command = if cfg!(windows) {
+            "Write-Output 'started'; Start-Sleep -Seconds 5".to_string()
+        } else {
+            "echo started; sleep 5".to_string()
+        };
+
+        let shell = Shell::new().with_timeout(Duration::from_secs(1));
+        let start = std::time::Instant::now();
+        let result = shell.execute(command).await;
+        assert!(start.elapsed() < Duration::from_secs(4));
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("timed out after 1 seconds"));
+        assert!(error.contains("started"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_cancellation() {
+        let command = if cfg!(windows) {
+            "Write-Output started; Start-Sleep -Seconds 10".to_string()
+        } else {
+            "echo started; sleep 10".to_string()
+        };
+
+        let shell = Shell::new();
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            trigger.cancel();
+        });
+        let options = ExecuteOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let result = shell.execute_with_options(command, options).await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(result.is_error, Some(true));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("was cancelled and killed"));
+        assert!(text.contains("started"));
+
+        // The status carries the termination, the output is only in the text
+        let status: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(status["termination"], "cancelled");
+        assert!(status.get("stdout").is_none());
+
+        // Output past the limit is truncated rather than failing the report
+        let shell = Shell::new().with_output_overflow(OutputOverflow::Error);
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            trigger.cancel();
+        });
+        let command = format!(
+            "{}; sleep 10",
+            if cfg!(windows) {
+                format!("Write-Output ('x' * {})", MAX_OUTPUT_CHARS + 10)
+            } else {
+                format!("head -c {} /dev/zero | tr '\\0' x", MAX_OUTPUT_CHARS + 10)
+            }
+        );
+        let options = ExecuteOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let result = shell.execute_with_options(command, options).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("[Output truncated: the first 10 of"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_cwd() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let expected = std::fs::canonicalize(temp_dir.path()).unwrap();
+        let command = if cfg!(windows) {
+            "(Get-Location).Path".to_string()
+        } else {
+            "pwd -P".to_string()
+        };
+
+        let shell = Shell::new();
+        let result = shell
+            .execute_with_options(
+                command.clone(),
+                ExecuteOptions {
+                    cwd: Some(temp_dir.path().to_path_buf()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(std::fs::canonicalize(text.trim()).unwrap(), expected);
+
+        let result = shell
+            .execute_with_options(
+                command,
+                ExecuteOptions {
+                    cwd: Some(temp_dir.path().join("missing")),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_streams_progress() {
+        let command = if cfg!(windows) {
+            "Write-Output 'one'; Write-Output 'two'".to_string()
+        } else {
+            "echo one; echo two".to_string()
+        };
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let result = Shell::new()
+            .execute_with_options(
+                command,
+                ExecuteOptions {
+                    progress: Some(sender),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // The full output is still returned at the end
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("one") && text.contains("two"));
+
+        let mut lines = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            lines.push(line);
+        }
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_separate_streams() {
+        let command = if cfg!(windows) {
+            "Write-Output 'out'; [Console]::Error.WriteLine('err'); exit 3".to_string()
+        } else {
+            "echo out; echo err >&2; exit 3".to_string()
+        };
+
+        let result = Shell::new()
+            .execute_with_options(
+                command,
+                ExecuteOptions {
+                    separate_streams: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(report["stdout"].as_str().unwrap().trim(), "out");
+        assert_eq!(report["stderr"].as_str().unwrap().trim(), "err");
+        assert_eq!(report["exit_code"], 3);
+
+        // The user still sees both streams together
+        let combined = &result.content[1].as_text().unwrap().text;
+        assert!(combined.contains("out") && combined.contains("err"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_json_output() {
+        let command = if cfg!(windows) {
+            "Write-Output 'out'; [Console]::Error.WriteLine('err'); exit 3".to_string()
+        } else {
+            "echo out; echo err >&2; exit 3".to_string()
+        };
+
+        let result = Shell::new()
+            .execute_with_options(
+                command.clone(),
+                ExecuteOptions {
+                    output_format: "JSON".parse().unwrap(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // A single block carries everything
+        assert_eq!(result.content.len(), 1);
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(report["command"], command);
+        assert_eq!(report["stdout"].as_str().unwrap().trim(), "out");
+        assert_eq!(report["stderr"].as_str().unwrap().trim(), "err");
+        assert_eq!(report["exit_code"], 3);
+        assert!(report["duration_ms"].is_u64());
+
+        assert!("yaml".parse::<OutputFormat>().is_err());
+        let result = Shell::new()
+            .execute_with_options(
+                "echo hi".to_string(),
+                ExecuteOptions {
+                    output_format: OutputFormat::Json,
+                    session_id: Some("json".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("session_id"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_command_policy() {
+        let denying = Shell::new().with_policy(CommandPolicy {
+            allowed: None,
+            denied: vec!["rm".to_string(), "shutdown".to_string()],
+        });
+        let result = denying
+            .execute("rm -rf /tmp/nothing-here".to_string())
+            .await;
+        assert!(result.unwrap_err().to_string().contains("denied"));
+        let result = denying
+            .execute("/bin/rm -rf /tmp/nothing-here".to_string())
+            .await;
+        assert!(result.unwrap_err().to_string().contains("denied"));
+        assert!(denying.execute("echo hello".to_string()).await.is_ok());
+
+        let allowing = Shell::new().with_policy(CommandPolicy {
+            allowed: Some(vec!["echo".to_string()]),
+            denied: Vec::new(),
+        });
+        assert!(allowing.execute("echo hello".to_string()).await.is_ok());
+        let result = allowing.execute("ls".to_string()).await;
+        assert!(result.unwrap_err().to_string().contains("not allowed"));
+
+        // Every command of a line is checked, through wrappers and nested shells
+        let no_env = HashMap::new();
+        for command in [
+            "ls; rm -rf x",
+            "true && rm x",
+            "false || rm x",
+            "echo hi | rm x",
+            "(cd /tmp && rm x)",
+            "FOO=1 rm x",
+            "sudo rm x",
+            "sudo -u root rm x",
+            "env A=1 nice -n 5 rm x",
+            "timeout 5 rm x",
+            "bash -c 'rm x'",
+            "sh -ec \"echo ok; rm x\"",
+            "eval rm x",
+            "find . -name '*.tmp' -exec rm {} \\;",
+            "if true; then rm x; fi",
+            "echo ok\nrm x",
+        ] {
+            let error = denying.policy.check(command, &no_env).unwrap_err();
+            assert!(error.to_string().contains("denied"), "{command}: {error}");
+        }
+        for command in [
+            "echo $(rm x)",
+            "echo `rm x`",
+            "$CMD x",
+            "echo rm | sh",
+            "source ./script.sh",
+            "BASH_ENV=./evil echo hi",
+            "export LD_PRELOAD=./evil.so",
+            "sudo -s",
+            "env -S 'rm x'",
+        ] {
+            let error = denying.policy.check(command, &no_env).unwrap_err();
+            assert!(
+                error.to_string().contains("can't check"),
+                "{command}: {error}"
+            );
+        }
+        let env = HashMap::from([("BASH_ENV".to_string(), "./evil".to_string())]);
+        assert!(denying.policy.check("echo hi", &env).is_err());
+        for command in [
+            "echo 'rm x'; ls 2>&1 | grep -v rm >/dev/null",
+            "echo hi &> out.txt # rm",
+            "nohup echo hi &",
+        ] {
+            assert!(denying.policy.check(command, &no_env).is_ok(), "{command}");
+        }
+
+        let result = allowing.execute("echo hello && ls".to_string()).await;
+        assert!(result.unwrap_err().to_string().contains("not allowed"));
+        assert!(allowing.policy.check("sudo echo hi", &no_env).is_ok());
+        assert!(allowing.policy.check("bash script.sh", &no_env).is_err());
+
+        // An empty allowlist is no allowlist
+        let empty = CommandPolicy {
+            allowed: Some(Vec::new()),
+            denied: Vec::new(),
+        };
+        assert!(empty.check("ls; cat x", &no_env).is_ok());
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_stdin() {
+        let shell = Shell::new();
+        let result = shell
+            .execute_with_options(
+                "cat".to_string(),
+                ExecuteOptions {
+                    stdin: Some("piped line one\npiped line two\n".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text.trim(),
+            "piped line one\npiped line two"
+        );
+
+        // Input larger than a pipe buffer doesn't deadlock against the output
+        let input = "x".repeat(1024 * 1024);
+        let result = shell
+            .execute_with_options(
+                "cat | wc -c".to_string(),
+                ExecuteOptions {
+                    stdin: Some(input),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.content[0].as_text().unwrap().text.trim(), "1048576");
+
+        // Without input, commands reading stdin see it closed rather than hanging
+        let result = shell.execute("cat".to_string()).await.unwrap();
+        assert_eq!(result.content[0].as_text().unwrap().text.trim(), "");
+
+        let result = shell
+            .execute_with_options(
+                "cat".to_string(),
+                ExecuteOptions {
+                    stdin: Some("text".to_string()),
+                    session_id: Some("stdin".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_dry_run() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let marker = temp_dir.path().join("marker");
+        let command = if cfg!(windows) {
+            format!("New-Item -Path '{}'", marker.display())
+        } else {
+            format!("touch '{}'", marker.display())
+        };
+        let shell = Shell::new().with_policy(CommandPolicy {
+            allowed: None,
+            denied: vec!["rm".to_string()],
+        });
+
+        let result = shell
+            .execute_with_options(
+                command.clone(),
+                ExecuteOptions {
+                    cwd: Some(temp_dir.path().to_path_buf()),
+                    env: HashMap::from([("RUST_LOG".to_string(), "debug".to_string())]),
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        // Nothing was spawned, so the marker was never created
+        assert!(!marker.exists());
+
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            report["command"],
+            shell.format_command_for_platform(&command)
+        );
+        assert_eq!(report["shell"], shell.get_shell_config().executable);
+        assert_eq!(report["env"], serde_json::json!(["RUST_LOG"]));
+        assert!(
+            result.content[1]
+                .as_text()
+                .unwrap()
+                .text
+                .starts_with("Would run: ")
+        );
+
+        // The policy is still enforced
+        let result = shell
+            .execute_with_options(
+                "rm -rf /tmp/nothing-here".to_string(),
+                ExecuteOptions {
+                    dry_run: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("denied"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_sessions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let expected = std::fs::canonicalize(temp_dir.path()).unwrap();
+        let shell = Shell::new();
+        let in_session = |session_id: &str| ExecuteOptions {
+            session_id: Some(session_id.to_string()),
+            ..Default::default()
+        };
+        let output = |result: CallToolResult| {
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .trim_end()
+                .to_string()
+        };
+
+        // Variables and the working directory persist within a session
+        shell
+            .execute_with_options(
+                format!("export GREETING=hello; cd {}", expected.display()),
+                in_session("work"),
+            )
+            .await
+            .unwrap();
+        let result = shell
+            .execute_with_options("echo $GREETING; pwd -P".to_string(), in_session("work"))
+            .await
+            .unwrap();
+        assert_eq!(output(result), format!("hello\n{}", expected.display()));
+
+        // Other sessions and one-off commands don't see them
+        let result = shell
+            .execute_with_options("echo \"[$GREETING]\"".to_string(), in_session("other"))
+            .await
+            .unwrap();
+        assert_eq!(output(result), "[]");
+
+        // Commands that don't end their output with a newline still complete
+        let result = shell
+            .execute_with_options("printf partial".to_string(), in_session("work"))
+            .await
+            .unwrap();
+        assert_eq!(output(result), "partial");
+
+        // Exiting ends the session, the next command starts a fresh one
+        let result = shell
+            .execute_with_options("exit".to_string(), in_session("work"))
+            .await
+            .unwrap();
+        assert!(output(result).contains("session 'work' ended"));
+        let result = shell
+            .execute_with_options("echo \"[$GREETING]\"".to_string(), in_session("work"))
+            .await
+            .unwrap();
+        assert_eq!(output(result), "[]");
+
+        // A cancelled command closes its session and reports what it printed
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            trigger.cancel();
+        });
+        let options = ExecuteOptions {
+            cancel: Some(cancel),
+            ..in_session("work")
+        };
+        let result = shell
+            .execute_with_options("echo started; sleep 10".to_string(), options)
+            .await
+            .unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = output(result);
+        assert!(text.contains("Session 'work' was closed"));
+        assert!(text.contains("started"));
+
+        // Idle sessions are closed
+        let shell = Shell::new().with_session_idle_timeout(Duration::ZERO);
+        shell
+            .execute_with_options("export GREETING=hello".to_string(), in_session("idle"))
+            .await
+            .unwrap();
+        let result = shell
+            .execute_with_options("echo \"[$GREETING]\"".to_string(), in_session("idle"))
+            .await
+            .unwrap();
+        assert_eq!(output(result), "[]");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_env() {
+        let command = if cfg!(windows) {
+            "Write-Output $env:MY_VAR".to_string()
+        } else {
+            "echo $MY_VAR".to_string()
+        };
+
+        let shell = Shell::new();
+        let result = shell
+            .execute_with_options(
+                command.clone(),
+                ExecuteOptions {
+                    env: HashMap::from([("MY_VAR".to_string(), "hello".to_string())]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(text.trim(), "hello");
+
+        // The variable doesn't leak into later commands
+        let result = shell.execute(command).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("hello"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_shell_config_from_env() {
+        // SAFETY: serial tests don't touch the environment concurrently
+        unsafe {
+            env::set_var("SHELL_EXECUTABLE", "/bin/sh");
+            env::set_var("SHELL_ARG", "-ec");
+        }
+        let shell = Shell::new();
+        unsafe {
+            env::remove_var("SHELL_EXECUTABLE");
+            env::remove_var("SHELL_ARG");
+        }
+
+        let config = shell.get_shell_config();
+        assert_eq!(config.executable, "/bin/sh");
+        assert_eq!(config.arg, "-ec");
+
+        if !cfg!(windows) {
+            assert!(executable_exists("sh"));
+            assert!(executable_exists("/bin/sh"));
+        }
+        assert!(!executable_exists("definitely-not-a-shell-executable"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_output_overflow() {
+        let command = if cfg!(windows) {
+            "Write-Output ('a' * 500000); Write-Output 'END'".to_string()
+        } else {
+            "head -c 500000 /dev/zero | tr '\\0' a; echo; echo END".to_string()
+        };
+
+        let result = Shell::new().execute(command.clone()).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("[Output truncated: the first "));
+        assert!(text.contains("exit code 0"));
+        assert!(text.trim_end().ends_with("END"));
+        assert!(text.chars().count() < MAX_OUTPUT_CHARS + 200);
+
+        let result = Shell::new()
+            .with_output_overflow(OutputOverflow::Error)
+            .execute(command)
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("too many characters")
+        );
+
+        assert_eq!("ERROR".parse(), Ok(OutputOverflow::Error));
+        assert!("drop".parse::<OutputOverflow>().is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shell_exit_status() {
+        let shell = Shell::new();
+        let status = |result: &CallToolResult| -> serde_json::Value {
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap()
+        };
+
+        let result = shell.execute("exit 3".to_string()).await.unwrap();
+        assert_eq!(status(&result)["exit_code"], 3);
+        assert!(status(&result)["signal"].is_null());
+
+        #[cfg(unix)]
+        {
+            let result = shell.execute("kill -9 $$".to_string()).await.unwrap();
+            assert!(status(&result)["exit_code"].is_null());
+            assert_eq!(status(&result)["signal"], 9);
+        }
+    }
 }
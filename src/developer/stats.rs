@@ -0,0 +1,274 @@
+use ignore::{WalkBuilder, overrides::OverrideBuilder};
+use rmcp::{
+    Error as McpError,
+    model::CallToolResult,
+    model::{Content, Role},
+};
+use std::path::{Path, PathBuf};
+
+use crate::developer::IgnorePatterns;
+
+// Only this many per-file lines are listed, totals still cover every file
+const MAX_LISTED_FILES: usize = 200;
+// Bytes inspected for NUL characters when guarding against binary files
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// `wc`-style counts: lines are newline characters, words are whitespace-separated runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+impl Counts {
+    /// Count everything in a single pass over the text
+    pub fn of(text: &str) -> Self {
+        let mut counts = Self {
+            bytes: text.len(),
+            ..Default::default()
+        };
+        let mut in_word = false;
+        for c in text.chars() {
+            counts.chars += 1;
+            if c == '\n' {
+                counts.lines += 1;
+            }
+            if c.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                counts.words += 1;
+            }
+        }
+        counts
+    }
+
+    fn add(&mut self, other: Counts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.bytes += other.bytes;
+    }
+}
+
+impl std::fmt::Display for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} lines, {} words, {} characters, {} bytes",
+            self.lines, self.words, self.chars, self.bytes
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct FileStats {
+    // Gitignore patterns for file access control, shared with the other tools
+    ignore_patterns: IgnorePatterns,
+}
+
+impl Default for FileStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileStats {
+    pub fn new() -> Self {
+        Self {
+            ignore_patterns: IgnorePatterns::new(),
+        }
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    pub async fn count(
+        &self,
+        path: String,
+        glob: Option<String>,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        self.ignore_patterns.check(&path, path.is_dir())?;
+
+        let output = match glob {
+            Some(glob) => self.count_matching(&path, &glob)?,
+            None => {
+                if !path.is_file() {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "The path '{display}' does not exist or is not a file. Pass a glob to count files under a directory.",
+                            display = path.display()
+                        ),
+                        None,
+                    ));
+                }
+                let counts = Self::count_file(&path)?.ok_or_else(|| {
+                    McpError::invalid_params(
+                        format!(
+                            "The file '{display}' appears to be binary",
+                            display = path.display()
+                        ),
+                        None,
+                    )
+                })?;
+                format!("{display}: {counts}", display = path.display())
+            }
+        };
+
+        Ok(CallToolResult::success(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    // Aggregate counts over the files under `root` matching `glob`
+    fn count_matching(&self, root: &Path, glob: &str) -> Result<String, McpError> {
+        if !root.is_dir() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' is not a directory. A glob is matched against files under a directory.",
+                    display = root.display()
+                ),
+                None,
+            ));
+        }
+
+        let mut overrides = OverrideBuilder::new(root);
+        overrides
+            .add(glob)
+            .map_err(|e| McpError::invalid_params(format!("Invalid glob '{glob}': {e}"), None))?;
+        let overrides = overrides
+            .build()
+            .map_err(|e| McpError::invalid_params(format!("Invalid glob '{glob}': {e}"), None))?;
+
+        // Walk the tree without descending into ignored directories
+        let ignore_patterns = self.ignore_patterns.clone();
+        let walker = WalkBuilder::new(root)
+            .overrides(overrides)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !ignore_patterns.is_ignored(entry.path(), is_dir)
+            })
+            .build();
+
+        let mut total = Counts::default();
+        let mut listed = Vec::new();
+        let mut file_count = 0;
+        let mut binary_count = 0;
+        for entry in walker.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            match Self::count_file(entry.path())? {
+                Some(counts) => {
+                    file_count += 1;
+                    total.add(counts);
+                    if listed.len() < MAX_LISTED_FILES {
+                        listed.push(format!("{}: {counts}", entry.path().display()));
+                    }
+                }
+                None => binary_count += 1,
+            }
+        }
+
+        if file_count == 0 {
+            return Ok(format!(
+                "No text files matching '{glob}' found in {display}",
+                display = root.display()
+            ));
+        }
+
+        let mut output = listed.join("\n");
+        if file_count > listed.len() {
+            output.push_str(&format!(
+                "\n... {} more files not listed",
+                file_count - listed.len()
+            ));
+        }
+        output.push_str(&format!("\n\nTotal ({file_count} files): {total}"));
+        if binary_count > 0 {
+            output.push_str(&format!("\nSkipped {binary_count} binary files"));
+        }
+        Ok(output)
+    }
+
+    // Counts for a text file, or None when it looks binary
+    fn count_file(path: &Path) -> Result<Option<Counts>, McpError> {
+        let bytes = std::fs::read(path).map_err(|e| {
+            McpError::internal_error(
+                format!("Failed to read file '{}': {e}", path.display()),
+                None,
+            )
+        })?;
+        if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            return Ok(None);
+        }
+        Ok(std::str::from_utf8(&bytes).ok().map(Counts::of))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result_text(result: &CallToolResult) -> String {
+        result.content[0].as_text().unwrap().text.clone()
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = Counts::of("hello world\n  héllo\tagain\n");
+        assert_eq!(
+            counts,
+            Counts {
+                lines: 2,
+                words: 4,
+                chars: 26,
+                bytes: 27,
+            }
+        );
+        assert_eq!(Counts::of(""), Counts::default());
+    }
+
+    #[tokio::test]
+    async fn test_count_file_and_glob() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        std::fs::create_dir(root.join("nested")).unwrap();
+        std::fs::write(root.join("nested/b.rs"), "fn b() {}\nfn c() {}\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "not counted\n").unwrap();
+        std::fs::write(root.join("data.rs"), [0u8, 1, 2]).unwrap();
+
+        let stats = FileStats::new();
+        let result = stats
+            .count(root.join("a.rs").to_string_lossy().to_string(), None)
+            .await
+            .unwrap();
+        assert!(result_text(&result).ends_with("1 lines, 3 words, 10 characters, 10 bytes"));
+
+        let result = stats
+            .count(root.to_string_lossy().to_string(), Some("*.rs".to_string()))
+            .await
+            .unwrap();
+        let text = result_text(&result);
+        assert!(text.contains("Total (2 files): 3 lines, 9 words, 30 characters, 30 bytes"));
+        assert!(text.contains("Skipped 1 binary files"));
+        assert!(!text.contains("notes.txt"));
+
+        let result = stats
+            .count(root.join("data.rs").to_string_lossy().to_string(), None)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("binary"));
+
+        temp_dir.close().unwrap();
+    }
+}
@@ -1,27 +1,155 @@
-use ignore::gitignore::Gitignore;
+use encoding_rs::{DecoderResult, Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use regex::Regex;
 use rmcp::{
     Error as McpError,
     model::CallToolResult,
     model::{Content, Role},
 };
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 use crate::developer::lang;
-use crate::developer::normalize_line_endings;
+use crate::developer::{IgnorePatterns, LineEnding, is_access_control_file};
 
 const DEFAULT_MAX_UNDO_HISTORY: usize = 10;
-const MAX_WRITE_CHAR_COUNT: usize = 400_000;
+const DEFAULT_MAX_FILE_BYTES: u64 = 400 * 1024; // 400KB in bytes
+const DEFAULT_MAX_CHARS: usize = 400_000;
+const MAX_SEARCH_MATCHES: usize = 200;
+// Files one view_many call may ask for
+const MAX_VIEW_MANY_FILES: usize = 50;
+// Entries one list_dir call returns, the count still covers the whole directory
+const MAX_LIST_DIR_ENTRIES: usize = 1000;
+// Lines of context shown around an edit unless the caller asks for a different amount
+const DEFAULT_SNIPPET_LINES: usize = 4;
+// Bytes read from the start of a file being replaced to keep its line endings
+const LINE_ENDING_SAMPLE_BYTES: u64 = 64 * 1024;
+// Files remembered for change detection, the least recently viewed are forgotten first
+const MAX_TRACKED_VIEWS: usize = 256;
+// Files an encoding may be chosen for, those that no longer exist are forgotten first
+const MAX_CHOSEN_ENCODINGS: usize = 256;
+
+/// Optional behaviors for `str_replace`
+#[derive(Debug, Clone, Default)]
+pub struct StrReplaceOptions {
+    /// Treat any run of whitespace in `old_str` as matching any run of whitespace in the file
+    pub whitespace_insensitive: bool,
+    /// Replace the Nth (1-based) match instead of requiring `old_str` to be unique
+    pub occurrence: Option<usize>,
+    /// Show the user a unified diff of the edit instead of the edited section
+    pub show_diff: bool,
+    /// Lines of context around the edit in the returned snippet, defaults to 4
+    pub context_lines: Option<usize>,
+    /// Only match `old_str` where it isn't part of a longer word
+    pub whole_word: bool,
+}
+
+/// The lines `view_lines` returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineWindow {
+    /// The first N lines
+    Head(usize),
+    /// The last N lines
+    Tail(usize),
+}
+
+/// Optional behaviors for `write`
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Add `file_text` to the end of the existing content instead of replacing it
+    pub append: bool,
+    /// Replace an existing file that wasn't viewed, when the editor requires a view first
+    pub overwrite: bool,
+}
+
+// How a file's text is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileEncoding {
+    encoding: &'static Encoding,
+    bom: bool,
+}
+
+impl FileEncoding {
+    fn decode(self, bytes: &[u8]) -> Option<String> {
+        let (text, had_errors) = self.encoding.decode_with_bom_removal(bytes);
+        (!had_errors).then(|| text.into_owned())
+    }
+
+    // encoding_rs only encodes to UTF-8 for the UTF-16 family, so those are done by hand
+    fn encode(self, text: &str) -> Result<Vec<u8>, McpError> {
+        let mut bytes = Vec::new();
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let little_endian = self.encoding == UTF_16LE;
+            for unit in std::iter::once('\u{feff}')
+                .filter(|_| self.bom)
+                .chain(text.chars())
+                .flat_map(|c| c.encode_utf16(&mut [0; 2]).to_vec())
+            {
+                bytes.extend(if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                });
+            }
+            return Ok(bytes);
+        }
+
+        if self.bom && self.encoding == UTF_8 {
+            bytes.extend(b"\xEF\xBB\xBF");
+        }
+        let (encoded, _, had_errors) = self.encoding.encode(text);
+        if had_errors {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The text contains characters that can't be encoded in {name}",
+                    name = self.encoding.name()
+                ),
+                None,
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+// A saved state of a file in the undo or redo history
+#[derive(Debug, Clone)]
+struct Snapshot {
+    content: String,
+    // How the file was encoded, so restoring it after a delete doesn't fall back to UTF-8
+    encoding: Option<FileEncoding>,
+    saved_at: SystemTime,
+}
+
+// The content hash of a file when it was last viewed
+#[derive(Debug, Clone)]
+struct ViewRecord {
+    hash: u64,
+    viewed_at: SystemTime,
+}
 
 #[derive(Clone)]
 pub struct TextEditor {
     // Store file history for undo functionality
-    file_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
-    // Optional gitignore patterns for file access control
-    ignore_patterns: Option<Arc<Gitignore>>,
+    file_history: Arc<Mutex<HashMap<PathBuf, Vec<Snapshot>>>>,
+    // Store undone file states for redo functionality
+    redo_history: Arc<Mutex<HashMap<PathBuf, Vec<Snapshot>>>>,
+    // Files seen by `view`, to detect changes made outside the editor
+    view_records: Arc<Mutex<HashMap<PathBuf, ViewRecord>>>,
+    // Encodings chosen with `set_encoding`, other files are detected from a byte order mark
+    encodings: Arc<Mutex<HashMap<PathBuf, &'static Encoding>>>,
+    // Gitignore patterns for file access control, shared with the other tools
+    ignore_patterns: IgnorePatterns,
     // Maximum number of undo states to keep per file
     max_history_per_file: usize,
+    // Largest file `view` will read, in bytes
+    max_file_bytes: u64,
+    // Most characters a file may have to be viewed or written
+    max_chars: usize,
+    // Reject writes over existing files that weren't viewed in this session
+    require_view_before_write: bool,
 }
 
 impl Default for TextEditor {
@@ -34,93 +162,603 @@ impl TextEditor {
     pub fn new() -> Self {
         Self {
             file_history: Arc::new(Mutex::new(HashMap::new())),
-            ignore_patterns: None,
+            redo_history: Arc::new(Mutex::new(HashMap::new())),
+            view_records: Arc::new(Mutex::new(HashMap::new())),
+            encodings: Arc::new(Mutex::new(HashMap::new())),
+            ignore_patterns: IgnorePatterns::new(),
             max_history_per_file: DEFAULT_MAX_UNDO_HISTORY,
+            max_file_bytes: Self::env_limit("TEXT_EDITOR_MAX_FILE_BYTES", DEFAULT_MAX_FILE_BYTES),
+            max_chars: Self::env_limit("TEXT_EDITOR_MAX_CHARS", DEFAULT_MAX_CHARS),
+            require_view_before_write: false,
         }
     }
 
     pub fn new_with_history_limit(max_history: usize) -> Self {
         Self {
             file_history: Arc::new(Mutex::new(HashMap::new())),
-            ignore_patterns: None,
+            redo_history: Arc::new(Mutex::new(HashMap::new())),
+            view_records: Arc::new(Mutex::new(HashMap::new())),
+            encodings: Arc::new(Mutex::new(HashMap::new())),
+            ignore_patterns: IgnorePatterns::new(),
             max_history_per_file: max_history,
+            max_file_bytes: Self::env_limit("TEXT_EDITOR_MAX_FILE_BYTES", DEFAULT_MAX_FILE_BYTES),
+            max_chars: Self::env_limit("TEXT_EDITOR_MAX_CHARS", DEFAULT_MAX_CHARS),
+            require_view_before_write: false,
         }
     }
 
-    pub fn with_ignore_patterns(mut self, ignore_patterns: Arc<Gitignore>) -> Self {
-        self.ignore_patterns = Some(ignore_patterns);
+    // Size limits can be raised for large generated files, invalid values keep the default
+    fn env_limit<T: std::str::FromStr>(name: &str, default: T) -> T {
+        std::env::var(name)
+            .ok()
+            .and_then(|s| s.parse::<T>().ok())
+            .unwrap_or(default)
+    }
+
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
         self
     }
 
-    fn check_ignore_patterns(&self, path: &Path) -> Result<(), McpError> {
-        if let Some(ignore_patterns) = &self.ignore_patterns {
-            if ignore_patterns.matched(path, false).is_ignore() {
-                return Err(McpError::invalid_request(
-                    format!(
-                        "The file '{display}' is restricted by ignore patterns",
-                        display = path.display()
-                    ),
-                    None,
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    /// Largest file `view` reads whole, in bytes
+    pub fn max_file_bytes(&self) -> u64 {
+        self.max_file_bytes
+    }
+
+    /// Most characters a file may have to be viewed or written
+    pub fn max_chars(&self) -> usize {
+        self.max_chars
+    }
+
+    /// Undo states kept per file
+    pub fn max_history(&self) -> usize {
+        self.max_history_per_file
+    }
+
+    /// Require an existing file to be viewed (or written) before `write` may replace it
+    pub fn with_require_view_before_write(mut self, require: bool) -> Self {
+        self.require_view_before_write = require;
+        self
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: IgnorePatterns) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    pub(crate) fn check_ignore_patterns(&self, path: &Path) -> Result<(), McpError> {
+        self.ignore_patterns.check(path, false)
+    }
+
+    // Paths a command changes can't be restricted or be an ignore or allow file
+    fn check_writable(&self, path: &Path) -> Result<(), McpError> {
+        self.ignore_patterns.check_writable(path, false)
+    }
+
+    pub async fn view(&self, path: String) -> Result<CallToolResult, McpError> {
+        // Files over the size limit are paged through rather than refused
+        let oversized = std::fs::metadata(&path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() > self.max_file_bytes);
+        if oversized {
+            return self.view_chunk(path, 0).await;
+        }
+
+        let path = PathBuf::from(path);
+        let content = self.read_text(&path)?;
+
+        self.record_view(&path, content.as_bytes());
+
+        let formatted = Self::format_view(&path, &content);
+
+        Ok(CallToolResult::success(vec![
+            Content::text(formatted.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(formatted)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    /// View only the first or last lines of a file, e.g. the end of a log. UTF-8 files are
+    /// streamed rather than loaded whole, so the file size limit doesn't apply to them
+    pub async fn view_lines(
+        &self,
+        path: String,
+        window: LineWindow,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+        self.check_ignore_patterns(&path)?;
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a file.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        let (LineWindow::Head(count) | LineWindow::Tail(count)) = window;
+        if count == 0 {
+            return Err(McpError::invalid_params(
+                "head and tail must be at least 1",
+                None,
+            ));
+        }
+
+        let (lines, total) = self.select_lines(&path, window)?;
+        let content = lines.join("\n");
+        if content.chars().count() > self.max_chars {
+            return Err(self.selection_too_large(&path));
+        }
+
+        let shown = lines.len();
+        let note = match (window, total) {
+            (_, Some(total)) if shown == total => format!("all {total} lines"),
+            (LineWindow::Head(_), Some(total)) => {
+                format!("first {shown} of {total} lines, partial view")
+            }
+            (LineWindow::Head(_), None) => format!("first {shown} lines, partial view"),
+            (LineWindow::Tail(_), total) => format!(
+                "last {shown} of {} lines, partial view",
+                total.unwrap_or(shown)
+            ),
+        };
+        let language = lang::get_language_identifier(&path);
+        let formatted = format!(
+            "### {display} ({note})\n```{language}\n{content}\n```",
+            display = path.display()
+        );
+
+        Ok(CallToolResult::success(vec![
+            Content::text(formatted.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(formatted)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    /// View part of a UTF-8 file starting at a byte offset, sized to the view limits and
+    /// ending at a line break, preferably a blank line between definitions. The response
+    /// gives the offset the next chunk starts at
+    pub async fn view_chunk(&self, path: String, offset: u64) -> Result<CallToolResult, McpError> {
+        use std::io::{Read, Seek};
+
+        let path = PathBuf::from(path);
+        self.check_ignore_patterns(&path)?;
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a file.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        let read_error =
+            |e: std::io::Error| McpError::internal_error(format!("Failed to read file: {e}"), None);
+        let mut file = std::fs::File::open(&path).map_err(read_error)?;
+        let total_bytes = file.metadata().map_err(read_error)?.len();
+        if offset >= total_bytes && !(offset == 0 && total_bytes == 0) {
+            return Err(McpError::invalid_params(
+                format!(
+                    "offset {offset} is past the end of the file, which has {total_bytes} bytes"
+                ),
+                None,
+            ));
+        }
+
+        let mut bom = [0; 3];
+        let bom_len = file.read(&mut bom).map_err(read_error)?;
+        if self.file_encoding(&path, Some(&bom[..bom_len])).encoding != UTF_8 {
+            return Err(McpError::invalid_params(
+                format!(
+                    "'{display}' isn't UTF-8, only UTF-8 files can be viewed in chunks",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // Every character counts at least one byte, so this also keeps within the character limit
+        let chunk_bytes = self.max_file_bytes.min(self.max_chars as u64);
+        file.seek(std::io::SeekFrom::Start(offset))
+            .map_err(read_error)?;
+        let mut chunk = Vec::new();
+        file.take(chunk_bytes)
+            .read_to_end(&mut chunk)
+            .map_err(read_error)?;
+        if chunk
+            .first()
+            .is_some_and(|byte| (0x80..0xC0).contains(byte))
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "offset {offset} is inside a character, use an offset returned by a previous view"
+                ),
+                None,
+            ));
+        }
+
+        let end = offset + chunk.len() as u64;
+        if end < total_bytes {
+            chunk.truncate(Self::chunk_boundary(&chunk));
+        }
+        let end = offset + chunk.len() as u64;
+        let next_offset = (end < total_bytes).then_some(end);
+        let text = String::from_utf8_lossy(&chunk);
+        let content = text.strip_prefix('\u{feff}').unwrap_or(&text);
+
+        let language = lang::get_language_identifier(&path);
+        let position = match next_offset {
+            Some(next_offset) => format!(
+                "bytes {offset}-{end} of {total_bytes}, partial view; continue with offset {next_offset}"
+            ),
+            None => format!("bytes {offset}-{end} of {total_bytes}, end of file"),
+        };
+        let formatted = format!(
+            "### {display} ({position})\n```{language}\n{content}\n```",
+            display = path.display()
+        );
+        let report = serde_json::json!({
+            "path": path.display().to_string(),
+            "offset": offset,
+            "end": end,
+            "next_offset": next_offset,
+            "total_bytes": total_bytes,
+        });
+
+        Ok(CallToolResult::success(vec![
+            Content::text(formatted.clone()).with_audience(vec![Role::Assistant]),
+            Content::json(report)?.with_audience(vec![Role::Assistant]),
+            Content::text(formatted)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    // Where to end a chunk that doesn't reach the end of the file: after a blank line in its
+    // last quarter if there is one, which usually falls between definitions, otherwise after
+    // the last line break, otherwise at the last whole character
+    fn chunk_boundary(chunk: &[u8]) -> usize {
+        let tail_start = chunk.len() - chunk.len() / 4;
+        if let Some(blank) = chunk[tail_start..]
+            .windows(2)
+            .rposition(|pair| pair == b"\n\n")
+        {
+            return tail_start + blank + 2;
+        }
+        if let Some(newline) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            return newline + 1;
+        }
+        let mut end = chunk.len();
+        while end > 0 && std::str::from_utf8(&chunk[..end]).is_err() {
+            end -= 1;
+        }
+        end
+    }
+
+    fn selection_too_large(&self, path: &Path) -> McpError {
+        McpError::invalid_params(
+            format!(
+                "The selected lines of '{display}' have more than {max_chars} characters, the maximum character count. Ask for fewer lines.",
+                display = path.display(),
+                max_chars = self.max_chars
+            ),
+            None,
+        )
+    }
+
+    // The lines in the window and the file's line count, None when reading stopped early.
+    // UTF-8 files fail as soon as the window can't fit the character limit, so no more than
+    // the limit is held in memory
+    fn select_lines(
+        &self,
+        path: &Path,
+        window: LineWindow,
+    ) -> Result<(Vec<String>, Option<usize>), McpError> {
+        use std::io::BufRead;
+
+        let read_error =
+            |e: std::io::Error| McpError::internal_error(format!("Failed to read file: {e}"), None);
+        let file = std::fs::File::open(path).map_err(read_error)?;
+        let mut reader = std::io::BufReader::new(file);
+        let encoding = self.file_encoding(path, Some(reader.fill_buf().map_err(read_error)?));
+
+        // Other encodings can't be split on newline bytes, so they're decoded whole
+        if encoding.encoding != UTF_8 {
+            let content = self.read_text(path)?;
+            let lines: Vec<&str> = content.lines().collect();
+            let total = lines.len();
+            let selected = match window {
+                LineWindow::Head(count) => &lines[..count.min(total)],
+                LineWindow::Tail(count) => &lines[total.saturating_sub(count)..],
+            };
+            return Ok((
+                selected.iter().map(|line| line.to_string()).collect(),
+                Some(total),
+            ));
+        }
+
+        // A line longer than this has more characters than the limit however it's encoded, the
+        // rest of it is skipped rather than read into memory
+        let max_line_bytes = self.max_chars.saturating_mul(4).saturating_add(2) as u64;
+        let mut selected = std::collections::VecDeque::new();
+        let mut selected_chars = 0;
+        // Oldest lines of the tail window that were dropped to stay within the limit, the
+        // window is too large for as long as any of them is in it
+        let mut dropped = 0;
+        // Characters of the selected lines joined with line breaks
+        let joined_chars = |selected: &std::collections::VecDeque<String>, chars: usize| {
+            chars + selected.len().saturating_sub(1)
+        };
+        let mut total = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = std::io::Read::take(&mut reader, max_line_bytes)
+                .read_until(b'\n', &mut line)
+                .map_err(read_error)?;
+            if read == 0 {
+                if dropped > 0 {
+                    return Err(self.selection_too_large(path));
+                }
+                return Ok((selected.into(), Some(total)));
+            }
+            if read as u64 == max_line_bytes && !line.ends_with(b"\n") {
+                Self::skip_rest_of_line(&mut reader).map_err(read_error)?;
+            }
+            total += 1;
+            let text = String::from_utf8_lossy(&line);
+            let mut text = text.trim_end_matches(['\n', '\r']);
+            if total == 1 {
+                text = text.trim_start_matches('\u{feff}');
+            }
+            match window {
+                LineWindow::Head(count) => {
+                    selected_chars += text.chars().count();
+                    selected.push_back(text.to_string());
+                    if joined_chars(&selected, selected_chars) > self.max_chars {
+                        return Err(self.selection_too_large(path));
+                    }
+                    if total == count {
+                        // Whether anything follows decides if this was the whole file
+                        let at_end = reader.fill_buf().map_err(read_error)?.is_empty();
+                        return Ok((selected.into(), at_end.then_some(total)));
+                    }
+                }
+                LineWindow::Tail(count) => {
+                    if selected.len() + dropped == count {
+                        if dropped > 0 {
+                            dropped -= 1;
+                        } else if let Some(oldest) = selected.pop_front() {
+                            selected_chars -= oldest.chars().count();
+                        }
+                    }
+                    selected_chars += text.chars().count();
+                    selected.push_back(text.to_string());
+                    while joined_chars(&selected, selected_chars) > self.max_chars {
+                        let Some(oldest) = selected.pop_front() else {
+                            break;
+                        };
+                        selected_chars -= oldest.chars().count();
+                        dropped += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Consume an overlong line up to and including its line break
+    fn skip_rest_of_line(reader: &mut impl std::io::BufRead) -> std::io::Result<()> {
+        loop {
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(());
+            }
+            match buffer.iter().position(|byte| *byte == b'\n') {
+                Some(end) => {
+                    reader.consume(end + 1);
+                    return Ok(());
+                }
+                None => {
+                    let length = buffer.len();
+                    reader.consume(length);
+                }
+            }
+        }
+    }
+
+    /// View several files in one call, each under its own header. Paths that failed to
+    /// resolve, files that can't be viewed, and files that would push the combined content
+    /// past the character limit are skipped with the reason instead of failing the call
+    pub async fn view_many(
+        &self,
+        paths: Vec<Result<PathBuf, (String, McpError)>>,
+    ) -> Result<CallToolResult, McpError> {
+        if paths.is_empty() || paths.len() > MAX_VIEW_MANY_FILES {
+            return Err(McpError::invalid_params(
+                format!(
+                    "view_many takes between 1 and {MAX_VIEW_MANY_FILES} files, got {}",
+                    paths.len()
+                ),
+                None,
+            ));
+        }
+
+        let mut sections = Vec::new();
+        let mut skipped = Vec::new();
+        let mut total_chars = 0;
+        for (index, path) in paths.iter().enumerate() {
+            let path = match path {
+                Ok(path) if paths[..index].iter().any(|seen| seen.as_ref() == Ok(path)) => {
+                    continue;
+                }
+                Ok(path) => path,
+                Err((requested, e)) => {
+                    skipped.push(format!("- {requested}: {}", e.message));
+                    continue;
+                }
+            };
+            let content = match self.read_text(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    skipped.push(format!("- {}: {}", path.display(), e.message));
+                    continue;
+                }
+            };
+            let chars = content.chars().count();
+            if total_chars + chars > self.max_chars {
+                skipped.push(format!(
+                    "- {}: {chars} characters would exceed the combined limit of {}, view it separately",
+                    path.display(),
+                    self.max_chars
                 ));
+                continue;
             }
+            total_chars += chars;
+            self.record_view(path, content.as_bytes());
+            sections.push(Self::format_view(path, &content));
         }
-        Ok(())
+
+        let viewed = sections.len();
+        let mut formatted = sections.join("\n\n");
+        if !skipped.is_empty() {
+            if !formatted.is_empty() {
+                formatted.push_str("\n\n");
+            }
+            formatted.push_str(&format!(
+                "Skipped {} files:\n{}",
+                skipped.len(),
+                skipped.join("\n")
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![
+            Content::text(formatted.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(format!("Viewed {viewed} files, skipped {}", skipped.len()))
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
     }
 
-    pub async fn view(&self, path: String) -> Result<CallToolResult, McpError> {
+    // A file's content in a fenced block under a header naming the file
+    fn format_view(path: &Path, content: &str) -> String {
+        let language = lang::get_language_identifier(path);
+        format!(
+            "### {display}\n```{language}\n{content}\n```",
+            display = path.display()
+        )
+    }
+
+    /// Report a file's size, line and character counts without returning its content,
+    /// streaming it so files over the `view` limits can be measured too
+    pub async fn stat(&self, path: String) -> Result<CallToolResult, McpError> {
+        use std::io::BufRead;
+
         let path = PathBuf::from(path);
+        self.check_ignore_patterns(&path)?;
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a file.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        let metadata = std::fs::metadata(&path).map_err(|e| {
+            McpError::internal_error(format!("Failed to get file metadata: {e}"), None)
+        })?;
+        let file = std::fs::File::open(&path)
+            .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+        let mut reader = std::io::BufReader::new(file);
+        let (mut lines, mut chars) = (0, 0);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader
+                .read_until(b'\n', &mut line)
+                .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+            if read == 0 {
+                break;
+            }
+            // A last line without a trailing newline still counts
+            lines += 1;
+            chars += String::from_utf8_lossy(&line).chars().count();
+        }
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs());
+        let bytes = metadata.len();
+        let report = serde_json::json!({
+            "path": path.display().to_string(),
+            "bytes": bytes,
+            "lines": lines,
+            "chars": chars,
+            "language": lang::get_language_identifier(&path),
+            "modified_unix_secs": modified,
+            "viewable": bytes <= self.max_file_bytes && chars <= self.max_chars,
+        });
+
+        Ok(CallToolResult::success(vec![
+            Content::text(report.to_string()).with_audience(vec![Role::Assistant]),
+            Content::text(format!(
+                "{display}: {lines} lines, {bytes} bytes",
+                display = path.display()
+            ))
+            .with_audience(vec![Role::User])
+            .with_priority(0.0),
+        ]))
+    }
 
+    /// Read a file's text under the same ignore patterns and size limits as `view`
+    pub fn read_text(&self, path: &Path) -> Result<String, McpError> {
         // Check ignore patterns first
-        self.check_ignore_patterns(&path)?;
+        self.check_ignore_patterns(path)?;
 
         if path.is_file() {
-            // Check file size first (400KB limit)
-            const MAX_FILE_SIZE: u64 = 400 * 1024; // 400KB in bytes
-            const MAX_CHAR_COUNT: usize = 400_000; // 409600 chars = 400KB
-
-            let file_size = std::fs::metadata(&path)
+            // Check file size first
+            let file_size = std::fs::metadata(path)
                 .map_err(|e| {
                     McpError::internal_error(format!("Failed to get file metadata: {e}"), None)
                 })?
                 .len();
 
-            if file_size > MAX_FILE_SIZE {
+            if file_size > self.max_file_bytes {
                 return Err(McpError::invalid_params(
                     format!(
-                        "File '{display}' is too large ({size:.2}KB). Maximum size is 400KB to prevent memory issues.",
+                        "File '{display}' is too large ({size:.2}KB). Maximum size is {max_size:.2}KB to prevent memory issues.",
                         display = path.display(),
-                        size = file_size as f64 / 1024.0
+                        size = file_size as f64 / 1024.0,
+                        max_size = self.max_file_bytes as f64 / 1024.0
                     ),
                     None,
                 ));
             }
 
-            let content = std::fs::read_to_string(&path)
-                .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+            let content = self.read_file(path)?;
 
             let char_count = content.chars().count();
-            if char_count > MAX_CHAR_COUNT {
+            if char_count > self.max_chars {
                 return Err(McpError::invalid_params(
                     format!(
-                        "File '{display}' has too many characters ({char_count}). Maximum character count is {MAX_CHAR_COUNT}.",
-                        display = path.display()
+                        "File '{display}' has too many characters ({char_count}). Maximum character count is {max_chars}.",
+                        display = path.display(),
+                        max_chars = self.max_chars
                     ),
                     None,
                 ));
             }
 
-            let language = lang::get_language_identifier(&path);
-            let formatted = format!(
-                "### {display}\n```{language}\n{content}\n```",
-                display = path.display()
-            );
-
-            Ok(CallToolResult::success(vec![
-                Content::text(formatted.clone()).with_audience(vec![Role::Assistant]),
-                Content::text(formatted)
-                    .with_audience(vec![Role::User])
-                    .with_priority(0.0),
-            ]))
+            Ok(content)
         } else {
             Err(McpError::invalid_params(
                 format!(
@@ -133,10 +771,20 @@ impl TextEditor {
     }
 
     pub async fn write(&self, path: String, file_text: String) -> Result<CallToolResult, McpError> {
+        self.write_with_options(path, file_text, WriteOptions::default())
+            .await
+    }
+
+    pub async fn write_with_options(
+        &self,
+        path: String,
+        file_text: String,
+        options: WriteOptions,
+    ) -> Result<CallToolResult, McpError> {
         let path = PathBuf::from(path);
 
         // Check ignore patterns first
-        self.check_ignore_patterns(&path)?;
+        self.check_writable(&path)?;
 
         // Check if path is an existing directory
         if path.is_dir() {
@@ -149,23 +797,65 @@ impl TextEditor {
             ));
         }
 
-        // Check character count limit
-        if file_text.chars().count() > MAX_WRITE_CHAR_COUNT {
+        // Only appending needs the current content, a replaced file may be too large or in an
+        // encoding that doesn't decode
+        let exists = path.is_file();
+        let existing = if exists && options.append {
+            Some(self.read_file(&path)?)
+        } else {
+            None
+        };
+
+        // Replacing content the caller never looked at is the mistake this guard catches
+        if self.require_view_before_write
+            && exists
+            && !options.append
+            && !options.overwrite
+            && !self.view_records.lock().unwrap().contains_key(&path)
+        {
+            return Err(McpError::invalid_request(
+                format!(
+                    "The file '{display}' already exists and hasn't been viewed in this session. View it first, or set overwrite to replace it anyway.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // Appending to a missing file is a plain write
+        let content = match &existing {
+            Some(existing) if options.append => format!("{existing}{file_text}"),
+            _ => file_text.clone(),
+        };
+
+        // Check character count limit against everything that will be in the file
+        if content.chars().count() > self.max_chars {
             return Err(McpError::invalid_params(
                 format!(
-                    "Input content for '{display}' has too many characters ({char_count}). Maximum allowed is {MAX_WRITE_CHAR_COUNT}.",
+                    "Input content for '{display}' has too many characters ({char_count}). Maximum allowed is {max_chars}.",
                     display = path.display(),
-                    char_count = file_text.chars().count()
+                    char_count = content.chars().count(),
+                    max_chars = self.max_chars
                 ),
                 None,
             ));
         }
 
-        // Save current file state for undo functionality
-        self.save_file_history(&path)?;
+        // Save current file state for undo functionality. Content that doesn't decode can't be
+        // kept, but can still be replaced
+        let history_error = match self.save_file_history(&path) {
+            Ok(()) => None,
+            Err(e) if !options.append => Some(e.message),
+            Err(e) => return Err(e),
+        };
 
-        // Normalize line endings based on platform
-        let normalized_text = normalize_line_endings(&file_text);
+        // Keep the file's existing line endings, new files get the platform's
+        let line_ending = match &existing {
+            Some(existing) => Self::line_ending_for(Some(existing)),
+            None if exists => self.sampled_line_ending(&path),
+            None => LineEnding::platform(),
+        };
+        let normalized_text = line_ending.apply(&content);
 
         // Create parent directories if they don't exist
         if let Some(parent) = path.parent() {
@@ -175,13 +865,21 @@ impl TextEditor {
         }
 
         // Write to the file
-        std::fs::write(&path, &normalized_text)
-            .map_err(|e| McpError::internal_error(format!("Failed to write file: {e}"), None))?;
+        self.write_file(&path, &normalized_text)?;
+        // The caller knows what it just wrote, so the file counts as seen
+        self.record_view(&path, normalized_text.as_bytes());
 
         // Try to detect the language from the file extension
         let language = lang::get_language_identifier(&path);
 
-        let success_message = format!("Successfully wrote to {display}", display = path.display());
+        let verb = if options.append { "appended" } else { "wrote" };
+        let mut success_message =
+            format!("Successfully {verb} to {display}", display = path.display());
+        if let Some(e) = history_error {
+            success_message.push_str(&format!(
+                ". The previous content couldn't be saved for undo_edit: {e}"
+            ));
+        }
         let formatted_output = format!(
             "### {display}\n```{language}\n{file_text}\n```",
             display = path.display()
@@ -195,22 +893,113 @@ impl TextEditor {
         ]))
     }
 
-    pub async fn str_replace(
+    /// Append one JSON record as a single newline-terminated line
+    pub async fn append_jsonl(
         &self,
         path: String,
-        old_str: String,
-        new_str: String,
+        record: String,
     ) -> Result<CallToolResult, McpError> {
         let path = PathBuf::from(path);
 
         // Check ignore patterns first
-        self.check_ignore_patterns(&path)?;
+        self.check_writable(&path)?;
 
-        // Check if file exists
-        if !path.exists() {
+        if path.is_dir() {
             return Err(McpError::invalid_params(
                 format!(
-                    "File '{display}' does not exist, you can write a new file with the `write` command",
+                    "The path '{display}' is an existing directory. The 'append_jsonl' command can only target files.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // A record spanning lines would corrupt the file for line-based readers
+        let record = record.trim_end();
+        if record.contains(['\n', '\r']) {
+            return Err(McpError::invalid_params(
+                "The record must be a single line of JSON without embedded newlines".to_string(),
+                None,
+            ));
+        }
+        let value: serde_json::Value = serde_json::from_str(record).map_err(|e| {
+            McpError::invalid_params(format!("The record is not valid JSON: {e}"), None)
+        })?;
+        let line = serde_json::to_string(&value).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize record: {e}"), None)
+        })?;
+
+        // Save current file state for undo functionality
+        self.save_file_history(&path)?;
+
+        // Terminate a previous record that was cut off, so this one starts on its own line
+        let existing = std::fs::read(&path).ok().filter(|bytes| !bytes.is_empty());
+        let encoding = self.file_encoding(&path, existing.as_deref());
+        let needs_separator = existing
+            .as_deref()
+            .and_then(|bytes| encoding.decode(bytes))
+            .is_some_and(|content| !content.ends_with('\n'));
+        let mut appended = String::new();
+        if needs_separator {
+            appended.push('\n');
+        }
+        appended.push_str(&line);
+        appended.push('\n');
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                McpError::internal_error(format!("Failed to create directories: {e}"), None)
+            })?;
+        }
+
+        // A single append-mode write, so concurrent readers never see half a record
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| McpError::internal_error(format!("Failed to open file: {e}"), None))?;
+        // Only a new file starts with a byte order mark
+        let encoding = FileEncoding {
+            bom: encoding.bom && existing.is_none(),
+            ..encoding
+        };
+        std::io::Write::write_all(&mut file, &encoding.encode(&appended)?)
+            .map_err(|e| McpError::internal_error(format!("Failed to write file: {e}"), None))?;
+        self.refresh_view_record(&path);
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Appended a record to {display}",
+            display = path.display()
+        ))]))
+    }
+
+    pub async fn str_replace(
+        &self,
+        path: String,
+        old_str: String,
+        new_str: String,
+    ) -> Result<CallToolResult, McpError> {
+        self.str_replace_with_options(path, old_str, new_str, StrReplaceOptions::default())
+            .await
+    }
+
+    pub async fn str_replace_with_options(
+        &self,
+        path: String,
+        old_str: String,
+        new_str: String,
+        options: StrReplaceOptions,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        // Check if file exists
+        if !path.exists() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "File '{display}' does not exist, you can write a new file with the `write` command",
                     display = path.display()
                 ),
                 None,
@@ -218,507 +1007,2916 @@ impl TextEditor {
         }
 
         // Read content
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+        let content = self.read_file(&path)?;
+
+        // Locate every span of the file matching 'old_str'
+        let matches: Vec<(usize, usize)> = if options.whitespace_insensitive {
+            let pattern = Self::whitespace_insensitive_pattern(&old_str)?;
+            pattern
+                .find_iter(&content)
+                .map(|m| (m.start(), m.end()))
+                .collect()
+        } else {
+            content
+                .match_indices(&old_str)
+                .map(|(start, matched)| (start, start + matched.len()))
+                .collect()
+        };
+        let matches = if options.whole_word {
+            let bounded: Vec<(usize, usize)> = matches
+                .iter()
+                .copied()
+                .filter(|&(start, end)| Self::is_whole_word(&content, start, end))
+                .collect();
+            if let Some(&(start, _)) = matches.first().filter(|_| bounded.is_empty()) {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "'old_str' only appears inside longer words (first at line {line}), so nothing was replaced. Include the whole word in old_str or unset whole_word",
+                        line = content[..start].matches('\n').count() + 1
+                    ),
+                    None,
+                ));
+            }
+            bounded
+        } else {
+            matches
+        };
+
+        // Ensure 'old_str' appears exactly once, unless a specific occurrence was requested
+        match options.occurrence {
+            None if matches.len() > 1 => {
+                return Err(McpError::invalid_params(
+                    "'old_str' must appear exactly once in the file, but it appears multiple times"
+                        .to_string(),
+                    None,
+                ));
+            }
+            Some(occurrence)
+                if !matches.is_empty() && (occurrence == 0 || occurrence > matches.len()) =>
+            {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "'occurrence' must be between 1 and {count}, but got {occurrence}: 'old_str' appears {count} time(s) in the file",
+                        count = matches.len()
+                    ),
+                    None,
+                ));
+            }
+            _ => {}
+        }
+        let index = options.occurrence.unwrap_or(1).saturating_sub(1);
+        let Some(&(match_start, match_end)) = matches.get(index) else {
+            let hint = if options.whitespace_insensitive {
+                "Make sure the string matches existing file content, ignoring differences in whitespace!"
+            } else {
+                "Make sure the string exactly matches existing file content, including whitespace!"
+            };
+            return Err(McpError::invalid_params(
+                format!(
+                    "'old_str' must appear exactly once in the file, but it does not appear in the file. {hint}"
+                ),
+                None,
+            ));
+        };
+
+        // Save history for undo
+        self.save_file_history(&path)?;
+
+        // Replace the matched span and write back with the file's existing line endings
+        let new_content = format!(
+            "{before}{new_str}{after}",
+            before = &content[..match_start],
+            after = &content[match_end..]
+        );
+        let normalized_content = Self::line_ending_for(Some(&content)).apply(&new_content);
+        self.write_file(&path, &normalized_content)?;
+        self.refresh_view_record(&path);
+
+        // Count newlines before the replacement to find the line number
+        let replacement_line = content[..match_start].matches('\n').count();
+
+        // Reviewers get a unified diff, the assistant still gets the snippet
+        let user_output = options
+            .show_diff
+            .then(|| Self::unified_diff(&path, &content, &new_content));
+
+        let mut result = Self::edit_snippet_result(
+            &path,
+            &new_content,
+            replacement_line,
+            &new_str,
+            options.context_lines.unwrap_or(DEFAULT_SNIPPET_LINES),
+            user_output,
+        );
+        result
+            .content
+            .push(Self::replacement_report(&path, 1, replacement_line)?);
+        Ok(result)
+    }
+
+    /// Replace matches of a regex, expanding `$1`-style capture groups in `new_str`.
+    /// Exactly one match is required unless `all` is set
+    pub async fn regex_replace(
+        &self,
+        path: String,
+        pattern: String,
+        new_str: String,
+        all: bool,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+        self.check_writable(&path)?;
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "File '{display}' does not exist, you can write a new file with the `write` command",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        let regex = Regex::new(&pattern).map_err(|e| {
+            McpError::invalid_params(format!("Invalid regex '{pattern}': {e}"), None)
+        })?;
+
+        let content = self.read_file(&path)?;
+        let count = regex.find_iter(&content).count();
+        match count {
+            0 => {
+                return Err(McpError::invalid_params(
+                    format!("The pattern '{pattern}' does not match anything in the file"),
+                    None,
+                ));
+            }
+            1 => {}
+            _ if !all => {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "The pattern '{pattern}' must match exactly once, but it matches {count} times. Make it more specific or set all to replace every match"
+                    ),
+                    None,
+                ));
+            }
+            _ => {}
+        }
+
+        // The first replacement is what the snippet shows
+        let first = regex.captures(&content).expect("the pattern matched");
+        let first_start = first.get(0).expect("group 0 is the whole match").start();
+        let mut first_replacement = String::new();
+        first.expand(&new_str, &mut first_replacement);
+
+        self.save_file_history(&path)?;
+        let new_content = regex.replace_all(&content, new_str.as_str()).into_owned();
+        let normalized_content = Self::line_ending_for(Some(&content)).apply(&new_content);
+        self.write_file(&path, &normalized_content)?;
+        self.refresh_view_record(&path);
+
+        let replacement_line = content[..first_start].matches('\n').count();
+        let mut result = Self::edit_snippet_result(
+            &path,
+            &new_content,
+            replacement_line,
+            &first_replacement,
+            DEFAULT_SNIPPET_LINES,
+            None,
+        );
+        if count > 1 {
+            result.content.insert(
+                0,
+                Content::text(format!(
+                    "Replaced {count} matches of '{pattern}', the first is shown below"
+                ))
+                .with_audience(vec![Role::Assistant]),
+            );
+        }
+        result
+            .content
+            .push(Self::replacement_report(&path, count, replacement_line)?);
+        Ok(result)
+    }
+
+    // Machine-readable confirmation of a replacement, `line` is the 1-based first line changed
+    fn replacement_report(
+        path: &Path,
+        replacements: usize,
+        replacement_line: usize,
+    ) -> Result<Content, McpError> {
+        let report = serde_json::json!({
+            "path": path.display().to_string(),
+            "replacements": replacements,
+            "line": replacement_line + 1,
+        });
+        Ok(Content::json(report)?.with_audience(vec![Role::Assistant]))
+    }
+
+    pub async fn insert(
+        &self,
+        path: String,
+        insert_line: usize,
+        text: String,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        // Check if file exists
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "File '{display}' does not exist, you can write a new file with the `write` command",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // Read content
+        let content = self.read_file(&path)?;
+
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        if insert_line > lines.len() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "insert_line {insert_line} is beyond the end of the file, which has {count} lines. Use 0 to insert at the start or {count} to insert at the end.",
+                    count = lines.len()
+                ),
+                None,
+            ));
+        }
+
+        // Keep the inserted text on its own lines
+        let mut before = lines[..insert_line].concat();
+        if !before.is_empty() && !before.ends_with('\n') {
+            before.push('\n');
+        }
+        let mut text = text;
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let new_content = format!(
+            "{before}{text}{after}",
+            after = lines[insert_line..].concat()
+        );
+
+        // Save history for undo
+        self.save_file_history(&path)?;
+
+        let normalized_content = Self::line_ending_for(Some(&content)).apply(&new_content);
+        self.write_file(&path, &normalized_content)?;
+        self.refresh_view_record(&path);
+
+        Ok(Self::edit_snippet_result(
+            &path,
+            &new_content,
+            insert_line,
+            &text,
+            DEFAULT_SNIPPET_LINES,
+            None,
+        ))
+    }
+
+    // Show the edited section with a few lines of context so the agent can verify it,
+    // the user sees the same section unless other output is given
+    fn edit_snippet_result(
+        path: &Path,
+        new_content: &str,
+        edit_line: usize,
+        edited_text: &str,
+        context_lines: usize,
+        user_output: Option<String>,
+    ) -> CallToolResult {
+        // Try to detect the language from the file extension
+        let language = lang::get_language_identifier(path);
+
+        // Calculate start and end lines for the snippet
+        let start_line = edit_line.saturating_sub(context_lines);
+        let end_line = edit_line + context_lines + edited_text.matches('\n').count();
+
+        // Get the relevant lines for our snippet
+        let lines: Vec<&str> = new_content.lines().collect();
+        let snippet = lines
+            .iter()
+            .skip(start_line)
+            .take(end_line - start_line + 1)
+            .cloned()
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let output = format!("```{language}\n{snippet}\n```");
+
+        let success_message = format!(
+            "The file {display} has been edited, and the section now reads:\n{output}\nReview the changes above for errors. Undo and edit the file again if necessary!",
+            display = path.display()
+        );
+
+        CallToolResult::success(vec![
+            Content::text(success_message).with_audience(vec![Role::Assistant]),
+            Content::text(user_output.unwrap_or(output))
+                .with_audience(vec![Role::User])
+                .with_priority(0.2),
+        ])
+    }
+
+    fn unified_diff(path: &Path, old_content: &str, new_content: &str) -> String {
+        let display = path.display().to_string();
+        let diff = similar::TextDiff::from_lines(old_content, new_content)
+            .unified_diff()
+            .context_radius(3)
+            .header(&display, &display)
+            .to_string();
+        format!("```diff\n{diff}```")
+    }
+
+    pub async fn search(&self, path: String, pattern: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_ignore_patterns(&path)?;
+
+        if !path.exists() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        let regex = Regex::new(&pattern).map_err(|e| {
+            McpError::invalid_params(format!("Invalid search pattern '{pattern}': {e}"), None)
+        })?;
+
+        // Walk the tree without descending into ignored directories
+        let ignore_patterns = self.ignore_patterns.clone();
+        let walker = ignore::WalkBuilder::new(&path)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !ignore_patterns.is_ignored(entry.path(), is_dir)
+            })
+            .build();
+
+        let mut grouped: Vec<(PathBuf, Vec<String>)> = Vec::new();
+        let mut match_count = 0;
+        let mut truncated = false;
+        'files: for entry in walker.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            // Skip files too large to view and anything that isn't text
+            if entry
+                .metadata()
+                .is_ok_and(|metadata| metadata.len() > self.max_file_bytes)
+            {
+                continue;
+            }
+            let Ok(content) = self.read_file(entry.path()) else {
+                continue;
+            };
+
+            let mut file_matches = Vec::new();
+            for (index, line) in content.lines().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if match_count == MAX_SEARCH_MATCHES {
+                    truncated = true;
+                    break;
+                }
+                match_count += 1;
+                file_matches.push(format!("  {line_number}: {line}", line_number = index + 1));
+            }
+            if !file_matches.is_empty() {
+                grouped.push((entry.into_path(), file_matches));
+            }
+            if truncated {
+                break 'files;
+            }
+        }
+
+        let mut output = if grouped.is_empty() {
+            format!(
+                "No matches found for `{pattern}` in {display}",
+                display = path.display()
+            )
+        } else {
+            format!(
+                "Found {match_count} matches for `{pattern}` in {display}:\n\n{matches}",
+                display = path.display(),
+                matches = grouped
+                    .iter()
+                    .map(|(file, lines)| format!("{}\n{}", file.display(), lines.join("\n")))
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            )
+        };
+        if truncated {
+            output.push_str(&format!(
+                "\n\nResults truncated to the first {MAX_SEARCH_MATCHES} matches. Narrow the pattern or path to see more."
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![
+            Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+            Content::text(output)
+                .with_audience(vec![Role::User])
+                .with_priority(0.0),
+        ]))
+    }
+
+    pub async fn delete(&self, path: String, recursive: bool) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        if path.is_dir() {
+            if !recursive {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "The path '{display}' is a directory. Set recursive to delete it and everything in it.",
+                        display = path.display()
+                    ),
+                    None,
+                ));
+            }
+            // Refuse to remove protected files as a side effect of removing their directory
+            self.check_tree_writable(&path)?;
+            std::fs::remove_dir_all(&path).map_err(|e| {
+                McpError::internal_error(format!("Failed to delete directory: {e}"), None)
+            })?;
+            self.encodings
+                .lock()
+                .unwrap()
+                .retain(|chosen, _| !chosen.starts_with(&path));
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Deleted directory {display}. Directory deletions cannot be undone.",
+                display = path.display()
+            ))]));
+        }
+
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // Save the content so undo_edit can bring the file back
+        self.save_file_history(&path)?;
+
+        std::fs::remove_file(&path)
+            .map_err(|e| McpError::internal_error(format!("Failed to delete file: {e}"), None))?;
+        self.encodings.lock().unwrap().remove(&path);
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Deleted {display}. Use undo_edit on this path to restore it.",
+            display = path.display()
+        ))]))
+    }
+
+    pub async fn move_path(
+        &self,
+        path: String,
+        destination: String,
+    ) -> Result<CallToolResult, McpError> {
+        let (path, destination) = self.check_transfer(path, destination)?;
+
+        std::fs::rename(&path, &destination)
+            .map_err(|e| McpError::internal_error(format!("Failed to move: {e}"), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Moved {source} to {destination}",
+            source = path.display(),
+            destination = destination.display()
+        ))]))
+    }
+
+    pub async fn copy_file(
+        &self,
+        path: String,
+        destination: String,
+    ) -> Result<CallToolResult, McpError> {
+        let (path, destination) = self.check_transfer(path, destination)?;
+
+        if !path.is_file() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' is not a file. Only files can be copied.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        // Like writing a new file, undo_edit on the destination empties it
+        self.save_file_history(&destination)?;
+
+        std::fs::copy(&path, &destination)
+            .map_err(|e| McpError::internal_error(format!("Failed to copy file: {e}"), None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Copied {source} to {destination}",
+            source = path.display(),
+            destination = destination.display()
+        ))]))
+    }
+
+    /// Create a directory and any missing parents, succeeding if it already exists
+    pub async fn mkdir(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+        self.ignore_patterns.check(&path, true)?;
+
+        if path.is_dir() {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Directory {display} already exists",
+                display = path.display()
+            ))]));
+        }
+        if path.exists() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' already exists and is not a directory.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        std::fs::create_dir_all(&path).map_err(|e| {
+            McpError::internal_error(format!("Failed to create directories: {e}"), None)
+        })?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Created directory {display}",
+            display = path.display()
+        ))]))
+    }
+
+    /// List a directory's entries sorted by name, with their type and file sizes.
+    /// Entries restricted by ignore patterns are left out
+    pub async fn list_dir(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+        self.ignore_patterns.check(&path, true)?;
+        if !path.is_dir() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist or is not a directory.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+
+        let read_error = |e: std::io::Error| {
+            McpError::internal_error(format!("Failed to list directory: {e}"), None)
+        };
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&path).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            // Symlinks are reported as such rather than followed
+            let metadata = entry.path().symlink_metadata().map_err(read_error)?;
+            let is_dir = metadata.is_dir();
+            if self.ignore_patterns.is_ignored(&entry.path(), is_dir) {
+                continue;
+            }
+            let (kind, size) = if is_dir {
+                ("dir", None)
+            } else if metadata.is_symlink() {
+                ("symlink", None)
+            } else {
+                ("file", Some(metadata.len()))
+            };
+            entries.push(serde_json::json!({
+                "name": entry.file_name().to_string_lossy(),
+                "type": kind,
+                "size": size,
+            }));
+        }
+        entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        let total = entries.len();
+        entries.truncate(MAX_LIST_DIR_ENTRIES);
+
+        let listing = serde_json::json!({
+            "path": path.display().to_string(),
+            "entries": entries,
+            "total": total,
+            "truncated": total > MAX_LIST_DIR_ENTRIES,
+        });
+        Ok(CallToolResult::success(vec![
+            Content::text(listing.to_string()).with_audience(vec![Role::Assistant]),
+            Content::text(format!(
+                "{display}: {total} entries",
+                display = path.display()
+            ))
+            .with_audience(vec![Role::User])
+            .with_priority(0.0),
+        ]))
+    }
+
+    // Fail when anything under `dir` is restricted or is an ignore or allow file, for commands
+    // that act on a whole directory. Hidden entries are walked too
+    fn check_tree_writable(&self, dir: &Path) -> Result<(), McpError> {
+        let protected = ignore::WalkBuilder::new(dir)
+            .standard_filters(false)
+            .build()
+            .flatten()
+            .find(|entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                self.ignore_patterns.is_ignored(entry.path(), is_dir)
+                    || is_access_control_file(entry.path())
+            });
+        match protected {
+            Some(entry) => Err(McpError::invalid_request(
+                format!(
+                    "The directory '{display}' contains '{protected}', which is restricted by ignore patterns or is an ignore or allow file",
+                    display = dir.display(),
+                    protected = entry.path().display()
+                ),
+                None,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Validate both ends of a move or copy, creating the destination's parent directories
+    fn check_transfer(
+        &self,
+        path: String,
+        destination: String,
+    ) -> Result<(PathBuf, PathBuf), McpError> {
+        let path = PathBuf::from(path);
+        let destination = PathBuf::from(destination);
+
+        // Check ignore patterns on both sides, a moved ignore or allow file would change them
+        self.check_writable(&path)?;
+        self.check_writable(&destination)?;
+
+        if !path.exists() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The path '{display}' does not exist.",
+                    display = path.display()
+                ),
+                None,
+            ));
+        }
+        // Moving or copying a directory carries everything in it along
+        if path.is_dir() {
+            self.check_tree_writable(&path)?;
+        }
+        if destination.exists() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "The destination '{display}' already exists. Delete it first to replace it.",
+                    display = destination.display()
+                ),
+                None,
+            ));
+        }
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                McpError::internal_error(format!("Failed to create directories: {e}"), None)
+            })?;
+        }
+        Ok((path, destination))
+    }
+
+    pub async fn undo_edit(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        let mut history = self.file_history.lock().unwrap();
+        let Some(contents) = history
+            .get_mut(&path)
+            .filter(|contents| !contents.is_empty())
+        else {
+            return Err(McpError::invalid_params(
+                "No edit history available to undo".to_string(),
+                None,
+            ));
+        };
+
+        // Keep the current content so the undo can be redone
+        let current_content = self.snapshot(&path)?;
+
+        // Write previous content back to file, the snapshot is only used up once that worked
+        if let Some(previous) = contents.last() {
+            self.restore_snapshot(&path, previous)?;
+        }
+        contents.pop();
+        self.refresh_view_record(&path);
+
+        let mut redo_history = self.redo_history.lock().unwrap();
+        self.push_bounded(
+            redo_history.entry(path.clone()).or_default(),
+            current_content,
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(
+            "Undid the last edit",
+        )]))
+    }
+
+    /// Revert a file to its oldest saved state in one step, then forget its history
+    pub async fn undo_all(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        // Lock in the same order as undo to avoid deadlocks
+        let mut history = self.file_history.lock().unwrap();
+        let mut redo_history = self.redo_history.lock().unwrap();
+        let Some(versions) = history.get(&path).filter(|versions| !versions.is_empty()) else {
+            return Err(McpError::invalid_params(
+                "No edit history available to undo".to_string(),
+                None,
+            ));
+        };
+        let count = versions.len();
+        self.restore_snapshot(&path, &versions[0])?;
+        self.refresh_view_record(&path);
+
+        history.remove(&path);
+        redo_history.remove(&path);
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Undid {count} edit(s) to {display}, restoring its oldest saved version. Its edit history was cleared, so this can't be undone.",
+            display = path.display()
+        ))]))
+    }
+
+    pub async fn redo(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        // Lock in the same order as undo to avoid deadlocks
+        let mut history = self.file_history.lock().unwrap();
+        let mut redo_history = self.redo_history.lock().unwrap();
+        let Some(contents) = redo_history
+            .get_mut(&path)
+            .filter(|contents| !contents.is_empty())
+        else {
+            return Err(McpError::invalid_params(
+                "No undone edit available to redo".to_string(),
+                None,
+            ));
+        };
+
+        // Keep the current content so the redo can be undone again
+        let current_content = self.snapshot(&path)?;
+
+        // Write the undone content back to file, the snapshot is only used up once that worked
+        if let Some(next) = contents.last() {
+            self.restore_snapshot(&path, next)?;
+        }
+        contents.pop();
+        self.refresh_view_record(&path);
+
+        self.push_bounded(history.entry(path).or_default(), current_content);
+
+        Ok(CallToolResult::success(vec![Content::text(
+            "Redid the last undone edit",
+        )]))
+    }
+
+    /// List the saved versions of a file, or restore one of them when `version` is given.
+    /// Version 1 is the most recent snapshot, the one `undo_edit` would restore.
+    pub async fn restore_version(
+        &self,
+        path: String,
+        version: Option<usize>,
+    ) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_writable(&path)?;
+
+        let history = self.file_history.lock().unwrap();
+        let versions = history.get(&path).map(Vec::as_slice).unwrap_or_default();
+        if versions.is_empty() {
+            return Err(McpError::invalid_params(
+                "No edit history available to restore".to_string(),
+                None,
+            ));
+        }
+
+        let Some(version) = version else {
+            let listing = versions
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(index, snapshot)| {
+                    let age = snapshot.saved_at.elapsed().unwrap_or_default().as_secs();
+                    format!(
+                        "  {version}: {size} bytes, saved {age}s ago",
+                        version = index + 1,
+                        size = snapshot.content.len()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let output = format!(
+                "Saved versions of {display} (1 is the most recent):\n{listing}\nPass `version` to restore one.",
+                display = path.display()
+            );
+            return Ok(CallToolResult::success(vec![
+                Content::text(output.clone()).with_audience(vec![Role::Assistant]),
+                Content::text(output)
+                    .with_audience(vec![Role::User])
+                    .with_priority(0.0),
+            ]));
+        };
+
+        if version == 0 || version > versions.len() {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Version {version} does not exist, {display} has versions 1 to {count}",
+                    display = path.display(),
+                    count = versions.len()
+                ),
+                None,
+            ));
+        }
+        let snapshot = versions[versions.len() - version].clone();
+        drop(history);
+
+        // Save the current state so the jump itself can be undone
+        self.save_file_history(&path)?;
+
+        self.restore_snapshot(&path, &snapshot)?;
+        self.refresh_view_record(&path);
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Restored version {version} of {display}. Use undo_edit to return to the state before the restore.",
+            display = path.display()
+        ))]))
+    }
+
+    /// Report whether a file changed since it was last viewed in this session
+    pub async fn check_unchanged(&self, path: String) -> Result<CallToolResult, McpError> {
+        let path = PathBuf::from(path);
+
+        // Check ignore patterns first
+        self.check_ignore_patterns(&path)?;
+
+        let record = self.view_records.lock().unwrap().get(&path).cloned();
+        let Some(record) = record else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "{display} has not been viewed in this session, view it before editing.",
+                display = path.display()
+            ))]));
+        };
+
+        let message = match std::fs::read(&path) {
+            Err(_) => format!(
+                "{display} no longer exists or can't be read, it was removed or renamed since it was last viewed.",
+                display = path.display()
+            ),
+            Ok(bytes) if self.text_hash(&path, &bytes) == record.hash => format!(
+                "{display} is unchanged since it was last viewed {age}s ago.",
+                display = path.display(),
+                age = record.viewed_at.elapsed().unwrap_or_default().as_secs()
+            ),
+            Ok(_) => {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                let when = match modified.and_then(|m| m.duration_since(record.viewed_at).ok()) {
+                    Some(after) => format!(", modified {}s after it was viewed", after.as_secs()),
+                    None => String::new(),
+                };
+                format!(
+                    "{display} has changed since it was last viewed{when}. View it again before editing.",
+                    display = path.display()
+                )
+            }
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(message)]))
+    }
+
+    // The dominant line ending of existing content, or the platform default for new files
+    fn line_ending_for(existing: Option<&str>) -> LineEnding {
+        existing
+            .and_then(LineEnding::detect)
+            .unwrap_or_else(LineEnding::platform)
+    }
+
+    // The dominant line ending at the start of a file, or the platform default when the
+    // start can't be read or decoded
+    fn sampled_line_ending(&self, path: &Path) -> LineEnding {
+        use std::io::Read;
+
+        let mut sample = Vec::new();
+        let read = std::fs::File::open(path)
+            .and_then(|file| file.take(LINE_ENDING_SAMPLE_BYTES).read_to_end(&mut sample));
+        if read.is_err() {
+            return LineEnding::platform();
+        }
+        // Not the last input, so a character cut off at the end of the sample isn't an error
+        let mut decoder = self
+            .file_encoding(path, Some(&sample))
+            .encoding
+            .new_decoder_with_bom_removal();
+        let Some(capacity) = decoder.max_utf8_buffer_length_without_replacement(sample.len())
+        else {
+            return LineEnding::platform();
+        };
+        let mut text = String::with_capacity(capacity);
+        match decoder.decode_to_string_without_replacement(&sample, &mut text, false) {
+            (DecoderResult::InputEmpty, _) => Self::line_ending_for(Some(&text)),
+            _ => LineEnding::platform(),
+        }
+    }
+
+    /// Read and write `path` in the given encoding (e.g. `utf-16le`, `latin1`) instead of
+    /// detecting it, for files without a byte order mark. Labels follow the WHATWG
+    /// Encoding Standard, so `latin1` and `iso-8859-1` mean windows-1252
+    pub fn set_encoding(&self, path: &Path, label: &str) -> Result<(), McpError> {
+        let encoding = Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+            McpError::invalid_params(
+                format!("Unknown encoding '{label}', use e.g. utf-8, utf-16le, utf-16be or latin1"),
+                None,
+            )
+        })?;
+        let mut encodings = self.encodings.lock().unwrap();
+        if encodings.len() >= MAX_CHOSEN_ENCODINGS && !encodings.contains_key(path) {
+            encodings.retain(|chosen, _| chosen.exists());
+            if encodings.len() >= MAX_CHOSEN_ENCODINGS {
+                return Err(McpError::invalid_request(
+                    format!(
+                        "Encodings are already chosen for {MAX_CHOSEN_ENCODINGS} files, restart the server to choose more"
+                    ),
+                    None,
+                ));
+            }
+        }
+        encodings.insert(path.to_path_buf(), encoding);
+        Ok(())
+    }
+
+    // The chosen encoding, or one detected from a byte order mark, or UTF-8. New files in
+    // UTF-16 get a byte order mark, as Windows tools expect
+    fn file_encoding(&self, path: &Path, existing: Option<&[u8]>) -> FileEncoding {
+        let chosen = self.encodings.lock().unwrap().get(path).copied();
+        let detected = existing
+            .and_then(Encoding::for_bom)
+            .map(|(encoding, _)| encoding);
+        match (chosen, detected) {
+            (Some(encoding), detected) => FileEncoding {
+                encoding,
+                bom: match existing {
+                    Some(_) => detected == Some(encoding),
+                    None => encoding == UTF_16LE || encoding == UTF_16BE,
+                },
+            },
+            (None, Some(encoding)) => FileEncoding {
+                encoding,
+                bom: true,
+            },
+            (None, None) => FileEncoding {
+                encoding: UTF_8,
+                bom: false,
+            },
+        }
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String, McpError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+        let encoding = self.file_encoding(path, Some(&bytes));
+        encoding.decode(&bytes).ok_or_else(|| {
+            McpError::invalid_params(
+                format!(
+                    "Failed to read file: '{display}' is not valid {name} text, set encoding if it uses another one",
+                    display = path.display(),
+                    name = encoding.encoding.name()
+                ),
+                None,
+            )
+        })
+    }
+
+    // Write text in the file's existing encoding, so UTF-16 files stay UTF-16
+    fn write_file(&self, path: &Path, text: &str) -> Result<(), McpError> {
+        self.write_file_in(path, text, None)
+    }
+
+    // Put a saved state back, recreating a deleted file in the encoding it had when saved
+    fn restore_snapshot(&self, path: &Path, snapshot: &Snapshot) -> Result<(), McpError> {
+        self.write_file_in(path, &snapshot.content, snapshot.encoding)
+    }
+
+    // A missing file is written in `new_file_encoding` when given
+    fn write_file_in(
+        &self,
+        path: &Path,
+        text: &str,
+        new_file_encoding: Option<FileEncoding>,
+    ) -> Result<(), McpError> {
+        let existing = std::fs::read(path).ok();
+        let encoding = match (&existing, new_file_encoding) {
+            (None, Some(encoding)) => encoding,
+            (existing, _) => self.file_encoding(path, existing.as_deref()),
+        };
+        let bytes = encoding.encode(text)?;
+        let result = if existing.is_some() {
+            Self::replace_atomically(path, &bytes)
+        } else {
+            std::fs::write(path, bytes)
+        };
+        result.map_err(|e| McpError::internal_error(format!("Failed to write file: {e}"), None))
+    }
+
+    // Write a sibling temp file and rename it over the target, so a crash mid-write never
+    // leaves a truncated file and watchers see a single change
+    fn replace_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        // Write through symlinks rather than replacing them
+        let target = std::fs::canonicalize(path)?;
+        let permissions = std::fs::metadata(&target)?.permissions();
+        let directory = target.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut temp_file = tempfile::NamedTempFile::new_in(directory)?;
+        std::io::Write::write_all(&mut temp_file, bytes)?;
+        temp_file.as_file().sync_all()?;
+        std::fs::set_permissions(temp_file.path(), permissions)?;
+        temp_file.persist(&target).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    // Hash the decoded text, so views and edits compare equal whatever the encoding
+    fn text_hash(&self, path: &Path, bytes: &[u8]) -> u64 {
+        match self.file_encoding(path, Some(bytes)).decode(bytes) {
+            Some(text) => Self::content_hash(text.as_bytes()),
+            None => Self::content_hash(bytes),
+        }
+    }
+
+    fn content_hash(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn record_view(&self, path: &Path, content: &[u8]) {
+        let mut records = self.view_records.lock().unwrap();
+        records.insert(
+            path.to_path_buf(),
+            ViewRecord {
+                hash: Self::content_hash(content),
+                viewed_at: SystemTime::now(),
+            },
+        );
+
+        // Keep the tracking bounded by forgetting the least recently viewed file
+        if records.len() > MAX_TRACKED_VIEWS {
+            let oldest = records
+                .iter()
+                .min_by_key(|(_, record)| record.viewed_at)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                records.remove(&oldest);
+            }
+        }
+    }
+
+    // The editor's own edits don't count as external changes
+    fn refresh_view_record(&self, path: &Path) {
+        let mut records = self.view_records.lock().unwrap();
+        if let Some(record) = records.get_mut(path) {
+            match std::fs::read(path) {
+                Ok(bytes) => record.hash = self.text_hash(path, &bytes),
+                Err(_) => {
+                    records.remove(path);
+                }
+            }
+        }
+    }
+
+    /// Build a pattern matching `old_str` with any run of whitespace standing in for another.
+    /// Leading and trailing whitespace match the file's indentation and line breaks there, so
+    /// a `new_str` with its own indentation and final newline replaces them instead of doubling them
+    fn whitespace_insensitive_pattern(old_str: &str) -> Result<Regex, McpError> {
+        let tokens: Vec<String> = old_str.split_whitespace().map(regex::escape).collect();
+        if tokens.is_empty() {
+            return Err(McpError::invalid_params(
+                "'old_str' must contain non-whitespace characters when matching whitespace-insensitively"
+                    .to_string(),
+                None,
+            ));
+        }
+        let leading = &old_str[..old_str.len() - old_str.trim_start().len()];
+        let trailing = &old_str[old_str.trim_end().len()..];
+        // Indentation alone only matches from the start of a line
+        let prefix = match leading {
+            "" => String::new(),
+            indent if !indent.contains('\n') => r"(?m:^)[ \t]*".to_string(),
+            leading => Self::line_break_pattern(leading),
+        };
+        let pattern = format!(
+            "{prefix}{body}{suffix}",
+            body = tokens.join(r"\s+"),
+            suffix = Self::line_break_pattern(trailing)
+        );
+        Regex::new(&pattern).map_err(|e| {
+            McpError::internal_error(format!("Failed to build match pattern: {e}"), None)
+        })
+    }
+
+    // Matches the same number of line breaks as `whitespace`, with any spaces or tabs around
+    // them, but no indentation after the last one unless `whitespace` has some there
+    fn line_break_pattern(whitespace: &str) -> String {
+        if whitespace.is_empty() {
+            return String::new();
+        }
+        whitespace
+            .split('\n')
+            .enumerate()
+            .map(|(index, spaces)| {
+                if index == 0 || !spaces.trim_end_matches('\r').is_empty() {
+                    r"[ \t]*"
+                } else {
+                    ""
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(r"\r?\n")
+    }
+
+    /// Whether the match at `start..end` doesn't continue a word at either edge, like a regex `\b`
+    fn is_whole_word(content: &str, start: usize, end: usize) -> bool {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let matched = &content[start..end];
+        let starts_clean = !matched.chars().next().is_some_and(is_word)
+            || !content[..start].chars().next_back().is_some_and(is_word);
+        let ends_clean = !matched.chars().next_back().is_some_and(is_word)
+            || !content[end..].chars().next().is_some_and(is_word);
+        starts_clean && ends_clean
+    }
+
+    fn save_file_history(&self, path: &PathBuf) -> Result<(), McpError> {
+        if path.is_dir() {
+            // Don't save history for directories
+            return Ok(());
+        }
+        let snapshot = self.snapshot(path)?;
+
+        let mut history = self.file_history.lock().unwrap();
+        self.push_bounded(history.entry(path.clone()).or_default(), snapshot);
+
+        // A fresh edit starts a new branch, so anything undone before it can't be redone
+        self.redo_history.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<Snapshot, McpError> {
+        use std::io::Read;
+
+        let (content, encoding) = if path.exists() {
+            let mut bom = Vec::new();
+            std::fs::File::open(path)
+                .and_then(|file| file.take(3).read_to_end(&mut bom))
+                .map_err(|e| McpError::internal_error(format!("Failed to read file: {e}"), None))?;
+            (
+                self.read_file(path)?,
+                Some(self.file_encoding(path, Some(&bom))),
+            )
+        } else {
+            (String::new(), None) // Represents a non-existent file
+        };
+        Ok(Snapshot {
+            content,
+            encoding,
+            saved_at: SystemTime::now(),
+        })
+    }
+
+    fn push_bounded(&self, file_specific_history: &mut Vec<Snapshot>, snapshot: Snapshot) {
+        file_specific_history.push(snapshot);
+
+        // Enforce history limit
+        if file_specific_history.len() > self.max_history_per_file && self.max_history_per_file > 0
+        {
+            let excess = file_specific_history.len() - self.max_history_per_file;
+            file_specific_history.drain(0..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::gitignore::GitignoreBuilder;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_text_editor_write_and_view_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        let editor = TextEditor::new();
+
+        // Create a new file
+        let result = editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "Hello, world!".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+
+        // View the file
+        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
+        assert!(view_result.is_ok());
+        let content = view_result.unwrap().content;
+        assert!(!content.is_empty());
+        let text = content[0].as_text().unwrap();
+        assert!(text.text.contains("Hello, world!"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_str_replace() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        let editor = TextEditor::new();
+
+        // Create a new file
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "Hello, world!".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Replace string
+        let replace_result = editor
+            .str_replace(
+                test_file.to_string_lossy().to_string(),
+                "world".to_string(),
+                "Rust".to_string(),
+            )
+            .await;
+        assert!(replace_result.is_ok());
+
+        // View the file to verify the change
+        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
+        let call_result = view_result.unwrap();
+        let text = call_result.content[0].as_text().unwrap();
+        assert!(text.text.contains("Hello, Rust!"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_undo_edit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        let editor = TextEditor::new();
+
+        // Create a new file
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "First line".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Replace string
+        editor
+            .str_replace(
+                test_file.to_string_lossy().to_string(),
+                "First line".to_string(),
+                "Second line".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Undo the edit
+        let undo_result = editor
+            .undo_edit(test_file.to_string_lossy().to_string())
+            .await;
+        assert!(undo_result.is_ok());
+
+        // View the file to verify the undo
+        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
+        let call_result = view_result.unwrap();
+        let text = call_result.content[0].as_text().unwrap();
+        assert!(text.text.contains("First line"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_size_limits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let large_file = temp_dir.path().join("large.txt");
+
+        // Create a file larger than 400KB
+        let mut file = std::fs::File::create(&large_file).unwrap();
+        let large_data = "x".repeat(500 * 1024); // 500KB
+        file.write_all(large_data.as_bytes()).unwrap();
+
+        let editor = TextEditor::new();
+        let result = editor.read_text(&large_file);
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("too large"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_configured_size_limits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let large_file = temp_dir.path().join("large.txt");
+        std::fs::write(&large_file, "x".repeat(500 * 1024)).unwrap();
+
+        // A raised byte limit lets the file through to the character check
+        let mut editor = TextEditor::new();
+        editor.max_file_bytes = 1024 * 1024;
+        let result = editor.view(large_file.to_string_lossy().to_string()).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Maximum character count is 400000")
+        );
+
+        editor.max_chars = 600_000;
+        let result = editor.view(large_file.to_string_lossy().to_string()).await;
+        assert!(result.is_ok());
+
+        // Errors report the configured maximum
+        editor.max_file_bytes = 100 * 1024;
+        let result = editor.read_text(&large_file);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Maximum size is 100.00KB")
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_nonexistent_file() {
+        let editor = TextEditor::new();
+        let result = editor.view("/nonexistent/file.txt".to_string()).await;
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("does not exist"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_editor_with_ignore_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Create ignore patterns
+        let mut builder = GitignoreBuilder::new(temp_dir.path());
+        builder.add_line(None, "secret.txt").unwrap();
+        builder.add_line(None, "*.env").unwrap();
+        let ignore_patterns = IgnorePatterns::from(builder.build().unwrap());
+
+        let editor = TextEditor::new().with_ignore_patterns(ignore_patterns);
+
+        // Create ignored files
+        let secret_file = temp_dir.path().join("secret.txt");
+        let env_file = temp_dir.path().join("test.env");
+        let normal_file = temp_dir.path().join("normal.txt");
+
+        // Try to write to ignored files
+        let result = editor
+            .write(
+                secret_file.to_string_lossy().to_string(),
+                "secret content".to_string(),
+            )
+            .await;
+        assert!(
+            result.is_err(),
+            "Should not be able to write to ignored file"
+        );
+        if let Err(e) = result {
+            assert!(e.to_string().contains("restricted by ignore patterns"));
+        }
+
+        let result = editor
+            .write(
+                env_file.to_string_lossy().to_string(),
+                "env content".to_string(),
+            )
+            .await;
+        assert!(
+            result.is_err(),
+            "Should not be able to write to ignored file"
+        );
+
+        // Should be able to write to normal file
+        let result = editor
+            .write(
+                normal_file.to_string_lossy().to_string(),
+                "normal content".to_string(),
+            )
+            .await;
+        assert!(result.is_ok(), "Should be able to write to normal file");
+
+        // Create the secret file externally and try to view it
+        std::fs::write(&secret_file, "secret content").unwrap();
+        let result = editor.view(secret_file.to_string_lossy().to_string()).await;
+        assert!(result.is_err(), "Should not be able to view ignored file");
+
+        // Should be able to view normal file
+        let result = editor.view(normal_file.to_string_lossy().to_string()).await;
+        assert!(result.is_ok(), "Should be able to view normal file");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_undo_functionality() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        let editor = TextEditor::new();
+
+        // Write initial content
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "Initial content".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Write new content (should be undoable)
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "New content".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Verify new content
+        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
+        let call_result = view_result.unwrap();
+        let text = call_result.content[0].as_text().unwrap();
+        assert!(text.text.contains("New content"));
+
+        // Undo the write
+        let undo_result = editor
+            .undo_edit(test_file.to_string_lossy().to_string())
+            .await;
+        assert!(undo_result.is_ok());
+
+        // Verify content reverted
+        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
+        let call_result = view_result.unwrap();
+        let text = call_result.content[0].as_text().unwrap();
+        assert!(text.text.contains("Initial content"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_to_directory_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir_path = temp_dir.path().join("test_dir");
+        std::fs::create_dir(&dir_path).unwrap();
+
+        let editor = TextEditor::new();
+
+        // Try to write to a directory
+        let result = editor
+            .write(
+                dir_path.to_string_lossy().to_string(),
+                "content".to_string(),
+            )
+            .await;
+
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("is an existing directory"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_character_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        let editor = TextEditor::new();
+
+        // Create content exceeding the character limit
+        let large_content = "x".repeat(DEFAULT_MAX_CHARS + 1);
+
+        let result = editor
+            .write(test_file.to_string_lossy().to_string(), large_content)
+            .await;
+
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("too many characters"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+
+        // Create editor with small history limit
+        let editor = TextEditor::new_with_history_limit(2);
+
+        // Write initial content
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "Content 1".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Make multiple edits to exceed history limit
+        for i in 2..=5 {
+            editor
+                .str_replace(
+                    test_file.to_string_lossy().to_string(),
+                    format!("Content {prev}", prev = i - 1),
+                    format!("Content {i}"),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Should only be able to undo 2 times (the limit)
+        for _ in 0..2 {
+            let undo_result = editor
+                .undo_edit(test_file.to_string_lossy().to_string())
+                .await;
+            assert!(undo_result.is_ok());
+        }
+
+        // Third undo should fail
+        let undo_result = editor
+            .undo_edit(test_file.to_string_lossy().to_string())
+            .await;
+        assert!(undo_result.is_err());
+        if let Err(e) = undo_result {
+            assert!(e.to_string().contains("No edit history available"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_write_to_new_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("new_file.txt");
+
+        let editor = TextEditor::new();
+
+        // Write to a new file
+        editor
+            .write(
+                test_file.to_string_lossy().to_string(),
+                "New file content".to_string(),
+            )
+            .await
+            .unwrap();
+
+        // Verify file exists and has content
+        assert!(test_file.exists());
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "New file content");
+
+        // Undo the write
+        let undo_result = editor
+            .undo_edit(test_file.to_string_lossy().to_string())
+            .await;
+        assert!(undo_result.is_ok());
+
+        // File should now be empty (representing the non-existent state)
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert!(content.is_empty());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_redo_after_undo() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let path = test_file.to_string_lossy().to_string();
+
+        let editor = TextEditor::new();
+
+        // Nothing to redo before any undo
+        let result = editor.redo(path.clone()).await;
+        assert!(result.unwrap_err().to_string().contains("No undone edit"));
+
+        editor
+            .write(path.clone(), "first".to_string())
+            .await
+            .unwrap();
+        editor
+            .write(path.clone(), "second".to_string())
+            .await
+            .unwrap();
+
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "first");
+
+        editor.redo(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "second");
+
+        // The redone edit can be undone again
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "first");
+
+        // A fresh edit discards the redo stack
+        editor
+            .write(path.clone(), "third".to_string())
+            .await
+            .unwrap();
+        assert!(editor.redo(path.clone()).await.is_err());
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "third");
+
+        // A snapshot that fails to write back is kept for another try
+        editor
+            .write(path.clone(), "漢字".to_string())
+            .await
+            .unwrap();
+        editor
+            .write(path.clone(), "fourth".to_string())
+            .await
+            .unwrap();
+        editor.set_encoding(&test_file, "latin1").unwrap();
+        assert!(editor.undo_edit(path.clone()).await.is_err());
+        editor.set_encoding(&test_file, "utf-8").unwrap();
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "漢字");
+        editor.undo_edit(path.clone()).await.unwrap();
+        editor.set_encoding(&test_file, "latin1").unwrap();
+        assert!(editor.redo(path.clone()).await.is_err());
+        editor.set_encoding(&test_file, "utf-8").unwrap();
+        editor.redo(path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "漢字");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_all() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "original\n").unwrap();
+
+        let editor = TextEditor::new();
+        let result = editor.undo_all(path.clone()).await;
+        assert!(result.unwrap_err().to_string().contains("No edit history"));
+
+        editor
+            .str_replace(path.clone(), "original".into(), "one".into())
+            .await
+            .unwrap();
+        editor.insert(path.clone(), 1, "two".into()).await.unwrap();
+        editor
+            .write(path.clone(), "three\n".to_string())
+            .await
+            .unwrap();
+
+        editor.undo_all(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "original\n");
+
+        // The history is gone, so there is nothing left to undo or redo
+        let result = editor.undo_edit(path.clone()).await;
+        assert!(result.unwrap_err().to_string().contains("No edit history"));
+        assert!(editor.redo(path.clone()).await.is_err());
+        assert!(editor.undo_all(path).await.is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_whitespace_insensitive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.py");
+        std::fs::write(
+            &test_file,
+            "def main():\n    if ready:\n        run()\n    done()\n",
+        )
+        .unwrap();
+
+        let editor = TextEditor::new();
+
+        // Exact matching fails because the indentation differs
+        let result = editor
+            .str_replace(
+                test_file.to_string_lossy().to_string(),
+                "if ready:\n  run()".to_string(),
+                "if ready:\n        start()".to_string(),
+            )
+            .await;
+        assert!(result.is_err());
+
+        let result = editor
+            .str_replace_with_options(
+                test_file.to_string_lossy().to_string(),
+                "if ready:\n  run()".to_string(),
+                "if ready:\n        start()".to_string(),
+                StrReplaceOptions {
+                    whitespace_insensitive: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.is_ok());
+
+        // The indentation before the matched span is preserved
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert_eq!(
+            content,
+            "def main():\n    if ready:\n        start()\n    done()\n"
+        );
+
+        // Indented blocks ending in a newline replace the file's indentation and line break
+        std::fs::write(
+            &test_file,
+            "def main():\n    if ready:\n        run()\n    done()\n",
+        )
+        .unwrap();
+        editor
+            .str_replace_with_options(
+                test_file.to_string_lossy().to_string(),
+                "  if ready:\n      run()\n".to_string(),
+                "    if ready:\n        start()\n        run()\n".to_string(),
+                StrReplaceOptions {
+                    whitespace_insensitive: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "def main():\n    if ready:\n        start()\n        run()\n    done()\n"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_whitespace_insensitive_requires_unique_match() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, "a  b\na\tb\n").unwrap();
+
+        let editor = TextEditor::new();
+        let result = editor
+            .str_replace_with_options(
+                test_file.to_string_lossy().to_string(),
+                "a b".to_string(),
+                "c".to_string(),
+                StrReplaceOptions {
+                    whitespace_insensitive: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert!(e.to_string().contains("appears multiple times"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_regex_replace() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("lib.rs");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(
+            &test_file,
+            "fn old_name() {}\r\nfn caller() { old_name(); old_names(); }\r\n",
+        )
+        .unwrap();
+        let editor = TextEditor::new();
+
+        // Several matches need `all`
+        let result = editor
+            .regex_replace(
+                path.clone(),
+                r"\bold_name\b".to_string(),
+                "new_name".to_string(),
+                false,
+            )
+            .await;
+        assert!(result.unwrap_err().message.contains("matches 2 times"));
+
+        let result = editor
+            .regex_replace(
+                path.clone(),
+                r"\bold_(name)\b".to_string(),
+                "new_$1".to_string(),
+                true,
+            )
+            .await
+            .unwrap();
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .starts_with("Replaced 2 matches")
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[3].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            (report["replacements"].as_u64(), report["line"].as_u64()),
+            (Some(2), Some(1))
+        );
+        // Word boundaries leave old_names alone, and CRLF endings are kept
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "fn new_name() {}\r\nfn caller() { new_name(); old_names(); }\r\n"
+        );
+
+        // A single match is replaced without `all`
+        editor
+            .regex_replace(
+                path.clone(),
+                r"fn (\w+)\(\) \{ ".to_string(),
+                "fn ${1}_renamed() { ".to_string(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(
+            std::fs::read_to_string(&test_file)
+                .unwrap()
+                .contains("fn caller_renamed() {")
+        );
+
+        let result = editor
+            .regex_replace(path.clone(), "(".to_string(), "".to_string(), false)
+            .await;
+        assert!(result.unwrap_err().message.contains("Invalid regex"));
+        let result = editor
+            .regex_replace(path.clone(), "absent".to_string(), "".to_string(), true)
+            .await;
+        assert!(result.unwrap_err().message.contains("does not match"));
+
+        // Both edits can be undone
+        editor.undo_edit(path.clone()).await.unwrap();
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "fn old_name() {}\r\nfn caller() { old_name(); old_names(); }\r\n"
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_occurrence() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, "item\nitem\nitem\n").unwrap();
+
+        let editor = TextEditor::new();
+        let result = editor
+            .str_replace_with_options(
+                test_file.to_string_lossy().to_string(),
+                "item".to_string(),
+                "second".to_string(),
+                StrReplaceOptions {
+                    occurrence: Some(2),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "item\nsecond\nitem\n");
+
+        // The report names the 1-based line that changed
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[2].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "path": test_file.display().to_string(),
+                "replacements": 1,
+                "line": 2,
+            })
+        );
+
+        // Out of range indices report how many matches exist
+        for occurrence in [0, 4] {
+            let result = editor
+                .str_replace_with_options(
+                    test_file.to_string_lossy().to_string(),
+                    "item".to_string(),
+                    "other".to_string(),
+                    StrReplaceOptions {
+                        occurrence: Some(occurrence),
+                        ..Default::default()
+                    },
+                )
+                .await;
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("between 1 and 2"));
+        }
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_whole_word() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.rs");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "let counter = 1;\nlet count = counter + 1;\n").unwrap();
+
+        let editor = TextEditor::new();
+        let whole_word = || StrReplaceOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        // Matches inside `counter` don't count, leaving one real match
+        editor
+            .str_replace_with_options(path.clone(), "count".into(), "total".into(), whole_word())
+            .await
+            .unwrap();
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "let counter = 1;\nlet total = counter + 1;\n");
+
+        // A word character at an edge must not continue a word, other edges need no boundary
+        editor
+            .str_replace_with_options(path.clone(), "= c".into(), "= C".into(), whole_word())
+            .await
+            .unwrap_err();
+        editor
+            .str_replace_with_options(path.clone(), "+ 1;".into(), "+ 2;".into(), whole_word())
+            .await
+            .unwrap();
+
+        // A match that is only ever part of a longer word is an error, not a replacement
+        let error = editor
+            .str_replace_with_options(path.clone(), "ounte".into(), "x".into(), whole_word())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("inside longer words (first at line 1)"));
+        let content = std::fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "let counter = 1;\nlet total = counter + 2;\n");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_insert() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "one\ntwo\nthree").unwrap();
+
+        let editor = TextEditor::new();
+
+        editor
+            .insert(path.clone(), 0, "zero".to_string())
+            .await
+            .unwrap();
+        let result = editor
+            .insert(path.clone(), 2, "one and a half\n".to_string())
+            .await
+            .unwrap();
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .contains("one and a half")
+        );
+        editor
+            .insert(path.clone(), 5, "four".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "zero\none\none and a half\ntwo\nthree\nfour\n"
+        );
+
+        // Lines past the end of the file are rejected
+        let result = editor.insert(path.clone(), 10, "nope".to_string()).await;
+        assert!(result.unwrap_err().to_string().contains("has 6 lines"));
+
+        // Insertions are undoable
+        editor.undo_edit(path).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "zero\none\none and a half\ntwo\nthree"
+        );
 
-        // Ensure 'old_str' appears exactly once
-        if content.matches(&old_str).count() > 1 {
-            return Err(McpError::invalid_params(
-                "'old_str' must appear exactly once in the file, but it appears multiple times"
-                    .to_string(),
-                None,
-            ));
-        }
-        if content.matches(&old_str).count() == 0 {
-            return Err(McpError::invalid_params(
-                "'old_str' must appear exactly once in the file, but it does not appear in the file. Make sure the string exactly matches existing file content, including whitespace!".to_string(),
-                None,
-            ));
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::write(root.join("lib.rs"), "fn alpha() {}\nfn beta() {}\n").unwrap();
+        std::fs::create_dir(root.join("secret")).unwrap();
+        std::fs::write(root.join("secret/keys.rs"), "fn alpha_key() {}\n").unwrap();
+
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(&root);
+        builder.add_line(None, "secret/").unwrap();
+        let editor = TextEditor::new().with_ignore_patterns(builder.build().unwrap().into());
+
+        let result = editor
+            .search(root.to_string_lossy().to_string(), r"fn \w+\(".to_string())
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Found 2 matches"));
+        assert!(text.contains("lib.rs\n  1: fn alpha() {}\n  2: fn beta() {}"));
+        assert!(!text.contains("alpha_key"));
+
+        // Results are capped
+        let many = "hit\n".repeat(MAX_SEARCH_MATCHES + 10);
+        std::fs::write(root.join("many.txt"), many).unwrap();
+        let result = editor
+            .search(
+                root.join("many.txt").to_string_lossy().to_string(),
+                "hit".to_string(),
+            )
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Results truncated"));
+
+        let result = editor
+            .search(root.to_string_lossy().to_string(), "(".to_string())
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid search pattern")
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let path = test_file.to_string_lossy().to_string();
+
+        let editor = TextEditor::new();
+        for content in ["v1", "v2", "v3", "v4"] {
+            editor
+                .write(path.clone(), content.to_string())
+                .await
+                .unwrap();
         }
 
-        // Save history for undo
-        self.save_file_history(&path)?;
+        // Versions are listed newest first
+        let result = editor.restore_version(path.clone(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("1: 2 bytes"));
+        assert!(text.contains("4: 0 bytes"));
 
-        // Replace and write back with platform-specific line endings
-        let new_content = content.replace(&old_str, &new_str);
-        let normalized_content = normalize_line_endings(&new_content);
-        std::fs::write(&path, &normalized_content)
-            .map_err(|e| McpError::internal_error(format!("Failed to write file: {e}"), None))?;
+        // Jump back two edits at once
+        editor.restore_version(path.clone(), Some(2)).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "v2");
 
-        // Try to detect the language from the file extension
-        let language = lang::get_language_identifier(&path);
+        // The jump itself is undoable
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "v4");
 
-        // Show a snippet of the changed content with context
-        const SNIPPET_LINES: usize = 4;
+        let result = editor.restore_version(path, Some(9)).await;
+        assert!(result.unwrap_err().to_string().contains("versions 1 to 4"));
 
-        // Count newlines before the replacement to find the line number
-        let replacement_line = content
-            .split(&old_str)
-            .next()
-            .expect("should split on already matched content")
-            .matches('\n')
-            .count();
+        temp_dir.close().unwrap();
+    }
 
-        // Calculate start and end lines for the snippet
-        let start_line = replacement_line.saturating_sub(SNIPPET_LINES);
-        let end_line = replacement_line + SNIPPET_LINES + new_str.matches('\n').count();
+    #[tokio::test]
+    async fn test_write_append() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("log.txt");
+        let path = test_file.to_string_lossy().to_string();
+        let append = WriteOptions {
+            append: true,
+            ..Default::default()
+        };
 
-        // Get the relevant lines for our snippet
-        let lines: Vec<&str> = new_content.lines().collect();
-        let snippet = lines
-            .iter()
-            .skip(start_line)
-            .take(end_line - start_line + 1)
-            .cloned()
-            .collect::<Vec<&str>>()
-            .join("\n");
+        let editor = TextEditor::new();
 
-        let output = format!("```{language}\n{snippet}\n```");
+        // Appending to a missing file creates it
+        editor
+            .write_with_options(path.clone(), "first\n".to_string(), append.clone())
+            .await
+            .unwrap();
+        editor
+            .write_with_options(path.clone(), "second\n".to_string(), append.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "first\nsecond\n"
+        );
 
-        let success_message = format!(
-            "The file {display} has been edited, and the section now reads:\n{output}\nReview the changes above for errors. Undo and edit the file again if necessary!",
-            display = path.display()
+        // A single undo reverts only the last append
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "first\n");
+
+        // The limit applies to the combined content
+        let mut editor = TextEditor::new();
+        editor.max_chars = 10;
+        let result = editor
+            .write_with_options(path, "0123456789".to_string(), append)
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("too many characters (16)")
         );
 
-        Ok(CallToolResult::success(vec![
-            Content::text(success_message).with_audience(vec![Role::Assistant]),
-            Content::text(output)
-                .with_audience(vec![Role::User])
-                .with_priority(0.2),
-        ]))
+        temp_dir.close().unwrap();
     }
 
-    pub async fn undo_edit(&self, path: String) -> Result<CallToolResult, McpError> {
-        let path = PathBuf::from(path);
-
-        // Check ignore patterns first
-        self.check_ignore_patterns(&path)?;
+    #[tokio::test]
+    async fn test_mkdir_and_list_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(&root);
+        builder.add_line(None, "secret.txt").unwrap();
+        builder.add_line(None, "build/").unwrap();
+        let editor = TextEditor::new().with_ignore_patterns(builder.build().unwrap().into());
+        let path_of = |name: &str| root.join(name).to_string_lossy().to_string();
+
+        editor.mkdir(path_of("src/nested/deep")).await.unwrap();
+        assert!(root.join("src/nested/deep").is_dir());
+        // Creating it again is fine, ignored directories are refused
+        editor.mkdir(path_of("src/nested/deep")).await.unwrap();
+        let result = editor.mkdir(path_of("build")).await;
+        assert!(result.unwrap_err().to_string().contains("ignore patterns"));
+
+        std::fs::write(root.join("b.txt"), "12345").unwrap();
+        std::fs::write(root.join("secret.txt"), "hidden").unwrap();
+        std::fs::create_dir(root.join("build")).unwrap();
+        let result = editor.mkdir(path_of("b.txt")).await;
+        assert!(result.unwrap_err().to_string().contains("not a directory"));
+
+        let result = editor.list_dir(path_of("")).await.unwrap();
+        let listing: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(
+            listing["entries"],
+            serde_json::json!([
+                {"name": "b.txt", "type": "file", "size": 5},
+                {"name": "src", "type": "dir", "size": null},
+            ])
+        );
+        assert_eq!(listing["total"], 2);
+        assert!(editor.list_dir(path_of("b.txt")).await.is_err());
 
-        let mut history = self.file_history.lock().unwrap();
-        if let Some(contents) = history.get_mut(&path) {
-            if let Some(previous_content) = contents.pop() {
-                // Write previous content back to file
-                std::fs::write(&path, previous_content).map_err(|e| {
-                    McpError::internal_error(format!("Failed to write file: {e}"), None)
-                })?;
-                Ok(CallToolResult::success(vec![Content::text(
-                    "Undid the last edit",
-                )]))
-            } else {
-                Err(McpError::invalid_params(
-                    "No edit history available to undo".to_string(),
-                    None,
-                ))
-            }
-        } else {
-            Err(McpError::invalid_params(
-                "No edit history available to undo".to_string(),
-                None,
-            ))
-        }
+        temp_dir.close().unwrap();
     }
 
-    fn save_file_history(&self, path: &PathBuf) -> Result<(), McpError> {
-        let mut history = self.file_history.lock().unwrap();
-        let content = if path.exists() {
-            if path.is_dir() {
-                // Don't save history for directories
-                return Ok(());
-            }
-            std::fs::read_to_string(path).map_err(|e| {
-                McpError::internal_error(format!("Failed to read file for history: {e}"), None)
-            })?
-        } else {
-            String::new() // Represents a non-existent file
-        };
+    #[tokio::test]
+    async fn test_delete_move_copy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(temp_dir.path()).unwrap();
+        let source = root.join("source.txt");
+        std::fs::write(&source, "content").unwrap();
+        std::fs::write(root.join("secret.txt"), "hidden").unwrap();
 
-        let file_specific_history = history.entry(path.clone()).or_default();
-        file_specific_history.push(content);
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(&root);
+        builder.add_line(None, "secret.txt").unwrap();
+        let editor = TextEditor::new().with_ignore_patterns(builder.build().unwrap().into());
+        let path_of = |name: &str| root.join(name).to_string_lossy().to_string();
 
-        // Enforce history limit
-        if file_specific_history.len() > self.max_history_per_file && self.max_history_per_file > 0
-        {
-            let excess = file_specific_history.len() - self.max_history_per_file;
-            file_specific_history.drain(0..excess);
-        }
-        Ok(())
-    }
-}
+        editor
+            .copy_file(path_of("source.txt"), path_of("copies/copy.txt"))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("copies/copy.txt")).unwrap(),
+            "content"
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ignore::gitignore::GitignoreBuilder;
-    use std::io::Write;
+        editor
+            .move_path(path_of("source.txt"), path_of("moved.txt"))
+            .await
+            .unwrap();
+        assert!(!source.exists());
+
+        // Existing destinations and ignored paths on either side are rejected
+        let result = editor
+            .copy_file(path_of("moved.txt"), path_of("copies/copy.txt"))
+            .await;
+        assert!(result.unwrap_err().to_string().contains("already exists"));
+        let result = editor
+            .move_path(path_of("moved.txt"), path_of("secret.txt"))
+            .await;
+        assert!(result.unwrap_err().to_string().contains("ignore patterns"));
+
+        // A directory can't carry protected files along when moved
+        std::fs::create_dir_all(root.join("project")).unwrap();
+        std::fs::write(root.join("project/secret.txt"), "hidden").unwrap();
+        let result = editor
+            .move_path(path_of("project"), path_of("public/project"))
+            .await;
+        assert!(result.unwrap_err().to_string().contains("secret.txt"));
+        assert!(root.join("project/secret.txt").exists());
+        std::fs::remove_dir_all(root.join("project")).unwrap();
+
+        // Deleting a file is undoable
+        editor.delete(path_of("moved.txt"), false).await.unwrap();
+        assert!(!root.join("moved.txt").exists());
+        editor.undo_edit(path_of("moved.txt")).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("moved.txt")).unwrap(),
+            "content"
+        );
+
+        // Directories need recursive, and can't contain protected files
+        let result = editor.delete(path_of("copies"), false).await;
+        assert!(result.unwrap_err().to_string().contains("recursive"));
+        editor.delete(path_of("copies"), true).await.unwrap();
+        assert!(!root.join("copies").exists());
+        let result = editor
+            .delete(root.to_string_lossy().to_string(), true)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("secret.txt"));
+
+        temp_dir.close().unwrap();
+    }
 
     #[tokio::test]
-    async fn test_text_editor_write_and_view_file() {
+    async fn test_str_replace_show_diff() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
+        let test_file = temp_dir.path().join("test.rs");
+        std::fs::write(&test_file, "fn main() {\n    old();\n}\n").unwrap();
 
         let editor = TextEditor::new();
-
-        // Create a new file
         let result = editor
-            .write(
+            .str_replace_with_options(
                 test_file.to_string_lossy().to_string(),
-                "Hello, world!".to_string(),
+                "old();".to_string(),
+                "new();".to_string(),
+                StrReplaceOptions {
+                    show_diff: true,
+                    ..Default::default()
+                },
             )
-            .await;
-        assert!(result.is_ok());
+            .await
+            .unwrap();
 
-        // View the file
-        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
-        assert!(view_result.is_ok());
-        let content = view_result.unwrap().content;
-        assert!(!content.is_empty());
-        let text = content[0].as_text().unwrap();
-        assert!(text.text.contains("Hello, world!"));
+        // The assistant keeps the snippet, the user gets the diff
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .contains("section now reads")
+        );
+        let diff = &result.content[1].as_text().unwrap().text;
+        assert!(diff.starts_with("```diff\n"));
+        assert!(diff.contains("\n-    old();\n"));
+        assert!(diff.contains("\n+    new();\n"));
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_text_editor_str_replace() {
+    async fn test_check_unchanged() {
         let temp_dir = tempfile::tempdir().unwrap();
         let test_file = temp_dir.path().join("test.txt");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "original").unwrap();
 
         let editor = TextEditor::new();
+        let check = |editor: &TextEditor| {
+            let editor = editor.clone();
+            let path = path.clone();
+            async move {
+                let result = editor.check_unchanged(path).await.unwrap();
+                result.content[0].as_text().unwrap().text.clone()
+            }
+        };
 
-        // Create a new file
+        assert!(check(&editor).await.contains("has not been viewed"));
+
+        editor.view(path.clone()).await.unwrap();
+        assert!(check(&editor).await.contains("is unchanged"));
+
+        // The editor's own edits keep the file current
         editor
-            .write(
-                test_file.to_string_lossy().to_string(),
-                "Hello, world!".to_string(),
-            )
+            .str_replace(path.clone(), "original".to_string(), "edited".to_string())
             .await
             .unwrap();
+        assert!(check(&editor).await.contains("is unchanged"));
 
-        // Replace string
-        let replace_result = editor
-            .str_replace(
+        // Changes made behind the editor's back are reported
+        std::fs::write(&test_file, "external").unwrap();
+        assert!(check(&editor).await.contains("has changed"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_str_replace_context_lines() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        std::fs::write(&test_file, "a\nb\nc\nd\ne\nf\ng\n").unwrap();
+
+        let editor = TextEditor::new();
+        let replace = |old: &str, new: &str, context_lines| {
+            editor.str_replace_with_options(
                 test_file.to_string_lossy().to_string(),
-                "world".to_string(),
-                "Rust".to_string(),
+                old.to_string(),
+                new.to_string(),
+                StrReplaceOptions {
+                    context_lines,
+                    ..Default::default()
+                },
             )
-            .await;
-        assert!(replace_result.is_ok());
+        };
 
-        // View the file to verify the change
-        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
-        let call_result = view_result.unwrap();
-        let text = call_result.content[0].as_text().unwrap();
-        assert!(text.text.contains("Hello, Rust!"));
+        // Only the changed line
+        let result = replace("d", "D", Some(0)).await.unwrap();
+        assert_eq!(result.content[1].as_text().unwrap().text, "```\nD\n```");
+
+        let result = replace("D", "d", Some(1)).await.unwrap();
+        assert_eq!(
+            result.content[1].as_text().unwrap().text,
+            "```\nc\nd\ne\n```"
+        );
+
+        // The default keeps four lines either side
+        let result = replace("d", "D", None).await.unwrap();
+        assert_eq!(
+            result.content[1].as_text().unwrap().text,
+            "```\na\nb\nc\nD\ne\nf\ng\n```"
+        );
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_text_editor_undo_edit() {
+    async fn test_edits_preserve_line_endings() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
+        let lf_file = temp_dir.path().join("lf.txt");
+        let crlf_file = temp_dir.path().join("crlf.txt");
+        std::fs::write(&lf_file, "one\ntwo\nthree\n").unwrap();
+        std::fs::write(&crlf_file, "one\r\ntwo\r\nthree\r\n").unwrap();
 
         let editor = TextEditor::new();
 
-        // Create a new file
+        // LF files stay LF whatever the platform default is
+        editor
+            .str_replace(
+                lf_file.to_string_lossy().to_string(),
+                "two\n".to_string(),
+                "2\nand a bit\n".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&lf_file).unwrap(),
+            "one\n2\nand a bit\nthree\n"
+        );
         editor
             .write(
-                test_file.to_string_lossy().to_string(),
-                "First line".to_string(),
+                lf_file.to_string_lossy().to_string(),
+                "a\r\nb\n".to_string(),
             )
             .await
             .unwrap();
+        assert_eq!(std::fs::read_to_string(&lf_file).unwrap(), "a\nb\n");
 
-        // Replace string
+        // CRLF files stay CRLF
         editor
             .str_replace(
-                test_file.to_string_lossy().to_string(),
-                "First line".to_string(),
-                "Second line".to_string(),
+                crlf_file.to_string_lossy().to_string(),
+                "two".to_string(),
+                "2\nand a bit".to_string(),
             )
             .await
             .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&crlf_file).unwrap(),
+            "one\r\n2\r\nand a bit\r\nthree\r\n"
+        );
 
-        // Undo the edit
-        let undo_result = editor
-            .undo_edit(test_file.to_string_lossy().to_string())
-            .await;
-        assert!(undo_result.is_ok());
-
-        // View the file to verify the undo
-        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
-        let call_result = view_result.unwrap();
-        let text = call_result.content[0].as_text().unwrap();
-        assert!(text.text.contains("First line"));
-
-        temp_dir.close().unwrap();
-    }
-
-    #[tokio::test]
-    async fn test_text_editor_size_limits() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let large_file = temp_dir.path().join("large.txt");
-
-        // Create a file larger than 400KB
-        let mut file = std::fs::File::create(&large_file).unwrap();
-        let large_data = "x".repeat(500 * 1024); // 500KB
-        file.write_all(large_data.as_bytes()).unwrap();
+        // Replacing only samples the start, a character cut off by the sample doesn't matter
+        let line = format!("{}é\r\n", "x".repeat(40));
+        std::fs::write(&crlf_file, line.repeat(4000)).unwrap();
+        editor
+            .write(
+                crlf_file.to_string_lossy().to_string(),
+                "a\nb\n".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&crlf_file).unwrap(), "a\r\nb\r\n");
 
-        let editor = TextEditor::new();
-        let result = editor.view(large_file.to_string_lossy().to_string()).await;
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert!(e.to_string().contains("too large"));
-        }
+        // Content that doesn't decode is replaced without an undo snapshot
+        std::fs::write(&lf_file, b"broken \xc3\x28\r\n").unwrap();
+        let result = editor
+            .write(lf_file.to_string_lossy().to_string(), "a\n".to_string())
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("couldn't be saved for undo_edit"));
+        let expected = LineEnding::platform().apply("a\n");
+        assert_eq!(std::fs::read_to_string(&lf_file).unwrap(), expected);
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_text_editor_nonexistent_file() {
-        let editor = TextEditor::new();
-        let result = editor.view("/nonexistent/file.txt".to_string()).await;
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert!(e.to_string().contains("does not exist"));
-        }
-    }
-
-    #[tokio::test]
-    async fn test_text_editor_with_ignore_patterns() {
+    async fn test_append_jsonl() {
         let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("results.jsonl");
+        let path = test_file.to_string_lossy().to_string();
 
-        // Create ignore patterns
-        let mut builder = GitignoreBuilder::new(temp_dir.path());
-        builder.add_line(None, "secret.txt").unwrap();
-        builder.add_line(None, "*.env").unwrap();
-        let ignore_patterns = Arc::new(builder.build().unwrap());
-
-        let editor = TextEditor::new().with_ignore_patterns(ignore_patterns);
-
-        // Create ignored files
-        let secret_file = temp_dir.path().join("secret.txt");
-        let env_file = temp_dir.path().join("test.env");
-        let normal_file = temp_dir.path().join("normal.txt");
-
-        // Try to write to ignored files
-        let result = editor
-            .write(
-                secret_file.to_string_lossy().to_string(),
-                "secret content".to_string(),
-            )
-            .await;
-        assert!(
-            result.is_err(),
-            "Should not be able to write to ignored file"
+        let editor = TextEditor::new();
+        editor
+            .append_jsonl(path.clone(), r#"{"step": 1,  "ok": true}"#.to_string())
+            .await
+            .unwrap();
+        editor
+            .append_jsonl(path.clone(), r#"{"step": 2}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "{\"ok\":true,\"step\":1}\n{\"step\":2}\n"
         );
-        if let Err(e) = result {
-            assert!(e.to_string().contains("restricted by ignore patterns"));
-        }
 
+        // Invalid and multi-line records are rejected
+        let result = editor.append_jsonl(path.clone(), "{oops".to_string()).await;
+        assert!(result.unwrap_err().to_string().contains("not valid JSON"));
         let result = editor
-            .write(
-                env_file.to_string_lossy().to_string(),
-                "env content".to_string(),
-            )
+            .append_jsonl(path.clone(), "{\n\"step\": 3\n}".to_string())
             .await;
         assert!(
-            result.is_err(),
-            "Should not be able to write to ignored file"
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("embedded newlines")
         );
 
-        // Should be able to write to normal file
-        let result = editor
-            .write(
-                normal_file.to_string_lossy().to_string(),
-                "normal content".to_string(),
-            )
-            .await;
-        assert!(result.is_ok(), "Should be able to write to normal file");
-
-        // Create the secret file externally and try to view it
-        std::fs::write(&secret_file, "secret content").unwrap();
-        let result = editor.view(secret_file.to_string_lossy().to_string()).await;
-        assert!(result.is_err(), "Should not be able to view ignored file");
+        // A truncated last line is terminated before appending
+        std::fs::write(&test_file, "{\"step\":1}").unwrap();
+        editor
+            .append_jsonl(path.clone(), "[2]".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&test_file).unwrap(),
+            "{\"step\":1}\n[2]\n"
+        );
 
-        // Should be able to view normal file
-        let result = editor.view(normal_file.to_string_lossy().to_string()).await;
-        assert!(result.is_ok(), "Should be able to view normal file");
+        // Appends are undoable
+        editor.undo_edit(path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "{\"step\":1}");
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_write_undo_functionality() {
+    async fn test_utf16_round_trip() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
+        let test_file = temp_dir.path().join("notes.txt");
+        let path = test_file.to_string_lossy().to_string();
+        let utf16le = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+        std::fs::write(&test_file, utf16le("héllo\nworld\n")).unwrap();
 
         let editor = TextEditor::new();
+        let result = editor.view(path.clone()).await.unwrap();
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .contains("héllo\nworld")
+        );
 
-        // Write initial content
+        editor
+            .str_replace(path.clone(), "world".to_string(), "wörld".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(&test_file).unwrap(),
+            utf16le("héllo\nwörld\n")
+        );
+        let result = editor.check_unchanged(path.clone()).await.unwrap();
+        assert!(
+            result.content[0]
+                .as_text()
+                .unwrap()
+                .text
+                .contains("unchanged")
+        );
+
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(
+            std::fs::read(&test_file).unwrap(),
+            utf16le("héllo\nworld\n")
+        );
+
+        // Undoing a delete brings the file back as UTF-16 with its byte order mark
+        editor.delete(path.clone(), false).await.unwrap();
+        editor.undo_edit(path.clone()).await.unwrap();
+        assert_eq!(
+            std::fs::read(&test_file).unwrap(),
+            utf16le("héllo\nworld\n")
+        );
+
+        // Files without a byte order mark need the encoding named
+        let latin1_file = temp_dir.path().join("latin1.txt");
+        std::fs::write(&latin1_file, b"caf\xE9\n").unwrap();
+        assert!(
+            editor
+                .view(latin1_file.to_string_lossy().to_string())
+                .await
+                .is_err()
+        );
+        editor.set_encoding(&latin1_file, "latin1").unwrap();
         editor
             .write(
-                test_file.to_string_lossy().to_string(),
-                "Initial content".to_string(),
+                latin1_file.to_string_lossy().to_string(),
+                "café au lait\n".to_string(),
             )
             .await
             .unwrap();
+        assert_eq!(std::fs::read(&latin1_file).unwrap(), b"caf\xE9 au lait\n");
+        assert!(editor.set_encoding(&latin1_file, "klingon").is_err());
 
-        // Write new content (should be undoable)
+        // Deleting the file forgets its encoding, a new file there starts as UTF-8
+        editor
+            .delete(latin1_file.to_string_lossy().to_string(), false)
+            .await
+            .unwrap();
         editor
             .write(
-                test_file.to_string_lossy().to_string(),
-                "New content".to_string(),
+                latin1_file.to_string_lossy().to_string(),
+                "café
+"
+                .to_string(),
             )
             .await
             .unwrap();
+        assert_eq!(
+            std::fs::read(&latin1_file).unwrap(),
+            "café
+"
+            .as_bytes()
+        );
 
-        // Verify new content
-        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
-        let call_result = view_result.unwrap();
-        let text = call_result.content[0].as_text().unwrap();
-        assert!(text.text.contains("New content"));
-
-        // Undo the write
-        let undo_result = editor
-            .undo_edit(test_file.to_string_lossy().to_string())
-            .await;
-        assert!(undo_result.is_ok());
-
-        // Verify content reverted
-        let view_result = editor.view(test_file.to_string_lossy().to_string()).await;
-        let call_result = view_result.unwrap();
-        let text = call_result.content[0].as_text().unwrap();
-        assert!(text.text.contains("Initial content"));
+        // Choices for files that are gone make room, existing files don't
+        let editor = TextEditor::new();
+        for index in 0..MAX_CHOSEN_ENCODINGS {
+            let file = temp_dir.path().join(format!("chosen{index}.txt"));
+            std::fs::write(&file, "").unwrap();
+            editor.set_encoding(&file, "latin1").unwrap();
+        }
+        let extra = temp_dir.path().join("extra.txt");
+        assert!(editor.set_encoding(&extra, "latin1").is_err());
+        std::fs::remove_file(temp_dir.path().join("chosen0.txt")).unwrap();
+        editor.set_encoding(&extra, "latin1").unwrap();
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_write_to_directory_error() {
+    async fn test_overwrite_is_atomic_and_keeps_permissions() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let dir_path = temp_dir.path().join("test_dir");
-        std::fs::create_dir(&dir_path).unwrap();
+        let test_file = temp_dir.path().join("script.sh");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "echo old\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&test_file, std::fs::Permissions::from_mode(0o750)).unwrap();
+        }
 
         let editor = TextEditor::new();
-
-        // Try to write to a directory
-        let result = editor
-            .write(
-                dir_path.to_string_lossy().to_string(),
-                "content".to_string(),
-            )
-            .await;
-
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert!(e.to_string().contains("is an existing directory"));
+        editor
+            .write(path.clone(), "echo new\n".to_string())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "echo new\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&test_file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
         }
 
+        // No temp files are left next to the target
+        let entries: Vec<_> = std::fs::read_dir(temp_dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_write_character_limit() {
+    async fn test_stat() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
-
-        let editor = TextEditor::new();
+        let test_file = temp_dir.path().join("main.rs");
+        std::fs::write(&test_file, "fn main() {}\n// é\nlast").unwrap();
 
-        // Create content exceeding the character limit
-        let large_content = "x".repeat(MAX_WRITE_CHAR_COUNT + 1);
+        let editor = TextEditor::new().with_max_chars(10);
+        let result = editor
+            .stat(test_file.to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(report["bytes"], 23);
+        assert_eq!(report["lines"], 3);
+        assert_eq!(report["chars"], 22);
+        assert_eq!(report["language"], "rust");
+        assert!(report["modified_unix_secs"].as_u64().unwrap() > 0);
+        // Too many characters for view, which stat still measures
+        assert_eq!(report["viewable"], false);
 
         let result = editor
-            .write(test_file.to_string_lossy().to_string(), large_content)
+            .stat(temp_dir.path().to_string_lossy().to_string())
             .await;
-
         assert!(result.is_err());
-        if let Err(e) = result {
-            assert!(e.to_string().contains("too many characters"));
-        }
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_history_limit() {
+    async fn test_view_lines() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
+        let log = temp_dir.path().join("server.log");
+        let path = log.to_string_lossy().to_string();
+        let lines: Vec<String> = (1..=100).map(|n| format!("line {n}")).collect();
+        std::fs::write(&log, lines.join("\r\n") + "\r\n").unwrap();
 
-        // Create editor with small history limit
-        let editor = TextEditor::new_with_history_limit(2);
+        // Larger than the file size limit, which streaming doesn't need
+        let mut editor = TextEditor::new();
+        editor.max_file_bytes = 100;
+        assert!(editor.read_text(&log).is_err());
+        let text = |result: &CallToolResult| result.content[0].as_text().unwrap().text.clone();
 
-        // Write initial content
-        editor
-            .write(
-                test_file.to_string_lossy().to_string(),
-                "Content 1".to_string(),
-            )
+        let result = editor
+            .view_lines(path.clone(), LineWindow::Tail(2))
             .await
             .unwrap();
+        assert!(text(&result).contains("(last 2 of 100 lines, partial view)"));
+        assert!(text(&result).contains("```\nline 99\nline 100\n```"));
 
-        // Make multiple edits to exceed history limit
-        for i in 2..=5 {
+        let result = editor
+            .view_lines(path.clone(), LineWindow::Head(3))
+            .await
+            .unwrap();
+        assert!(text(&result).contains("(first 3 lines, partial view)"));
+        assert!(text(&result).contains("```\nline 1\nline 2\nline 3\n```"));
+
+        let result = editor
+            .view_lines(path.clone(), LineWindow::Head(500))
+            .await
+            .unwrap();
+        assert!(text(&result).contains("(all 100 lines)"));
+
+        assert!(
             editor
-                .str_replace(
-                    test_file.to_string_lossy().to_string(),
-                    format!("Content {prev}", prev = i - 1),
-                    format!("Content {i}"),
-                )
+                .view_lines(path.clone(), LineWindow::Tail(0))
                 .await
-                .unwrap();
-        }
+                .is_err()
+        );
 
-        // Should only be able to undo 2 times (the limit)
-        for _ in 0..2 {
-            let undo_result = editor
-                .undo_edit(test_file.to_string_lossy().to_string())
-                .await;
-            assert!(undo_result.is_ok());
+        // The window has to fit the character limit, an overlong line is skipped unread once
+        // the tail moves past it
+        let mut content = "x".repeat(10_000);
+        content.push_str("\nshort\nlines\n");
+        std::fs::write(&log, content).unwrap();
+        editor.max_chars = 20;
+        let result = editor
+            .view_lines(path.clone(), LineWindow::Tail(2))
+            .await
+            .unwrap();
+        assert!(text(&result).contains("```\nshort\nlines\n```"));
+        for window in [LineWindow::Head(1), LineWindow::Tail(3)] {
+            let error = editor.view_lines(path.clone(), window).await.unwrap_err();
+            assert!(error.to_string().contains("more than 20 characters"));
         }
 
-        // Third undo should fail
-        let undo_result = editor
-            .undo_edit(test_file.to_string_lossy().to_string())
-            .await;
-        assert!(undo_result.is_err());
-        if let Err(e) = undo_result {
-            assert!(e.to_string().contains("No edit history available"));
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_view_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("generated.rs");
+        let path = source.to_string_lossy().to_string();
+        let functions: Vec<String> = (0..40).map(|n| format!("fn f{n}() {{}}\n")).collect();
+        let content = functions.join("\n");
+        std::fs::write(&source, &content).unwrap();
+
+        let mut editor = TextEditor::new();
+        editor.max_file_bytes = 100;
+
+        // Oversized files are paged through from the start, one chunk per call
+        let mut result = editor.view(path.clone()).await.unwrap();
+        let mut offset = 0;
+        let mut pieces = String::new();
+        loop {
+            let report: serde_json::Value =
+                serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+            assert_eq!(report["offset"], offset);
+            assert_eq!(report["total_bytes"], content.len());
+            let end = report["end"].as_u64().unwrap();
+            assert!(end - offset <= 100);
+            pieces.push_str(&content[offset as usize..end as usize]);
+            // Chunks end between functions
+            assert!(content[..end as usize].ends_with("\n\n") || end as usize == content.len());
+            let Some(next_offset) = report["next_offset"].as_u64() else {
+                assert!(
+                    result.content[0]
+                        .as_text()
+                        .unwrap()
+                        .text
+                        .contains("end of file")
+                );
+                break;
+            };
+            assert!(
+                result.content[0]
+                    .as_text()
+                    .unwrap()
+                    .text
+                    .contains(&format!("continue with offset {next_offset}"))
+            );
+            offset = next_offset;
+            result = editor.view_chunk(path.clone(), offset).await.unwrap();
         }
+        assert_eq!(pieces, content);
+
+        assert!(editor.view_chunk(path.clone(), 100_000).await.is_err());
+
+        // Offsets inside a multi-byte character are rejected
+        std::fs::write(&source, "é".repeat(100)).unwrap();
+        assert!(editor.view_chunk(path.clone(), 1).await.is_err());
+        let result = editor.view_chunk(path.clone(), 0).await.unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&result.content[1].as_text().unwrap().text).unwrap();
+        assert_eq!(report["end"], 100);
 
         temp_dir.close().unwrap();
     }
 
     #[tokio::test]
-    async fn test_undo_write_to_new_file() {
+    async fn test_view_many() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let test_file = temp_dir.path().join("new_file.txt");
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("a.rs"), "fn a() {}").unwrap();
+        std::fs::write(dir.join("b.py"), "print('b')").unwrap();
+        std::fs::write(dir.join("big.txt"), "x".repeat(20)).unwrap();
+        std::fs::write(dir.join("secret.txt"), "hidden").unwrap();
 
-        let editor = TextEditor::new();
+        let mut builder = ignore::gitignore::GitignoreBuilder::new(dir);
+        builder.add_line(None, "secret.txt").unwrap();
+        let editor = TextEditor::new()
+            .with_max_chars(25)
+            .with_ignore_patterns(builder.build().unwrap().into());
 
-        // Write to a new file
-        editor
-            .write(
-                test_file.to_string_lossy().to_string(),
-                "New file content".to_string(),
-            )
+        let result = editor
+            .view_many(vec![
+                Ok(dir.join("a.rs")),
+                Ok(dir.join("b.py")),
+                Ok(dir.join("a.rs")),
+                Ok(dir.join("big.txt")),
+                Ok(dir.join("secret.txt")),
+                Ok(dir.join("missing.txt")),
+                Err((
+                    "relative.txt".to_string(),
+                    McpError::invalid_params("relative.txt is not an absolute path", None),
+                )),
+            ])
             .await
             .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(&format!(
+            "### {}\n```rust\nfn a() {{}}\n```",
+            dir.join("a.rs").display()
+        )));
+        assert!(text.contains("print('b')"));
+        assert_eq!(text.matches("fn a() {}").count(), 1);
+        // 20 more characters would pass the combined limit of 25
+        assert!(text.contains("would exceed the combined limit"));
+        assert!(text.contains("restricted by ignore patterns"));
+        assert!(text.contains("does not exist"));
+        assert!(text.contains("- relative.txt: relative.txt is not an absolute path"));
+        assert!(text.contains("Skipped 4 files"));
+        assert_eq!(
+            result.content[1].as_text().unwrap().text,
+            "Viewed 2 files, skipped 4"
+        );
 
-        // Verify file exists and has content
-        assert!(test_file.exists());
-        let content = std::fs::read_to_string(&test_file).unwrap();
-        assert_eq!(content, "New file content");
+        assert!(editor.view_many(vec![]).await.is_err());
 
-        // Undo the write
-        let undo_result = editor
-            .undo_edit(test_file.to_string_lossy().to_string())
-            .await;
-        assert!(undo_result.is_ok());
+        temp_dir.close().unwrap();
+    }
 
-        // File should now be empty (representing the non-existent state)
-        let content = std::fs::read_to_string(&test_file).unwrap();
-        assert!(content.is_empty());
+    #[tokio::test]
+    async fn test_write_requires_view() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let test_file = temp_dir.path().join("config.toml");
+        let path = test_file.to_string_lossy().to_string();
+        std::fs::write(&test_file, "original").unwrap();
+
+        let editor = TextEditor::new().with_require_view_before_write(true);
+
+        // An unseen existing file can't be replaced
+        let result = editor.write(path.clone(), "blind".to_string()).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("hasn't been viewed in this session")
+        );
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "original");
+
+        // Viewing it first allows the write
+        editor.view(path.clone()).await.unwrap();
+        editor
+            .write(path.clone(), "viewed".to_string())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&test_file).unwrap(), "viewed");
+
+        // So does an explicit overwrite
+        let other_file = temp_dir.path().join("other.toml");
+        let other_path = other_file.to_string_lossy().to_string();
+        std::fs::write(&other_file, "original").unwrap();
+        let overwrite = WriteOptions {
+            overwrite: true,
+            ..Default::default()
+        };
+        editor
+            .write_with_options(other_path.clone(), "forced".to_string(), overwrite)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&other_file).unwrap(), "forced");
+
+        // New files, and files already written this session, need no view
+        let new_path = temp_dir
+            .path()
+            .join("new.toml")
+            .to_string_lossy()
+            .to_string();
+        editor
+            .write(new_path.clone(), "one".to_string())
+            .await
+            .unwrap();
+        editor.write(new_path, "two".to_string()).await.unwrap();
+
+        // Without the guard, writes behave as before
+        std::fs::write(&other_file, "original").unwrap();
+        TextEditor::new()
+            .write(other_path, "unguarded".to_string())
+            .await
+            .unwrap();
 
         temp_dir.close().unwrap();
     }
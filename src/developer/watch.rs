@@ -0,0 +1,261 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rmcp::{Error as McpError, Peer, RoleServer, model::ResourceUpdatedNotificationParam};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default number of files watched at once, each watcher holds an OS handle
+pub const DEFAULT_MAX_WATCHERS: usize = 32;
+
+// Changes this close together are reported once, e.g. a build writing a file in bursts
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// Each client connection watches its own subscriptions
+type WatchKey = (u64, String);
+
+// A subscription's watcher, dropping it stops the notifications
+struct Watch {
+    // Tells a replaced subscription apart from the one that replaced it
+    id: u64,
+    _watcher: RecommendedWatcher,
+}
+
+/// Watches files clients subscribed to and tells them when one changes on disk
+#[derive(Clone)]
+pub struct FileWatcher {
+    // Keyed by client connection and resource URI
+    watchers: Arc<Mutex<HashMap<WatchKey, Watch>>>,
+    next_id: Arc<AtomicU64>,
+    max_watchers: usize,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self {
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+            max_watchers: DEFAULT_MAX_WATCHERS,
+        }
+    }
+
+    pub fn with_max_watchers(mut self, max_watchers: usize) -> Self {
+        self.max_watchers = max_watchers;
+        self
+    }
+
+    /// Send `notifications/resources/updated` for `uri` to `peer`, the client of `connection`,
+    /// whenever `path` changes. The subscription ends once a notification can't be delivered
+    pub fn watch(
+        &self,
+        connection: u64,
+        uri: String,
+        path: PathBuf,
+        peer: Peer<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.watch_with((connection, uri), path, move |uri| {
+            let peer = peer.clone();
+            async move {
+                let notification = ResourceUpdatedNotificationParam { uri };
+                match peer.notify_resource_updated(notification).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::debug!("Failed to send resource update: {e}");
+                        false
+                    }
+                }
+            }
+        })
+    }
+
+    // Call `on_change` with the URI after each settled burst of changes, until it returns false
+    // and the subscription is dropped. The file itself may not exist yet, but its directory must
+    fn watch_with<F, Fut>(&self, key: WatchKey, path: PathBuf, on_change: F) -> Result<(), McpError>
+    where
+        F: Fn(String) -> Fut + Send + 'static,
+        Fut: Future<Output = bool> + Send,
+    {
+        let mut watchers = self.watchers.lock().unwrap();
+        if !watchers.contains_key(&key) && watchers.len() >= self.max_watchers {
+            return Err(McpError::invalid_request(
+                format!(
+                    "Already watching {} files, unsubscribe from one first",
+                    self.max_watchers
+                ),
+                None,
+            ));
+        }
+        let dir = path.parent().filter(|dir| dir.is_dir()).ok_or_else(|| {
+            McpError::invalid_params(
+                format!(
+                    "Can't watch '{display}', its directory does not exist",
+                    display = path.display()
+                ),
+                None,
+            )
+        })?;
+
+        // Editors and build tools often replace a file rather than writing it in place, so
+        // the directory is watched and its events filtered down to the file
+        let (changes, mut changed) = tokio::sync::mpsc::unbounded_channel();
+        let watched = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if !matches!(event.kind, EventKind::Access(_)) && event.paths.contains(&watched) {
+                let _ = changes.send(());
+            }
+        })
+        .map_err(|e| McpError::internal_error(format!("Failed to create watcher: {e}"), None))?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| {
+                McpError::internal_error(
+                    format!("Failed to watch '{display}': {e}", display = path.display()),
+                    None,
+                )
+            })?;
+
+        // Ends once the watcher, and with it the sending half, is dropped. A client that stopped
+        // listening gives up its slot, unless it has subscribed again meanwhile
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let subscriptions = Arc::downgrade(&self.watchers);
+        let subscription = key.clone();
+        tokio::spawn(async move {
+            while changed.recv().await.is_some() {
+                tokio::time::sleep(DEBOUNCE).await;
+                while changed.try_recv().is_ok() {}
+                if !on_change(subscription.1.clone()).await {
+                    let Some(subscriptions) = subscriptions.upgrade() else {
+                        break;
+                    };
+                    let mut subscriptions = subscriptions.lock().unwrap();
+                    if subscriptions
+                        .get(&subscription)
+                        .is_some_and(|watch| watch.id == id)
+                    {
+                        subscriptions.remove(&subscription);
+                    }
+                    break;
+                }
+            }
+        });
+
+        // Subscribing again replaces the watcher, e.g. for a reconnected client
+        watchers.insert(
+            key,
+            Watch {
+                id,
+                _watcher: watcher,
+            },
+        );
+        Ok(())
+    }
+
+    /// Stop watching `uri` for `connection`, false if it wasn't watched
+    pub fn unwatch(&self, connection: u64, uri: &str) -> bool {
+        self.watchers
+            .lock()
+            .unwrap()
+            .remove(&(connection, uri.to_string()))
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_notifies_on_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        let target = dir.join("output.txt");
+        let uri = "file:///output.txt".to_string();
+        let watcher = FileWatcher::new().with_max_watchers(1);
+
+        let (updates, mut updated) = tokio::sync::mpsc::unbounded_channel();
+        watcher
+            .watch_with((1, uri.clone()), target.clone(), move |uri| {
+                let updates = updates.clone();
+                async move { updates.send(uri).is_ok() }
+            })
+            .unwrap();
+
+        // Other files in the directory are ignored, a burst of writes is one update
+        std::fs::write(dir.join("other.txt"), "noise").unwrap();
+        std::fs::write(&target, "first").unwrap();
+        std::fs::write(&target, "second").unwrap();
+        let update = tokio::time::timeout(Duration::from_secs(5), updated.recv())
+            .await
+            .unwrap();
+        assert_eq!(update, Some(uri.clone()));
+        tokio::time::sleep(DEBOUNCE * 2).await;
+        assert!(updated.try_recv().is_err());
+
+        // The cap counts distinct subscriptions, the same URI from another connection is another one
+        let result = watcher.watch_with(
+            (1, "file:///other".to_string()),
+            target.clone(),
+            |_| async { true },
+        );
+        assert!(result.is_err());
+        let result = watcher.watch_with((2, uri.clone()), target, |_| async { true });
+        assert!(result.is_err());
+
+        assert!(!watcher.unwatch(2, &uri));
+        assert!(watcher.unwatch(1, &uri));
+        assert!(!watcher.unwatch(1, &uri));
+        // Dropping the watcher closes the channel
+        let closed = tokio::time::timeout(Duration::from_secs(5), updated.recv())
+            .await
+            .unwrap();
+        assert_eq!(closed, None);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_dropped_when_delivery_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        let target = dir.join("output.txt");
+        let watcher = FileWatcher::new();
+
+        let (attempts, mut attempted) = tokio::sync::mpsc::unbounded_channel();
+        watcher
+            .watch_with(
+                (1, "file:///output.txt".to_string()),
+                target.clone(),
+                move |_| {
+                    let _ = attempts.send(());
+                    async { false }
+                },
+            )
+            .unwrap();
+        std::fs::write(&target, "changed").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), attempted.recv())
+            .await
+            .unwrap();
+
+        // The subscription is gone once its task gives up
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !watcher.watchers.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        temp_dir.close().unwrap();
+    }
+}
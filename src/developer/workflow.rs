@@ -1,6 +1,7 @@
 use rmcp::{Error as McpError, model::CallToolResult, model::Content};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -17,6 +18,32 @@ pub struct WorkflowStep {
     pub needs_more_steps: Option<bool>,
 }
 
+/// Number of recent steps included in a verbose status
+const VERBOSE_RECENT_STEPS: usize = 5;
+
+/// How much detail the workflow status response carries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusVerbosity {
+    /// A single-line summary, cheap for frequent step calls
+    Compact,
+    /// The standard JSON status
+    #[default]
+    Normal,
+    /// The standard status plus recent step and branch contents
+    Verbose,
+}
+
+impl StatusVerbosity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "compact" => Some(Self::Compact),
+            "normal" => Some(Self::Normal),
+            "verbose" => Some(Self::Verbose),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct WorkflowStatus {
     step_number: i32,
@@ -28,6 +55,37 @@ struct WorkflowStatus {
     step_history_length: usize,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct StepSummary {
+    step_number: i32,
+    step_description: String,
+}
+
+impl From<&WorkflowStep> for StepSummary {
+    fn from(step: &WorkflowStep) -> Self {
+        Self {
+            step_number: step.step_number,
+            step_description: step.step_description.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VerboseWorkflowStatus {
+    #[serde(flatten)]
+    status: WorkflowStatus,
+    recent_steps: Vec<StepSummary>,
+    branch_steps: HashMap<String, Vec<StepSummary>>,
+}
+
+/// Everything recorded so far, for re-orienting after a long session
+#[derive(Debug, Serialize)]
+struct WorkflowHistory<'a> {
+    current_branch: Option<&'a str>,
+    step_history: &'a [WorkflowStep],
+    branches: BTreeMap<&'a str, &'a [WorkflowStep]>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct WorkflowState {
     step_history: Vec<WorkflowStep>,
@@ -35,12 +93,47 @@ struct WorkflowState {
     current_branch: Option<String>,
 }
 
+impl WorkflowState {
+    // A missing file starts fresh, and so does a corrupt one after a warning
+    fn load(path: &Path) -> Self {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to read workflow state, starting fresh");
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Corrupt workflow state, starting fresh");
+            Self::default()
+        })
+    }
+
+    // Written to a sibling file first so a crash never leaves a half-written state
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        std::fs::write(&temp_path, json)?;
+        std::fs::rename(&temp_path, path)
+    }
+}
+
 #[derive(Clone)]
 pub struct Workflow {
     state: Arc<Mutex<WorkflowState>>,
     allow_branches: bool,
     max_steps: Option<i32>,
     log_steps: bool,
+    // Where the state is saved after each step, so it survives restarts
+    state_file: Option<PathBuf>,
 }
 
 impl Default for Workflow {
@@ -50,6 +143,7 @@ impl Default for Workflow {
             allow_branches: true,
             max_steps: None,
             log_steps: true,
+            state_file: None,
         }
     }
 }
@@ -61,10 +155,27 @@ impl Workflow {
             allow_branches,
             max_steps,
             log_steps,
+            state_file: None,
         }
     }
 
+    /// Restore the state saved in `path`, and save it there after every step
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state = Arc::new(Mutex::new(WorkflowState::load(&path)));
+        self.state_file = Some(path);
+        self
+    }
+
     pub async fn execute_step(&self, args: WorkflowStep) -> Result<CallToolResult, McpError> {
+        self.execute_step_with_verbosity(args, StatusVerbosity::default())
+            .await
+    }
+
+    pub async fn execute_step_with_verbosity(
+        &self,
+        args: WorkflowStep,
+        verbosity: StatusVerbosity,
+    ) -> Result<CallToolResult, McpError> {
         // Optional: Log the received arguments at the beginning
         if self.log_steps {
             tracing::debug!(workflow_step_args = ?args, "Workflow step arguments received");
@@ -107,8 +218,26 @@ impl Workflow {
             return Ok(Self::error(error_msg));
         }
 
-        if step_data.branch_id.is_some() && step_data.branch_from_step.is_none() {
-            let error_msg = "When creating a branch (branch_id), you must specify branch_from_step";
+        if let Some(revises_step) = step_data.revises_step {
+            let history_length = state.step_history.len();
+            if revises_step <= 0 || revises_step as usize > history_length {
+                let error_msg = format!(
+                    "revises_step {revises_step} does not exist in step history (history length: {history_length})"
+                );
+                if self.log_steps {
+                    tracing::warn!(error_msg, "Workflow revision validation error");
+                }
+                return Ok(Self::error(error_msg));
+            }
+        }
+
+        // A branch_id alone re-enters an existing branch, a new one needs its starting point
+        let unknown_branch = step_data
+            .branch_id
+            .as_ref()
+            .is_some_and(|branch_id| !state.branches.contains_key(branch_id));
+        if unknown_branch && step_data.branch_from_step.is_none() {
+            let error_msg = "When creating a branch (branch_id), you must specify branch_from_step. Only existing branches can be re-entered without it";
             if self.log_steps {
                 tracing::warn!(error_msg, invalid_branch_args = ?step_data, "Workflow step validation error");
             }
@@ -144,6 +273,22 @@ impl Workflow {
                 );
             }
 
+            state.current_branch = Some(branch_id.clone());
+            state
+                .branches
+                .entry(branch_id.clone())
+                .or_default()
+                .push(step_data.clone());
+        } else if let Some(branch_id) = &step_data.branch_id {
+            if self.log_steps {
+                tracing::info!(
+                    branch_id,
+                    previous_branch = ?state.current_branch,
+                    step_number = step_data.step_number,
+                    "Re-entering existing branch."
+                );
+            }
+
             state.current_branch = Some(branch_id.clone());
             state
                 .branches
@@ -163,6 +308,12 @@ impl Workflow {
 
         state.step_history.push(step_data.clone());
 
+        if let Some(state_file) = &self.state_file {
+            state.save(state_file).unwrap_or_else(|e| {
+                tracing::warn!(path = %state_file.display(), error = %e, "Failed to save workflow state");
+            });
+        }
+
         // Log before returning success
         if self.log_steps {
             tracing::info!(
@@ -180,7 +331,35 @@ impl Workflow {
 
         let response_status = self.build_workflow_status(&state, &step_data).await;
 
-        match serde_json::to_string_pretty(&response_status) {
+        let response = match verbosity {
+            StatusVerbosity::Compact => {
+                return Ok(Self::success(Self::compact_status(&response_status)));
+            }
+            StatusVerbosity::Normal => serde_json::to_string_pretty(&response_status),
+            StatusVerbosity::Verbose => {
+                let verbose_status = VerboseWorkflowStatus {
+                    status: response_status,
+                    recent_steps: state
+                        .step_history
+                        .iter()
+                        .rev()
+                        .take(VERBOSE_RECENT_STEPS)
+                        .rev()
+                        .map(StepSummary::from)
+                        .collect(),
+                    branch_steps: state
+                        .branches
+                        .iter()
+                        .map(|(id, steps)| {
+                            (id.clone(), steps.iter().map(StepSummary::from).collect())
+                        })
+                        .collect(),
+                };
+                serde_json::to_string_pretty(&verbose_status)
+            }
+        };
+
+        match response {
             Ok(json_response) => Ok(Self::success(json_response)),
             Err(e) => {
                 // Also log serialization errors
@@ -192,6 +371,57 @@ impl Workflow {
         }
     }
 
+    /// The full step history and every branch as pretty JSON, leaving the state untouched
+    pub async fn history(&self) -> Result<CallToolResult, McpError> {
+        let state = self.state.lock().await;
+        let history = WorkflowHistory {
+            current_branch: state.current_branch.as_deref(),
+            step_history: &state.step_history,
+            branches: state
+                .branches
+                .iter()
+                .map(|(id, steps)| (id.as_str(), steps.as_slice()))
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&history).map_err(|e| {
+            McpError::internal_error(format!("Failed to serialize workflow history: {e}"), None)
+        })?;
+        Ok(Self::success(json))
+    }
+
+    /// Clear every step and branch to start a new workflow, saving the empty state
+    pub async fn reset(&self) -> Result<CallToolResult, McpError> {
+        let mut state = self.state.lock().await;
+        let cleared_steps = state.step_history.len();
+        let cleared_branches = state.branches.len();
+        *state = WorkflowState::default();
+
+        if let Some(state_file) = &self.state_file {
+            state.save(state_file).map_err(|e| {
+                McpError::internal_error(format!("Failed to save workflow state: {e}"), None)
+            })?;
+        }
+        if self.log_steps {
+            tracing::info!(cleared_steps, cleared_branches, "Workflow reset.");
+        }
+        Ok(Self::success(format!(
+            "Workflow reset, cleared {cleared_steps} steps and {cleared_branches} branches"
+        )))
+    }
+
+    // Single-line form of the status for high-frequency callers
+    fn compact_status(status: &WorkflowStatus) -> String {
+        format!(
+            "step {step}/{total} | next_step_needed: {next} | branch: {branch} | branches: {branches} | history: {history}",
+            step = status.step_number,
+            total = status.total_steps,
+            next = status.next_step_needed,
+            branch = status.current_branch.as_deref().unwrap_or("main"),
+            branches = status.branches.len(),
+            history = status.step_history_length
+        )
+    }
+
     fn error(error_message: impl Into<String>) -> CallToolResult {
         CallToolResult::error(vec![Content::text(error_message.into())])
     }
@@ -361,4 +591,210 @@ mod tests {
         let result = tool.execute_step(branch_step).await.unwrap();
         assert!(result.is_error == Some(true));
     }
+
+    fn simple_step(step_number: i32, description: &str) -> WorkflowStep {
+        WorkflowStep {
+            step_description: description.to_string(),
+            step_number,
+            total_steps: 3,
+            next_step_needed: true,
+            is_step_revision: None,
+            revises_step: None,
+            branch_from_step: None,
+            branch_id: None,
+            needs_more_steps: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_status() {
+        let tool = Workflow::new(true, None, false);
+        let result = tool
+            .execute_step_with_verbosity(simple_step(1, "First"), StatusVerbosity::Compact)
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains('\n'));
+        assert!(text.starts_with("step 1/3"));
+        assert!(text.contains("history: 1"));
+    }
+
+    #[tokio::test]
+    async fn test_verbose_status() {
+        let tool = Workflow::new(true, None, false);
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+        let mut branch_step = simple_step(2, "Branch work");
+        branch_step.branch_from_step = Some(1);
+        branch_step.branch_id = Some("alt".to_string());
+        tool.execute_step(branch_step).await.unwrap();
+
+        let result = tool
+            .execute_step_with_verbosity(simple_step(3, "Third"), StatusVerbosity::Verbose)
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        let status: VerboseWorkflowStatus = serde_json::from_str(text).unwrap();
+        assert_eq!(status.status.step_number, 3);
+        assert_eq!(status.recent_steps.len(), 3);
+        assert_eq!(status.recent_steps[0].step_description, "First");
+        assert_eq!(
+            status.branch_steps["alt"][0].step_description,
+            "Branch work"
+        );
+    }
+
+    #[test]
+    fn test_status_verbosity_parse() {
+        assert_eq!(
+            StatusVerbosity::parse("compact"),
+            Some(StatusVerbosity::Compact)
+        );
+        assert_eq!(
+            StatusVerbosity::parse("verbose"),
+            Some(StatusVerbosity::Verbose)
+        );
+        assert_eq!(StatusVerbosity::parse("loud"), None);
+    }
+
+    #[tokio::test]
+    async fn test_state_file_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state_file = temp_dir.path().join("workflow.json");
+
+        let tool = Workflow::new(true, None, false).with_state_file(state_file.clone());
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+        let mut branch_step = simple_step(2, "Branch work");
+        branch_step.branch_from_step = Some(1);
+        branch_step.branch_id = Some("alt".to_string());
+        tool.execute_step(branch_step).await.unwrap();
+
+        // A restarted server picks up where the last one stopped
+        let restored = Workflow::new(true, None, false).with_state_file(state_file.clone());
+        let state = restored.state.lock().await;
+        assert_eq!(state.step_history.len(), 2);
+        assert_eq!(state.current_branch.as_deref(), Some("alt"));
+        assert_eq!(state.branches["alt"][0].step_description, "Branch work");
+        drop(state);
+
+        // A corrupt file is replaced by a fresh state
+        std::fs::write(&state_file, "{ not json").unwrap();
+        let fresh = Workflow::new(true, None, false).with_state_file(state_file.clone());
+        assert!(fresh.state.lock().await.step_history.is_empty());
+        fresh.execute_step(simple_step(1, "Again")).await.unwrap();
+        let saved: WorkflowState =
+            serde_json::from_str(&std::fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert_eq!(saved.step_history.len(), 1);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let tool = Workflow::new(true, None, false);
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+        let mut revision = simple_step(2, "Better first");
+        revision.is_step_revision = Some(true);
+        revision.revises_step = Some(1);
+        tool.execute_step(revision).await.unwrap();
+        let mut branch_step = simple_step(3, "Branch work");
+        branch_step.branch_from_step = Some(2);
+        branch_step.branch_id = Some("alt".to_string());
+        tool.execute_step(branch_step).await.unwrap();
+
+        let result = tool.history().await.unwrap();
+        let history: serde_json::Value =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(history["current_branch"], "alt");
+        assert_eq!(history["step_history"].as_array().unwrap().len(), 3);
+        assert_eq!(history["step_history"][1]["revises_step"], 1);
+        assert_eq!(history["branches"]["alt"][0]["branch_from_step"], 2);
+
+        // Reading the history doesn't record anything
+        tool.history().await.unwrap();
+        assert_eq!(tool.state.lock().await.step_history.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state_file = temp_dir.path().join("workflow.json");
+        let tool = Workflow::new(true, None, false).with_state_file(state_file.clone());
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+        let mut branch_step = simple_step(2, "Branch work");
+        branch_step.branch_from_step = Some(1);
+        branch_step.branch_id = Some("alt".to_string());
+        tool.execute_step(branch_step).await.unwrap();
+
+        let result = tool.reset().await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Workflow reset, cleared 2 steps and 1 branches"
+        );
+
+        let result = tool.execute_step(simple_step(1, "New task")).await.unwrap();
+        let status: WorkflowStatus =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(status.step_history_length, 1);
+        assert!(status.branches.is_empty());
+        assert_eq!(status.current_branch, None);
+
+        // The saved file was cleared too
+        tool.reset().await.unwrap();
+        let saved: WorkflowState =
+            serde_json::from_str(&std::fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert_eq!(saved.step_history.len(), 0);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_revises_step_bounds() {
+        let tool = Workflow::new(true, None, false);
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+
+        for revises_step in [0, 99] {
+            let mut revision = simple_step(2, "Revision");
+            revision.is_step_revision = Some(true);
+            revision.revises_step = Some(revises_step);
+            let result = tool.execute_step(revision).await.unwrap();
+            assert_eq!(result.is_error, Some(true));
+            let text = &result.content[0].as_text().unwrap().text;
+            assert!(text.contains(&format!("revises_step {revises_step}")));
+            assert!(text.contains("history length: 1"));
+        }
+
+        let mut revision = simple_step(2, "Revision");
+        revision.is_step_revision = Some(true);
+        revision.revises_step = Some(1);
+        let result = tool.execute_step(revision).await.unwrap();
+        assert_ne!(result.is_error, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_reenter_branch() {
+        let tool = Workflow::new(true, None, false);
+        tool.execute_step(simple_step(1, "First")).await.unwrap();
+        let mut branch_step = simple_step(2, "Branch work");
+        branch_step.branch_from_step = Some(1);
+        branch_step.branch_id = Some("alt".to_string());
+        tool.execute_step(branch_step).await.unwrap();
+        tool.execute_step(simple_step(3, "Back on main"))
+            .await
+            .unwrap();
+
+        // Naming an existing branch resumes it
+        let mut resume = simple_step(4, "More branch work");
+        resume.branch_id = Some("alt".to_string());
+        let result = tool.execute_step(resume).await.unwrap();
+        let status: WorkflowStatus =
+            serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap();
+        assert_eq!(status.current_branch.as_deref(), Some("alt"));
+        assert_eq!(tool.state.lock().await.branches["alt"].len(), 2);
+
+        // An unknown branch still needs branch_from_step
+        let mut unknown = simple_step(5, "Elsewhere");
+        unknown.branch_id = Some("missing".to_string());
+        let result = tool.execute_step(unknown).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+    }
 }
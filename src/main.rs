@@ -1,7 +1,18 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use rmcp::{ServiceExt, transport::stdio};
-use tracing_subscriber::{self, EnvFilter};
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use rmcp::model::LoggingLevel;
+use rmcp::{
+    ServiceExt,
+    transport::{
+        SseServer, StreamableHttpService, stdio,
+        streamable_http_server::session::local::LocalSessionManager,
+    },
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt};
 
 pub mod developer;
 
@@ -12,12 +23,44 @@ pub mod developer;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// TOML file with server settings, environment variables take precedence
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Directory relative paths resolve against, and whose .gitignore applies
+    #[arg(long, value_name = "DIR")]
+    workspace: Option<PathBuf>,
+
+    /// How clients connect to the server
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+
+    /// Address to listen on for the sse and http transports
+    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    host: IpAddr,
+
+    /// Port to listen on for the sse and http transports
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Transport {
+    /// Serve a single client over stdin and stdout
+    Stdio,
+    /// Serve clients over HTTP with server-sent events at /sse and posts to /message
+    Sse,
+    /// Serve clients over streamable HTTP at /mcp
+    Http,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Output the tools JSON schema
     Toolbox,
+    /// Print each tool's name and description
+    ListTools,
 }
 
 #[tokio::main]
@@ -31,26 +74,79 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("{tools_schema}");
             return Ok(());
         }
+        Some(Commands::ListTools) => {
+            println!("{}", developer::Developer::get_tools_as_text());
+            return Ok(());
+        }
         None => {
             // Default behavior - start the MCP server
-            tracing_subscriber::fmt()
-                .with_env_filter(
-                    EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()),
+            // The filter is reloadable so clients can change the level with logging/setLevel
+            let (filter, log_filter) =
+                reload::Layer::new(developer::logging::filter_for(LoggingLevel::Debug));
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(std::io::stderr)
+                        .with_ansi(false),
                 )
-                .with_writer(std::io::stderr)
-                .with_ansi(false)
                 .init();
 
             tracing::info!("Starting MCP server");
 
-            let server = developer::Developer::new();
+            let config = match &cli.config {
+                Some(path) => developer::Config::retrieve(path)
+                    .await
+                    .with_context(|| format!("Failed to load config file {}", path.display()))?,
+                None => developer::Config::default(),
+            };
+            let mut server = developer::Developer::with_config(config).with_log_filter(log_filter);
+            if let Some(workspace) = &cli.workspace {
+                let root = std::fs::canonicalize(workspace)
+                    .with_context(|| format!("Failed to open workspace {}", workspace.display()))?;
+                if !root.is_dir() {
+                    return Err(format!("Workspace {} is not a directory", root.display()).into());
+                }
+                server = server.with_workspace_root(root);
+            }
+            let bind = SocketAddr::new(cli.host, cli.port);
+
+            match cli.transport {
+                Transport::Stdio => {
+                    // Create an instance of our developer service
+                    let service = server.serve(stdio()).await.inspect_err(|e| {
+                        tracing::error!("serving error: {:?}", e);
+                    })?;
+
+                    service.waiting().await?;
+                }
+                Transport::Sse => {
+                    // Every connection shares the same tools and state
+                    let ct = SseServer::serve(bind)
+                        .await?
+                        .with_service(move || server.for_connection());
+                    tracing::info!(%bind, "Serving MCP over SSE at /sse");
 
-            // Create an instance of our developer service
-            let service = server.serve(stdio()).await.inspect_err(|e| {
-                tracing::error!("serving error: {:?}", e);
-            })?;
+                    tokio::signal::ctrl_c().await?;
+                    ct.cancel();
+                }
+                Transport::Http => {
+                    let service = StreamableHttpService::new(
+                        move || Ok(server.for_connection()),
+                        LocalSessionManager::default().into(),
+                        Default::default(),
+                    );
+                    let router = axum::Router::new().nest_service("/mcp", service);
+                    let listener = tokio::net::TcpListener::bind(bind).await?;
+                    tracing::info!(%bind, "Serving MCP over streamable HTTP at /mcp");
 
-            service.waiting().await?;
+                    axum::serve(listener, router)
+                        .with_graceful_shutdown(async {
+                            let _ = tokio::signal::ctrl_c().await;
+                        })
+                        .await?;
+                }
+            }
         }
     }
 